@@ -5,7 +5,7 @@ use log::{debug, error, info};
 use tauri::Window;
 use tokio::fs;
 
-use crate::{addons::progress::AddonsProgress, app::{api::ApiEndpoints, app_data::LauncherOptions, gui::FileData, modrinth_api::{Datapack, DatapackInfo, ModrinthApiEndpoints}}, error::{Error, ErrorKind}, utils::download_file};
+use crate::{addons::progress::AddonsProgress, app::{api::ApiEndpoints, app_data::LauncherOptions, gui::FileData, modrinth_api::{Datapack, DatapackInfo, ModrinthApiEndpoints}}, error::{Error, ErrorKind}, minecraft::progress::{ProgressTracker, ProgressUpdateSteps}, utils::download_file_to};
 
 pub struct DataPackManager {}
 
@@ -84,11 +84,13 @@ impl DataPackManager {
             if let Some(url) = &datapack.url {
                 info!("Downloading datapack {} from {}", &datapack.file_name, url);
                 
-                let retrieved_bytes = download_file(url, |a, b| {
-                    window.emit("addons-progress", AddonsProgress { identifier: datapack.slug.clone(), current: a, max: b }).unwrap();
+                let tracker = ProgressTracker::new(ProgressUpdateSteps::DownloadAddon, 1, 0);
+                tracker.set_current_file(&datapack.file_name);
+                download_file_to(url, &datapack_path, |a, b| {
+                    if let Some(state) = tracker.set_bytes(a, b) {
+                        window.emit("addons-progress", AddonsProgress::new(datapack.slug.clone(), state)).unwrap();
+                    }
                 }).await?;
-                
-                fs::write(&datapack_path, retrieved_bytes).await?;
                 info!("Installed Datapack {} in world {}", &datapack.file_name, &datapack.world_name);
             }
         }
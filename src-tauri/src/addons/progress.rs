@@ -1,8 +1,17 @@
 use serde::{Deserialize, Serialize};
 
+use crate::minecraft::progress::ProgressState;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct AddonsProgress {
     pub identifier: String,
     pub current: u64,
-    pub max: u64
-}
\ No newline at end of file
+    pub max: u64,
+    pub state: ProgressState,
+}
+
+impl AddonsProgress {
+    pub fn new(identifier: String, state: ProgressState) -> Self {
+        AddonsProgress { identifier, current: state.completed_bytes, max: state.total_bytes, state }
+    }
+}
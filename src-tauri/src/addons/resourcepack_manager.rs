@@ -5,7 +5,7 @@ use log::{debug, error, info};
 use tauri::Window;
 use tokio::fs;
 
-use crate::{addons::progress::AddonsProgress, app::{api::ApiEndpoints, app_data::LauncherOptions, gui::FileData, modrinth_api::{ModrinthApiEndpoints, ResourcePack, ResourcePackInfo}}, error::{Error, ErrorKind}, utils::download_file};
+use crate::{addons::progress::AddonsProgress, app::{api::ApiEndpoints, app_data::LauncherOptions, gui::FileData, modrinth_api::{ModrinthApiEndpoints, ResourcePack, ResourcePackInfo}}, error::{Error, ErrorKind}, minecraft::progress::{ProgressTracker, ProgressUpdateSteps}, utils::download_file_to};
 
 pub struct ResourcePackManager {}
 
@@ -81,11 +81,13 @@ impl ResourcePackManager {
             if let Some(url) = &resourcepack.url {
                 info!("Downloading resourcepack {} from {}", &resourcepack.file_name, url);
                 
-                let retrieved_bytes = download_file(url, |a, b| {
-                    window.emit("addons-progress", AddonsProgress { identifier: resourcepack.slug.clone(), current: a, max: b }).unwrap();
+                let tracker = ProgressTracker::new(ProgressUpdateSteps::DownloadAddon, 1, 0);
+                tracker.set_current_file(&resourcepack.file_name);
+                download_file_to(url, &resourcepack_path, |a, b| {
+                    if let Some(state) = tracker.set_bytes(a, b) {
+                        window.emit("addons-progress", AddonsProgress::new(resourcepack.slug.clone(), state)).unwrap();
+                    }
                 }).await?;
-                
-                fs::write(&resourcepack_path, retrieved_bytes).await?;
                 info!("Installed ResourcePack {}", &resourcepack.file_name);
             }
         }
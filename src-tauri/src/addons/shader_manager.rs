@@ -4,7 +4,7 @@ use log::{error, info};
 use tauri::Window;
 use tokio::fs;
 
-use crate::{addons::progress::AddonsProgress, app::{api::ApiEndpoints, app_data::LauncherOptions, gui::FileData, modrinth_api::{ModrinthApiEndpoints, Shader, ShaderInfo}}, error::{Error, ErrorKind}, utils::download_file};
+use crate::{addons::progress::AddonsProgress, app::{api::ApiEndpoints, app_data::LauncherOptions, gui::FileData, modrinth_api::{ModrinthApiEndpoints, Shader, ShaderInfo}}, error::{Error, ErrorKind}, minecraft::progress::{ProgressTracker, ProgressUpdateSteps}, utils::download_file_to};
 
 pub struct ShaderManager;
 
@@ -97,11 +97,13 @@ impl ShaderManager {
             if let Some(url) = &shader.url {
                 info!("Downloading shader {} from {}", &shader.file_name, url);
                 
-                let retrieved_bytes = download_file(url, |a, b| {
-                    window.emit("addons-progress", AddonsProgress { identifier: shader.slug.clone(), current: a, max: b }).unwrap();
+                let tracker = ProgressTracker::new(ProgressUpdateSteps::DownloadAddon, 1, 0);
+                tracker.set_current_file(&shader.file_name);
+                download_file_to(url, &shader_path, |a, b| {
+                    if let Some(state) = tracker.set_bytes(a, b) {
+                        window.emit("addons-progress", AddonsProgress::new(shader.slug.clone(), state)).unwrap();
+                    }
                 }).await?;
-                
-                fs::write(&shader_path, retrieved_bytes).await?;
                 info!("Installed Shader {}", &shader.file_name);
             }
         }
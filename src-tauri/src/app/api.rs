@@ -6,13 +6,14 @@ use log::{debug, info};
 use serde::{Deserialize, Serialize};
 use serde::de::DeserializeOwned;
 use uuid::Uuid;
-use crate::{HTTP_CLIENT, LAUNCHER_DIRECTORY};
+use crate::{ENDPOINTS, HTTP_CLIENT, LAUNCHER_DIRECTORY};
 use super::app_data::{Announcement, ChangeLog, LauncherOptions};
 use super::gui::{NoRiskBranch, OnlineStatusInfo};
 use crate::custom_servers::models::CustomServer;
+use crate::error::LauncherError;
 use crate::minecraft::minecraft_auth::NoRiskToken;
 use crate::minecraft::version::AssetObject;
-use crate::utils::get_maven_artifact_path;
+use crate::utils::{get_maven_artifact_path, MavenCoordinate};
 
 pub const CONTENT_FOLDER: &str = "NoRiskClient";
 
@@ -20,11 +21,7 @@ pub const CONTENT_FOLDER: &str = "NoRiskClient";
 pub struct ApiEndpoints;
 
 pub fn get_api_base(is_experimental: bool) -> String {
-    return if is_experimental {
-        String::from("https://api-staging.norisk.gg/api/v1")
-    } else {
-        String::from("https://api.norisk.gg/api/v1")
-    };
+    ENDPOINTS.norisk_api(is_experimental).to_string()
 }
 
 impl ApiEndpoints {
@@ -349,7 +346,7 @@ impl ApiEndpoints {
     /// Request JSON formatted data from launcher API
     pub async fn request_from_download_norisk_endpoint<T: DeserializeOwned>(endpoint: &str) -> Result<T> {
         let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default();
-        let url = format!("https://dl{}.norisk.gg/{}", if options.experimental_mode { "-staging" } else { "" }, endpoint);
+        let url = format!("{}/{}", ENDPOINTS.norisk_download(options.experimental_mode), endpoint);
         info!("URL: {}", url); // Den formatierten String ausgeben
         Ok(HTTP_CLIENT.get(url)
             .send().await?
@@ -609,7 +606,41 @@ pub struct NoRiskBuild {
     pub branch: String,
     pub mc_version: String,
     pub jre_version: u32,
+    #[serde(default)]
     pub fabric_loader_version: String,
+    #[serde(default)]
+    pub quilt_loader_version: Option<String>,
+    #[serde(default)]
+    pub forge_loader_version: Option<String>,
+    #[serde(default)]
+    pub neoforge_version: Option<String>,
+}
+
+impl NoRiskBuild {
+    /// Replaces the version placeholders of a subsystem manifest or installer url
+    pub fn fill_loader_template(&self, template: &str) -> Result<String> {
+        let placeholders = [
+            ("{MINECRAFT_VERSION}", Some(&self.mc_version)),
+            ("{FABRIC_LOADER_VERSION}", Some(&self.fabric_loader_version).filter(|version| !version.is_empty())),
+            ("{QUILT_LOADER_VERSION}", self.quilt_loader_version.as_ref()),
+            ("{FORGE_LOADER_VERSION}", self.forge_loader_version.as_ref()),
+            ("{NEOFORGE_VERSION}", self.neoforge_version.as_ref()),
+        ];
+
+        let mut filled = template.to_string();
+        for (placeholder, version) in placeholders {
+            if !filled.contains(placeholder) {
+                continue;
+            }
+
+            match version {
+                Some(version) => filled = filled.replace(placeholder, version),
+                None => return Err(LauncherError::InvalidVersionProfile(format!("Build of branch {} has no version for {}", self.branch, placeholder)).into()),
+            }
+        }
+
+        Ok(filled)
+    }
 }
 
 ///
@@ -738,6 +769,13 @@ impl ModSource {
         }
     }
 
+    /// Snapshot mods can change without a version bump, their cached file has to match the latest build
+    pub fn is_snapshot(&self) -> bool {
+        match self {
+            ModSource::Repository { repository: _repository, artifact, url: _ } => MavenCoordinate::parse(artifact).map(|coordinate| coordinate.is_snapshot()).unwrap_or(false),
+        }
+    }
+
     pub fn get_path(&self) -> Result<String> {
         Ok(
             match self {
@@ -755,8 +793,12 @@ impl ModSource {
 pub enum LoaderSubsystem {
     #[serde(rename = "fabric")]
     Fabric { manifest: String, mod_directory: String },
+    #[serde(rename = "quilt")]
+    Quilt { manifest: String, mod_directory: String },
     #[serde(rename = "forge")]
-    Forge { manifest: String, mod_directory: String },
+    Forge { manifest: String, mod_directory: String, #[serde(default)] installer: Option<String> },
+    #[serde(rename = "neoforge")]
+    NeoForge { manifest: String, mod_directory: String, #[serde(default)] installer: Option<String> },
 }
 
 ///
@@ -785,4 +827,61 @@ pub struct WhitelistSlots {
     pub available_slots: i32,
     #[serde(rename = "previousInvites")]
     pub previous_invites: u32,
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn build() -> NoRiskBuild {
+        NoRiskBuild {
+            branch: "main".to_string(),
+            mc_version: "1.20.1".to_string(),
+            jre_version: 17,
+            fabric_loader_version: "0.15.11".to_string(),
+            quilt_loader_version: Some("0.26.0".to_string()),
+            forge_loader_version: Some("47.2.0".to_string()),
+            neoforge_version: Some("20.4.237".to_string()),
+        }
+    }
+
+    #[test]
+    fn every_placeholder_is_filled() {
+        let build = build();
+        assert_eq!(
+            build.fill_loader_template("https://meta.fabricmc.net/v2/versions/loader/{MINECRAFT_VERSION}/{FABRIC_LOADER_VERSION}/profile/json").unwrap(),
+            "https://meta.fabricmc.net/v2/versions/loader/1.20.1/0.15.11/profile/json"
+        );
+        assert_eq!(
+            build.fill_loader_template("https://meta.quiltmc.org/v3/versions/loader/{MINECRAFT_VERSION}/{QUILT_LOADER_VERSION}/profile/json").unwrap(),
+            "https://meta.quiltmc.org/v3/versions/loader/1.20.1/0.26.0/profile/json"
+        );
+        assert_eq!(
+            build.fill_loader_template("forge-{MINECRAFT_VERSION}-{FORGE_LOADER_VERSION}-installer.jar").unwrap(),
+            "forge-1.20.1-47.2.0-installer.jar"
+        );
+        assert_eq!(
+            build.fill_loader_template("neoforge-{NEOFORGE_VERSION}-installer.jar").unwrap(),
+            "neoforge-20.4.237-installer.jar"
+        );
+    }
+
+    #[test]
+    fn templates_without_placeholders_are_kept() {
+        let build = NoRiskBuild { fabric_loader_version: String::new(), quilt_loader_version: None, forge_loader_version: None, neoforge_version: None, ..build() };
+        assert_eq!(build.fill_loader_template("https://example.com/profile.json").unwrap(), "https://example.com/profile.json");
+        assert_eq!(build.fill_loader_template("{MINECRAFT_VERSION}/{MINECRAFT_VERSION}").unwrap(), "1.20.1/1.20.1");
+    }
+
+    #[test]
+    fn missing_versions_are_an_error() {
+        let build = NoRiskBuild { fabric_loader_version: String::new(), quilt_loader_version: None, forge_loader_version: None, neoforge_version: None, ..build() };
+
+        for placeholder in ["{FABRIC_LOADER_VERSION}", "{QUILT_LOADER_VERSION}", "{FORGE_LOADER_VERSION}", "{NEOFORGE_VERSION}"] {
+            let err = build.fill_loader_template(&format!("{{MINECRAFT_VERSION}}/{}", placeholder)).unwrap_err();
+            match err.downcast_ref::<LauncherError>() {
+                Some(LauncherError::InvalidVersionProfile(message)) => assert!(message.contains(placeholder), "{}", message),
+                _ => panic!("unexpected error {:?}", err),
+            }
+        }
+    }
+}
@@ -7,13 +7,17 @@ use anyhow::Result;
 use directories::UserDirs;
 use log::debug;
 use log::info;
+use log::warn;
+use reqwest::RequestBuilder;
 use serde::{Deserialize, Serialize};
 use tokio::fs;
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 use tokio::process::Command;
+use uuid::Uuid;
 
-use crate::LAUNCHER_DIRECTORY;
+use crate::utils::SecretStore;
+use crate::{BANDWIDTH_LIMITER, HTTP_CLIENT, LAUNCHER_DIRECTORY};
 
 use super::modrinth_api::CustomMod;
 use super::modrinth_api::Datapack;
@@ -38,6 +42,9 @@ pub struct LauncherProfile {
     pub branch: String,
     pub name: String,
     pub mods: Vec<CustomMod>,
+    /// Account the profile is launched with instead of the default account
+    #[serde(rename = "accountId", default)]
+    pub account_id: Option<Uuid>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -114,11 +121,215 @@ pub struct LauncherOptions {
     pub latest_dev_branch: Option<String>,
     #[serde(rename = "concurrentDownloads", default = "default_concurrent_downloads")]
     pub concurrent_downloads: i32,
+    #[serde(rename = "customRepositories", default)]
+    pub custom_repositories: Vec<MavenRepository>,
+    /// Download bandwidth limit in Mbit/s shared by all downloads, 0 means unlimited
+    #[serde(rename = "bandwidthLimit", default)]
+    pub bandwidth_limit: u64,
+    #[serde(rename = "deprioritizeBackgroundDownloads", default)]
+    pub deprioritize_background_downloads: bool,
+    /// Hash every game file on launch instead of trusting the verified file index
+    #[serde(rename = "fullVerify", default)]
+    pub full_verify: bool,
+    #[serde(default)]
+    pub proxy: ProxySettings,
+    /// Paths of additional PEM or DER root certificates, e.g. of TLS intercepting proxies
+    #[serde(rename = "trustedCertificates", default)]
+    pub trusted_certificates: Vec<String>,
     pub language: String,
     #[serde(rename = "configVersion")]
     pub config_version: String,
 }
 
+/// User configured maven repository which is used in addition to the repositories of the launch manifest
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MavenRepository {
+    pub name: String,
+    pub url: String,
+    #[serde(default)]
+    pub auth: Option<MavenRepositoryAuth>,
+}
+
+/// Passwords and tokens are never serialized, they are kept in the options secret store.
+/// An empty secret sent by the frontend keeps the stored one.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum MavenRepositoryAuth {
+    #[serde(rename = "basic")]
+    Basic {
+        username: String,
+        #[serde(default, skip_serializing)]
+        password: String,
+    },
+    #[serde(rename = "bearer")]
+    Bearer {
+        #[serde(default, skip_serializing)]
+        token: String,
+    },
+}
+
+impl MavenRepository {
+    /// Base url of the repository, always ending with a slash so artifact paths can be appended
+    pub fn base_url(&self) -> String {
+        if self.url.ends_with('/') {
+            self.url.clone()
+        } else {
+            format!("{}/", self.url)
+        }
+    }
+}
+
+impl MavenRepositoryAuth {
+    fn secret(&self) -> &str {
+        match self {
+            MavenRepositoryAuth::Basic { password, .. } => password,
+            MavenRepositoryAuth::Bearer { token } => token,
+        }
+    }
+
+    fn secret_mut(&mut self) -> &mut String {
+        match self {
+            MavenRepositoryAuth::Basic { password, .. } => password,
+            MavenRepositoryAuth::Bearer { token } => token,
+        }
+    }
+
+    /// Attach the credentials to a request going to the repository
+    pub fn apply(&self, request: RequestBuilder) -> RequestBuilder {
+        match self {
+            MavenRepositoryAuth::Basic { username, password } => request.basic_auth(username, Some(password)),
+            MavenRepositoryAuth::Bearer { token } => request.bearer_auth(token),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ProxyType {
+    #[default]
+    #[serde(rename = "none")]
+    None,
+    #[serde(rename = "http")]
+    Http,
+    #[serde(rename = "https")]
+    Https,
+    #[serde(rename = "socks5")]
+    Socks5,
+}
+
+/// Proxy used for all network traffic of the launcher and the game
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ProxySettings {
+    #[serde(rename = "type", default)]
+    pub proxy_type: ProxyType,
+    #[serde(default)]
+    pub host: String,
+    #[serde(default)]
+    pub port: u16,
+    #[serde(default)]
+    pub username: Option<String>,
+    /// Never serialized, kept in the options secret store like the repository secrets.
+    /// An empty password sent by the frontend keeps the stored one.
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
+    /// Hosts which are reached directly, e.g. `localhost` or `.example.com` for all subdomains
+    #[serde(rename = "noProxy", default)]
+    pub no_proxy: Vec<String>,
+}
+
+impl ProxySettings {
+    /// Url of the proxy, none if no proxy is configured
+    pub fn url(&self) -> Option<String> {
+        let scheme = match self.proxy_type {
+            ProxyType::None => return None,
+            ProxyType::Http => "http",
+            ProxyType::Https => "https",
+            ProxyType::Socks5 => "socks5",
+        };
+        if self.host.is_empty() {
+            return None;
+        }
+
+        Some(format!("{}://{}:{}", scheme, self.host, self.port))
+    }
+
+    /// System properties so the game uses the same proxy. Java doesn't support proxy credentials
+    /// as system properties, those have to be handled by the proxy for the game.
+    pub fn java_args(&self) -> Vec<String> {
+        if self.url().is_none() {
+            return Vec::new();
+        }
+
+        let mut args = match self.proxy_type {
+            ProxyType::Socks5 => vec![
+                format!("-DsocksProxyHost={}", self.host),
+                format!("-DsocksProxyPort={}", self.port),
+                "-DsocksProxyVersion=5".to_string(),
+            ],
+            _ => vec![
+                format!("-Dhttp.proxyHost={}", self.host),
+                format!("-Dhttp.proxyPort={}", self.port),
+                format!("-Dhttps.proxyHost={}", self.host),
+                format!("-Dhttps.proxyPort={}", self.port),
+            ],
+        };
+
+        if !self.no_proxy.is_empty() {
+            // Java expects wildcards instead of leading dots, the http list also applies to https
+            let hosts = self.no_proxy.iter()
+                .map(|host| if host.starts_with('.') { format!("*{}", host) } else { host.clone() })
+                .collect::<Vec<_>>()
+                .join("|");
+            match self.proxy_type {
+                ProxyType::Socks5 => args.push(format!("-DsocksNonProxyHosts={}", hosts)),
+                _ => args.push(format!("-Dhttp.nonProxyHosts={}", hosts)),
+            }
+        }
+
+        args
+    }
+
+    fn has_username(&self) -> bool {
+        self.username.as_ref().map_or(false, |username| !username.is_empty())
+    }
+
+    fn password(&self) -> &str {
+        self.password.as_deref().unwrap_or_default()
+    }
+
+    fn fill_password(&mut self, stored: Option<&String>) {
+        if self.has_username() && self.password().is_empty() {
+            self.password = stored.cloned();
+        }
+    }
+}
+
+/// Secrets of the options, stored encrypted in `options.enc` instead of options.json
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+struct OptionSecrets {
+    /// Password or token by repository url
+    #[serde(default)]
+    repositories: HashMap<String, String>,
+    #[serde(default)]
+    proxy_password: Option<String>,
+}
+
+impl OptionSecrets {
+    fn secret_store(app_data: &Path) -> SecretStore {
+        SecretStore::new(app_data.join("options.enc"))
+    }
+
+    fn load(app_data: &Path) -> Result<Self> {
+        match Self::secret_store(app_data).read()? {
+            Some(data) => Ok(serde_json::from_slice(&data)?),
+            None => Ok(Self::default()),
+        }
+    }
+
+    fn store(&self, app_data: &Path) -> Result<()> {
+        Self::secret_store(app_data).write(&serde_json::to_vec(self)?)
+    }
+}
+
 // use this to make settings migration possible. this involves some more stuff, ask tim for more info :)
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct OldLauncherOptions {
@@ -150,6 +361,7 @@ pub struct OldLauncherOptions {
 }
 
 impl LauncherOptions {
+    /// Loads the options without their secrets, callers which need them use [`Self::load_secrets`]
     pub async fn load(app_data: &Path) -> Result<Self> {
         if !fs::try_exists(app_data.join("options.json")).await.unwrap_or(false) {
             // if the file does not exist, create it with the default options
@@ -161,8 +373,15 @@ impl LauncherOptions {
 
         // load the options from the file
         let options = serde_json::from_slice::<LauncherOptions>(&fs::read(app_data.join("options.json")).await?);
-        if options.is_ok() {
-            Ok(options.unwrap())
+        if let Ok(mut options) = options {
+            // Secrets of older versions are still in options.json, storing moves them into the secret store
+            if options.has_plain_secrets() {
+                info!("Moving secrets of options.json into the secret store");
+                if let Err(err) = options.store(app_data).await {
+                    warn!("Failed to move secrets into the secret store, keeping them in options.json: {:?}", err);
+                }
+            }
+            Ok(options)
         } else {
             info!("Failed to load options.json, trying to migrate old options.json");
             let old_options = serde_json::from_slice::<OldLauncherOptions>(&fs::read(app_data.join("options.json")).await?);
@@ -187,6 +406,12 @@ impl LauncherOptions {
                     latest_branch: old_options.latest_branch,
                     latest_dev_branch: old_options.latest_dev_branch,
                     concurrent_downloads: old_options.concurrent_downloads,
+                    custom_repositories: default.custom_repositories,
+                    bandwidth_limit: default.bandwidth_limit,
+                    deprioritize_background_downloads: default.deprioritize_background_downloads,
+                    full_verify: default.full_verify,
+                    proxy: default.proxy,
+                    trusted_certificates: default.trusted_certificates,
                     language: old_options.language,
                     config_version: default.config_version
                 };
@@ -198,10 +423,87 @@ impl LauncherOptions {
     }
 
     pub async fn store(&self, app_data: &Path) -> Result<()> {
+        // Without new secrets the stored ones stay untouched, so the options can still be saved
+        if let Err(err) = self.store_secrets(app_data) {
+            if self.has_plain_secrets() {
+                return Err(err);
+            }
+            warn!("Failed to update option secrets: {:?}", err);
+        }
         let _ = fs::write(app_data.join("options.json"), serde_json::to_string_pretty(&self)?).await.map_err(|err| -> String { format!("Failed to write options.json: {}", err).into() });
         Ok(())
     }
 
+    /// Whether any secret is set, after loading these are the secrets which aren't stored yet
+    fn has_plain_secrets(&self) -> bool {
+        self.custom_repositories.iter()
+            .filter_map(|repository| repository.auth.as_ref())
+            .any(|auth| !auth.secret().is_empty())
+            || !self.proxy.password().is_empty()
+    }
+
+    /// Fills the secrets which are not set yet from the secret store, e.g. for options sent by the frontend
+    pub fn load_secrets(&mut self, app_data: &Path) {
+        match OptionSecrets::load(app_data) {
+            Ok(secrets) => self.fill_secrets(&secrets),
+            Err(err) => warn!("Failed to read option secrets, continuing without them: {:?}", err),
+        }
+    }
+
+    /// Fills the secrets which are not serialized from the secret store, secrets which are already set are kept
+    fn fill_secrets(&mut self, secrets: &OptionSecrets) {
+        self.proxy.fill_password(secrets.proxy_password.as_ref());
+        for repository in &mut self.custom_repositories {
+            if let Some(auth) = repository.auth.as_mut() {
+                if let Some(secret) = secrets.repositories.get(&repository.url).filter(|_| auth.secret().is_empty()) {
+                    *auth.secret_mut() = secret.clone();
+                }
+            }
+        }
+    }
+
+    /// Writes the secrets of these options, empty secrets keep the stored ones and removed repositories lose theirs
+    fn store_secrets(&self, app_data: &Path) -> Result<()> {
+        // An unreadable secret store is never overwritten, it would lose every stored secret
+        let stored = OptionSecrets::load(app_data)?;
+
+        let mut secrets = OptionSecrets::default();
+        for repository in &self.custom_repositories {
+            if let Some(auth) = repository.auth.as_ref() {
+                let secret = match auth.secret() {
+                    "" => stored.repositories.get(&repository.url).cloned(),
+                    secret => Some(secret.to_string()),
+                };
+                if let Some(secret) = secret {
+                    secrets.repositories.insert(repository.url.clone(), secret);
+                }
+            }
+        }
+        // Without a username the proxy doesn't use the password, so it is removed
+        if self.proxy.has_username() {
+            secrets.proxy_password = match self.proxy.password() {
+                "" => stored.proxy_password.clone(),
+                password => Some(password.to_string()),
+            };
+        }
+
+        if secrets == stored {
+            return Ok(());
+        }
+        secrets.store(app_data)
+    }
+
+    /// Applies the bandwidth settings to all downloads of the launcher
+    pub fn apply_download_limits(&self) {
+        // Limits too big for bytes per second are as good as unlimited, they must not overflow
+        BANDWIDTH_LIMITER.configure(self.bandwidth_limit.saturating_mul(1_000_000) / 8, self.deprioritize_background_downloads);
+    }
+
+    /// Rebuilds the http client with the proxy and certificates of these options
+    pub fn apply_network_settings(&self) -> Result<()> {
+        HTTP_CLIENT.rebuild(&self.proxy, &self.trusted_certificates)
+    }
+
     pub fn data_path_buf(&self) -> PathBuf {
         if self.data_path.is_empty() {
             return LAUNCHER_DIRECTORY.data_dir().to_path_buf();
@@ -234,6 +536,12 @@ impl Default for LauncherOptions {
             latest_branch: None,
             latest_dev_branch: None,
             concurrent_downloads: 20,
+            custom_repositories: Vec::new(),
+            bandwidth_limit: 0,
+            deprioritize_background_downloads: false,
+            full_verify: false,
+            proxy: ProxySettings::default(),
+            trusted_certificates: Vec::new(),
             language: String::from("en_US"),
             config_version: String::from("1.1"),
         }
@@ -242,8 +550,9 @@ impl Default for LauncherOptions {
 
 impl LauncherProfiles {
     pub async fn load(app_data: &Path) -> Result<Self> {
-        // load the launcher_profiles from the file
-        let launcher_profiles = serde_json::from_slice::<LauncherProfiles>(&fs::read(app_data.join("launcher_profiles.json")).await?).map_err(|err| -> String { format!("Failed to write launcher_profiles.json: {}", err.to_string()).into() }).unwrap_or_else(|_| LauncherProfiles::default());
+        // load the launcher_profiles from the file, callers decide whether an unreadable file falls back to the defaults
+        let launcher_profiles = serde_json::from_slice::<LauncherProfiles>(&fs::read(app_data.join("launcher_profiles.json")).await?)
+            .map_err(|err| anyhow::anyhow!("Failed to read launcher_profiles.json: {}", err))?;
         Ok(launcher_profiles)
     }
 
@@ -326,6 +635,8 @@ impl LauncherProfiles {
             branch: import_profile.branch.clone(),
             name: import_profile.name.clone(),
             mods: import_profile.mods.clone(),
+            // Accounts are local to this launcher
+            account_id: None,
         };
     
         if options.experimental_mode {
@@ -521,3 +832,79 @@ impl LatestRunningGame {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repository_secrets_are_not_serialized() {
+        let repository: MavenRepository = serde_json::from_str(
+            r#"{"name": "private", "url": "https://maven.example.com", "auth": {"type": "basic", "username": "user", "password": "secret"}}"#,
+        ).unwrap();
+        assert_eq!(repository.auth.as_ref().unwrap().secret(), "secret");
+
+        let json = serde_json::to_string(&repository).unwrap();
+        assert!(!json.contains("secret"));
+        assert!(json.contains("user"));
+
+        let bearer = MavenRepositoryAuth::Bearer { token: "token".to_string() };
+        assert_eq!(serde_json::to_string(&bearer).unwrap(), r#"{"type":"bearer"}"#);
+    }
+
+    #[test]
+    fn proxy_password_is_not_serialized() {
+        let proxy: ProxySettings = serde_json::from_str(
+            r#"{"type": "http", "host": "proxy", "port": 8080, "username": "user", "password": "secret"}"#,
+        ).unwrap();
+        assert_eq!(proxy.password(), "secret");
+
+        let json = serde_json::to_string(&proxy).unwrap();
+        assert!(!json.contains("secret"));
+        assert!(json.contains("user"));
+    }
+
+    #[test]
+    fn stored_proxy_password_is_filled_for_proxies_with_username() {
+        let stored = "secret".to_string();
+
+        let mut proxy = ProxySettings { username: Some("user".to_string()), ..Default::default() };
+        proxy.fill_password(Some(&stored));
+        assert_eq!(proxy.password(), "secret");
+
+        let mut proxy = ProxySettings { username: Some("user".to_string()), password: Some("new".to_string()), ..Default::default() };
+        proxy.fill_password(Some(&stored));
+        assert_eq!(proxy.password(), "new");
+
+        let mut proxy = ProxySettings::default();
+        proxy.fill_password(Some(&stored));
+        assert_eq!(proxy.password, None);
+    }
+
+    #[test]
+    fn socks_proxies_use_socks_non_proxy_hosts() {
+        let mut proxy = ProxySettings {
+            proxy_type: ProxyType::Socks5,
+            host: "proxy".to_string(),
+            port: 1080,
+            no_proxy: vec!["localhost".to_string(), ".example.com".to_string()],
+            ..Default::default()
+        };
+        let args = proxy.java_args();
+        assert!(args.contains(&"-DsocksProxyHost=proxy".to_string()));
+        assert!(args.contains(&"-DsocksNonProxyHosts=localhost|*.example.com".to_string()));
+        assert!(!args.iter().any(|arg| arg.starts_with("-Dhttp.")));
+
+        proxy.proxy_type = ProxyType::Http;
+        let args = proxy.java_args();
+        assert!(args.contains(&"-Dhttps.proxyHost=proxy".to_string()));
+        assert!(args.contains(&"-Dhttp.nonProxyHosts=localhost|*.example.com".to_string()));
+        assert!(!args.iter().any(|arg| arg.starts_with("-DsocksNonProxyHosts")));
+    }
+
+    #[test]
+    fn missing_secrets_deserialize_empty() {
+        let auth: MavenRepositoryAuth = serde_json::from_str(r#"{"type": "basic", "username": "user"}"#).unwrap();
+        assert_eq!(auth.secret(), "");
+    }
+}
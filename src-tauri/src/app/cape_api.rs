@@ -1,16 +1,17 @@
 use std::error::Error;
-use std::fs::File;
 #[cfg(target_os = "linux")]
 use std::fs::metadata;
-use std::io::Read;
+use std::io::Cursor;
 use std::path::PathBuf;
 use std::process::Command;
 
+use image::imageops::{self, FilterType};
+use image::{GenericImageView, ImageFormat, ImageOutputFormat, RgbaImage};
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
 use log::debug;
 
-use crate::{HTTP_CLIENT, LAUNCHER_DIRECTORY};
+use crate::{HTTP_CLIENT, LAUNCHER_DIRECTORY, PROFILE_CACHE};
 use crate::app::api::get_api_base;
 use crate::app::app_data::LauncherOptions;
 
@@ -35,7 +36,10 @@ impl CapeApiEndpoints {
         debug!("Cape equiped status {:?}",response.status());
 
         return match response.status() {
-            StatusCode::OK => Ok(()),
+            StatusCode::OK => {
+                PROFILE_CACHE.invalidate_cape(uuid);
+                Ok(())
+            }
             _ => {
                 let response_text = response.text().await.map_err(|err| {
                     format!("Error reading the request: {}", err)
@@ -62,7 +66,10 @@ impl CapeApiEndpoints {
         debug!("Cape delete status {:?}",response.status());
 
         return match response.status() {
-            StatusCode::OK => Ok(()),
+            StatusCode::OK => {
+                PROFILE_CACHE.invalidate_cape(uuid);
+                Ok(())
+            }
             _ => {
                 let response_text = response.text().await.map_err(|err| {
                     format!("Error reading the request: {}", err)
@@ -76,60 +83,42 @@ impl CapeApiEndpoints {
         debug!("Image Path {:?}",image_path);
         let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default();
         // Lese den Inhalt der Bilddatei in Bytes ein
-        match File::open(image_path) {
-            Ok(mut file) => {
-                let mut image_data = Vec::new();
-                file.read_to_end(&mut image_data).expect("Error Reading File");
-
-                // Baue die URL mit dem Token als Query-Parameter
-                let url = format!("{}/cosmetics/cape?uuid={}", get_api_base(options.experimental_mode), uuid);
-                
-                // Sende den POST-Request
-                let response = HTTP_CLIENT
-                    .post(&url)
-                    .header("Authorization", format!("Bearer {}", token))
-                    .body(image_data)
-                    .send()
-                    .await
-                    .map_err(|err| format!("Fehler beim Senden des Requests: {}", err))?;
-
-                debug!("Cape upload status {:?}",response.status());
+        let image_data = tokio::fs::read(image_path)
+            .await
+            .map_err(|err| format!("Error reading cape file {:?}: {}", image_path, err))?;
+        let image_data = normalize_cape(&image_data)?;
 
-                let response_text = response.text().await.map_err(|err| {
-                    format!("Error reading cape upload response text: {}", err)
-                })?;
-                Ok(response_text)
-            }
-            Err(_err) => {
-                Err("Error Selecting Cape".parse().unwrap())
-            }
+        // Baue die URL mit dem Token als Query-Parameter
+        let url = format!("{}/cosmetics/cape?uuid={}", get_api_base(options.experimental_mode), uuid);
+
+        // Sende den POST-Request
+        let response = HTTP_CLIENT
+            .post(&url)
+            .header("Authorization", format!("Bearer {}", token))
+            .body(image_data)
+            .send()
+            .await
+            .map_err(|err| format!("Fehler beim Senden des Requests: {}", err))?;
+
+        let status = response.status();
+        debug!("Cape upload status {:?}", status);
+
+        let response_text = response.text().await.map_err(|err| {
+            format!("Error reading cape upload response text: {}", err)
+        })?;
+        if !status.is_success() {
+            return Err(format!("Cape upload failed with status {}: {}", status, response_text));
         }
+        PROFILE_CACHE.invalidate_cape(uuid);
+        Ok(response_text)
     }
 
     pub async fn mc_name_by_uuid(uuid: &str) -> Result<String, Box<dyn Error>> {
-        debug!("Requesting Minecraft Username {}",uuid);
-        let url = format!("https://sessionserver.mojang.com/session/minecraft/profile/{}", uuid);
-        let response = HTTP_CLIENT.get(url).send().await?;
-        let response_text = response.json::<McProfile>().await?;
-        Ok(response_text.name)
-    }
-
-    pub async fn mc_uuid_by_name(username: &str) -> Result<String, Box<dyn Error>> {
-        debug!("Requesting Minecraft UUID {}",username);
-        let url = format!("https://api.mojang.com/users/profiles/minecraft/{}", username);
-        let response = HTTP_CLIENT.get(url).send().await?;
-        let response_text = response.json::<McProfile>().await?;
-        let uuid = uuid::Uuid::parse_str(&response_text.id)?;
-        Ok(uuid.to_string())
+        Ok(PROFILE_CACHE.name_by_uuid(uuid).await?)
     }
 
     pub async fn cape_hash_by_uuid(uuid: &str) -> Result<String, Box<dyn Error>> {
-        debug!("Requesting Cape Hash {}",uuid);
-        let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default();
-        let url = format!("{}/cosmetics/user/{}/cape", get_api_base(options.experimental_mode), uuid);
-        let response = HTTP_CLIENT.get(url).send().await?;
-        let response_text = response.text().await?;
-        Ok(response_text)
+        Ok(PROFILE_CACHE.cape_hash_by_uuid(uuid).await?)
     }
 
     pub async fn unequip_cape(norisk_token: &str, uuid: &str) -> Result<(), String> {
@@ -149,6 +138,7 @@ impl CapeApiEndpoints {
 
         return match response.status() {
             StatusCode::OK => {
+                PROFILE_CACHE.invalidate_cape(uuid);
                 Ok(())
             }
             _ => {
@@ -172,9 +162,8 @@ impl CapeApiEndpoints {
             .json::<Vec<Cape>>().await?)
     }
 
-    pub async fn request_user_capes(norisk_token: &str, uuid: &str, username: &str) -> Result<Vec<Cape>, Box<dyn Error>> {
-        debug!("Requesting User Capes of {}...", username);
-        let target_uuid = Self::mc_uuid_by_name(username).await?;
+    pub async fn request_user_capes(norisk_token: &str, uuid: &str, target_uuid: &str) -> Result<Vec<Cape>, Box<dyn Error>> {
+        debug!("Requesting User Capes of {}...", target_uuid);
         let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default();
         let url = format!("{}/cosmetics/cape/user/{}?uuid={}", get_api_base(options.experimental_mode), target_uuid, uuid);
         Ok(HTTP_CLIENT
@@ -247,7 +236,51 @@ impl CapeApiEndpoints {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Resolution every cape is uploaded with
+const CAPE_WIDTH: u32 = 512;
+const CAPE_HEIGHT: u32 = 256;
+
+/// Brings a cape texture into the uploaded format: 512x256 pixels, re-encoded as png without any metadata.
+/// 64x32 and 128x64 textures are upscaled, 22x17 textures are placed on a 64x32 canvas first.
+pub fn normalize_cape(image_data: &[u8]) -> Result<Vec<u8>, String> {
+    let image = image::load_from_memory_with_format(image_data, ImageFormat::Png)
+        .map_err(|err| format!("The cape is not a valid png: {}", err))?
+        .to_rgba8();
+
+    let image = match image.dimensions() {
+        (CAPE_WIDTH, CAPE_HEIGHT) => image,
+        (64, 32) | (128, 64) => image,
+        (22, 17) => {
+            let mut canvas = RgbaImage::new(64, 32);
+            imageops::overlay(&mut canvas, &image, 0, 0);
+            canvas
+        }
+        (width, height) => {
+            return Err(format!(
+                "Unsupported cape resolution {}x{}, use the template (512x256) or a 128x64, 64x32 or 22x17 texture",
+                width, height
+            ))
+        }
+    };
+    let image = imageops::resize(&image, CAPE_WIDTH, CAPE_HEIGHT, FilterType::Nearest);
+
+    // Minecraft renders capes solid, transparent pixels on the front or back show up black
+    let factor = CAPE_WIDTH / 64;
+    for (name, x) in [("front", 1), ("back", 12)] {
+        let face = imageops::crop_imm(&image, x * factor, factor, 10 * factor, 16 * factor);
+        if face.pixels().any(|(_, _, pixel)| pixel[3] != 255) {
+            return Err(format!("The {} of the cape contains transparent pixels, use the template to see which area has to be filled", name));
+        }
+    }
+
+    let mut png = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+        .map_err(|err| format!("Failed to encode the cape: {}", err))?;
+    Ok(png)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct McProfile {
     pub id: String,
     pub name: String,
@@ -264,3 +297,62 @@ pub struct Cape {
     #[serde(rename = "creationDate")]
     pub creation_date: i64,
 }
+
+#[cfg(test)]
+mod tests {
+    use image::Rgba;
+
+    use super::*;
+
+    fn png(image: RgbaImage) -> Vec<u8> {
+        let mut png = Vec::new();
+        image.write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png).unwrap();
+        png
+    }
+
+    fn opaque_cape(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| Rgba([x as u8, y as u8, 0, 255]))
+    }
+
+    #[test]
+    fn small_capes_are_upscaled() {
+        for (width, height) in [(64, 32), (128, 64), (512, 256)] {
+            let normalized = normalize_cape(&png(opaque_cape(width, height))).unwrap();
+            let image = image::load_from_memory(&normalized).unwrap().to_rgba8();
+            assert_eq!(image.dimensions(), (CAPE_WIDTH, CAPE_HEIGHT));
+        }
+
+        let normalized = normalize_cape(&png(opaque_cape(64, 32))).unwrap();
+        let image = image::load_from_memory(&normalized).unwrap().to_rgba8();
+        assert_eq!(image.get_pixel(8, 8), &Rgba([1, 1, 0, 255]));
+        assert_eq!(image.get_pixel(511, 255), &Rgba([63, 31, 0, 255]));
+    }
+
+    #[test]
+    fn legacy_capes_are_placed_on_a_full_texture() {
+        let normalized = normalize_cape(&png(opaque_cape(22, 17))).unwrap();
+        let image = image::load_from_memory(&normalized).unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), (CAPE_WIDTH, CAPE_HEIGHT));
+        assert_eq!(image.get_pixel(8 * 21, 8 * 16), &Rgba([21, 16, 0, 255]));
+        assert_eq!(image.get_pixel(8 * 22, 0)[3], 0);
+    }
+
+    #[test]
+    fn unsupported_capes_are_rejected() {
+        assert!(normalize_cape(&png(opaque_cape(100, 50))).is_err());
+        assert!(normalize_cape(b"not a png").is_err());
+
+        let mut front = opaque_cape(64, 32);
+        front.put_pixel(5, 5, Rgba([0, 0, 0, 0]));
+        assert!(normalize_cape(&png(front)).unwrap_err().contains("front"));
+
+        let mut back = opaque_cape(64, 32);
+        back.put_pixel(15, 10, Rgba([0, 0, 0, 128]));
+        assert!(normalize_cape(&png(back)).unwrap_err().contains("back"));
+
+        // Pixels outside of the front and back may be transparent
+        let mut elytra = opaque_cape(64, 32);
+        elytra.put_pixel(40, 5, Rgba([0, 0, 0, 0]));
+        assert!(normalize_cape(&png(elytra)).is_ok());
+    }
+}
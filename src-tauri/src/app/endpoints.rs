@@ -0,0 +1,227 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::LAUNCHER_DIRECTORY;
+
+/// Prefix of the environment variables overriding single endpoints, e.g. `NRC_ENDPOINT_NORISK_API`
+/// or `NRC_ENDPOINT_AUTH_OAUTH_TOKEN` for nested endpoints
+const ENV_PREFIX: &str = "NRC_ENDPOINT";
+/// Environment variable with the path of an endpoints file which is used instead of `endpoints.json` in the config directory
+const ENV_FILE: &str = "NRC_ENDPOINTS_FILE";
+
+/// Base urls of all services the launcher talks to, they never end with a slash.
+/// Every endpoint can be overridden by the endpoints file or environment variables, so the launcher can run
+/// against local stand-in servers. Missing entries of the file keep their default.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Endpoints {
+    pub norisk_api: String,
+    pub norisk_api_staging: String,
+    pub norisk_download: String,
+    pub norisk_download_staging: String,
+    pub norisk_cdn: String,
+    pub auth: AuthEndpoints,
+    pub minecraft_services: String,
+    pub mojang_api: String,
+    pub mojang_session: String,
+    pub mojang_launcher_meta: String,
+    pub minecraft_resources: String,
+    pub minecraft_libraries: String,
+    pub modrinth_api: String,
+    pub modrinth_meta: String,
+    pub mclogs_api: String,
+    pub fabric_meta: String,
+    pub paper_api: String,
+    pub folia_api: String,
+    pub purpur_api: String,
+    pub getbukkit: String,
+    pub forge_maven: String,
+    pub neoforge_maven: String,
+    pub quilt_maven: String,
+}
+
+/// Microsoft and Xbox endpoints of the Microsoft login
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AuthEndpoints {
+    pub device_authenticate: String,
+    pub sisu_authenticate: String,
+    pub sisu_authorize: String,
+    pub xsts_authorize: String,
+    pub oauth_token: String,
+    pub oauth_device_code: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self {
+            norisk_api: "https://api.norisk.gg/api/v1".to_string(),
+            norisk_api_staging: "https://api-staging.norisk.gg/api/v1".to_string(),
+            norisk_download: "https://dl.norisk.gg".to_string(),
+            norisk_download_staging: "https://dl-staging.norisk.gg".to_string(),
+            norisk_cdn: "https://cdn.norisk.gg".to_string(),
+            auth: AuthEndpoints::default(),
+            minecraft_services: "https://api.minecraftservices.com".to_string(),
+            mojang_api: "https://api.mojang.com".to_string(),
+            mojang_session: "https://sessionserver.mojang.com".to_string(),
+            mojang_launcher_meta: "https://launchermeta.mojang.com".to_string(),
+            minecraft_resources: "https://resources.download.minecraft.net".to_string(),
+            minecraft_libraries: "https://libraries.minecraft.net".to_string(),
+            modrinth_api: "https://api.modrinth.com/v2".to_string(),
+            modrinth_meta: "https://meta.modrinth.com".to_string(),
+            mclogs_api: "https://api.mclo.gs/1".to_string(),
+            fabric_meta: "https://meta.fabricmc.net/v2".to_string(),
+            paper_api: "https://api.papermc.io/v2/projects/paper".to_string(),
+            folia_api: "https://api.papermc.io/v2/projects/folia".to_string(),
+            purpur_api: "https://api.purpurmc.org/v2/purpur".to_string(),
+            getbukkit: "https://download.getbukkit.org".to_string(),
+            forge_maven: "https://maven.minecraftforge.net".to_string(),
+            neoforge_maven: "https://maven.neoforged.net/releases".to_string(),
+            quilt_maven: "https://maven.quiltmc.org/repository/release/org/quiltmc".to_string(),
+        }
+    }
+}
+
+impl Default for AuthEndpoints {
+    fn default() -> Self {
+        Self {
+            device_authenticate: "https://device.auth.xboxlive.com/device/authenticate".to_string(),
+            sisu_authenticate: "https://sisu.xboxlive.com/authenticate".to_string(),
+            sisu_authorize: "https://sisu.xboxlive.com/authorize".to_string(),
+            xsts_authorize: "https://xsts.auth.xboxlive.com/xsts/authorize".to_string(),
+            oauth_token: "https://login.live.com/oauth20_token.srf".to_string(),
+            oauth_device_code: "https://login.live.com/oauth20_connect.srf".to_string(),
+        }
+    }
+}
+
+impl Endpoints {
+    /// Loads the defaults, overridden by the endpoints file and then by the environment variables
+    pub fn load() -> Self {
+        let path = env::var_os(ENV_FILE)
+            .map(PathBuf::from)
+            .unwrap_or_else(|| LAUNCHER_DIRECTORY.config_dir().join("endpoints.json"));
+
+        Self::load_from(&path, |name| env::var(name).ok())
+    }
+
+    fn load_from<F: Fn(&str) -> Option<String>>(path: &Path, env_var: F) -> Self {
+        let endpoints = match std::fs::read(path) {
+            Ok(content) => match serde_json::from_slice::<Endpoints>(&content) {
+                Ok(endpoints) => {
+                    info!("Using endpoints of {:?}", path);
+                    endpoints
+                }
+                Err(err) => {
+                    error!("Invalid endpoints file {:?}, using the default endpoints: {:?}", path, err);
+                    Endpoints::default()
+                }
+            },
+            Err(_) => Endpoints::default(),
+        };
+
+        let mut value = match serde_json::to_value(&endpoints) {
+            Ok(value) => value,
+            Err(_) => return endpoints,
+        };
+        apply_env_overrides(&mut value, ENV_PREFIX, &env_var);
+        serde_json::from_value(value).unwrap_or(endpoints)
+    }
+
+    pub fn norisk_api(&self, is_experimental: bool) -> &str {
+        if is_experimental { &self.norisk_api_staging } else { &self.norisk_api }
+    }
+
+    pub fn norisk_download(&self, is_experimental: bool) -> &str {
+        if is_experimental { &self.norisk_download_staging } else { &self.norisk_download }
+    }
+}
+
+/// Applies the environment overrides and removes trailing slashes of the defaults, the file and the overrides
+fn apply_env_overrides<F: Fn(&str) -> Option<String>>(value: &mut Value, prefix: &str, env_var: &F) {
+    if let Value::Object(map) = value {
+        for (key, value) in map.iter_mut() {
+            let name = format!("{}_{}", prefix, key.to_uppercase());
+            match value {
+                Value::String(url) => {
+                    if let Some(override_url) = env_var(&name) {
+                        info!("Using endpoint {} = {}", name, override_url);
+                        *url = override_url;
+                    }
+                    let trimmed = url.trim_end_matches('/').len();
+                    url.truncate(trimmed);
+                }
+                nested => apply_env_overrides(nested, &name, env_var),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn endpoints_file(content: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("nrc-endpoints-{}.json", uuid::Uuid::new_v4()));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn defaults_have_no_trailing_slash() {
+        let endpoints = Endpoints::load_from(Path::new("/nonexistent/endpoints.json"), |_| None);
+        let value = serde_json::to_value(&endpoints).unwrap();
+        let mut urls = vec![value["auth"]["oauth_token"].as_str().unwrap()];
+        urls.extend(value.as_object().unwrap().values().filter_map(Value::as_str));
+
+        assert!(urls.len() > 20);
+        assert!(urls.iter().all(|url| !url.ends_with('/')));
+        assert_eq!(endpoints.minecraft_libraries, "https://libraries.minecraft.net");
+    }
+
+    #[test]
+    fn file_overrides_single_endpoints() {
+        let path = endpoints_file(r#"{"norisk_api": "http://localhost:8080/api/v1/", "auth": {"oauth_token": "http://localhost:8081/token"}}"#);
+        let endpoints = Endpoints::load_from(&path, |_| None);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(endpoints.norisk_api, "http://localhost:8080/api/v1");
+        assert_eq!(endpoints.auth.oauth_token, "http://localhost:8081/token");
+        // Missing entries keep their default
+        assert_eq!(endpoints.norisk_cdn, Endpoints::default().norisk_cdn);
+        assert_eq!(endpoints.auth.sisu_authorize, AuthEndpoints::default().sisu_authorize);
+    }
+
+    #[test]
+    fn invalid_file_uses_defaults() {
+        let path = endpoints_file("{ not json");
+        let endpoints = Endpoints::load_from(&path, |_| None);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(endpoints.norisk_api, Endpoints::default().norisk_api);
+    }
+
+    #[test]
+    fn env_overrides_the_file() {
+        let path = endpoints_file(r#"{"norisk_api": "http://file", "mojang_api": "http://file"}"#);
+        let env = HashMap::from([
+            ("NRC_ENDPOINT_NORISK_API", "http://env/"),
+            ("NRC_ENDPOINT_AUTH_OAUTH_TOKEN", "http://env/token"),
+            ("NRC_ENDPOINT_FORGE_MAVEN", "http://env/maven//"),
+        ]);
+        let endpoints = Endpoints::load_from(&path, |name| env.get(name).map(|value| value.to_string()));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(endpoints.norisk_api, "http://env");
+        assert_eq!(endpoints.mojang_api, "http://file");
+        assert_eq!(endpoints.auth.oauth_token, "http://env/token");
+        assert_eq!(endpoints.auth.oauth_device_code, AuthEndpoints::default().oauth_device_code);
+        assert_eq!(endpoints.forge_maven, "http://env/maven");
+    }
+}
@@ -1,15 +1,14 @@
 use std::convert::From;
 use std::fs::File;
 use std::io::BufRead;
-use std::io::Write;
 use std::{
     collections::HashMap,
+    future::Future,
     io,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::{Arc, Mutex},
     thread,
 };
-use image::GenericImageView;
 
 use anyhow::Result;
 use chrono::Utc;
@@ -32,19 +31,24 @@ use crate::addons::mod_manager::ModManager;
 use crate::addons::resourcepack_manager::ResourcePackManager;
 use crate::addons::shader_manager::ShaderManager;
 use crate::app::api::{LoginData, NoRiskLaunchManifest};
-use crate::app::cape_api::{Cape, CapeApiEndpoints};
+use crate::app::cape_api::{normalize_cape, Cape, CapeApiEndpoints};
 use crate::app::mclogs_api::{McLogsApiEndpoints, McLogsUploadResponse};
 use crate::app::modrinth_api::{
     CustomMod, ModInfo, ModrinthApiEndpoints, ModrinthModsSearchResponse, ModrinthProject,
     ModrinthSearchRequestParams,
 };
-use crate::app::nrc_cache::{AppState, NRCCache, OutputData, RunnerInstance};
+use crate::app::skin_library::{LibrarySkin, SkinLibrary, SkinVariant};
+use crate::app::skin_render::{RenderKind, SkinRenderer};
+use crate::app::nrc_cache::{AppState, InstanceAccount, NRCCache, OutputData, RunnerInstance};
 use crate::error::Error;
 use crate::error::ErrorKind;
 use crate::error::ErrorKind::OtherError;
+use crate::error::LauncherError;
 use crate::minecraft::auth;
-use crate::minecraft::minecraft_auth::{Credentials, MinecraftAuthStore};
+use crate::minecraft::minecraft_auth::{device_code, Credentials, MinecraftAuthStore};
+use crate::minecraft::norisk_assets::AssetOverrideSettings;
 use crate::minecraft::progress::ClientProgressUpdate;
+use crate::minecraft::token_refresher::start_token_refresher;
 use crate::LAUNCHER_VERSION;
 use crate::{
     custom_servers::{
@@ -67,14 +71,15 @@ use crate::{
         launcher::{LauncherData, LaunchingParameter},
         prelauncher,
         progress::ProgressUpdate,
+        version::{ManifestVersion, VersionManifest},
     },
-    utils::{total_memory, McDataHandler},
-    HTTP_CLIENT, LAUNCHER_DIRECTORY,
+    utils::{download_bytes_with_priority, download_file_to, total_memory, CancellationToken, DownloadPriority, ImportReport, InstanceImporter, McDataHandler},
+    ENDPOINTS, HTTP_CLIENT, LAUNCHER_DIRECTORY, PROFILE_CACHE,
 };
 
 use super::{
     api::{
-        ApiEndpoints, CustomServersResponse, FeaturedServer, LoaderMod, NoRiskUserMinimal,
+        get_api_base, ApiEndpoints, CustomServersResponse, FeaturedServer, LoaderMod, NoRiskUserMinimal,
         WhitelistSlots,
     },
     app_data::{Announcement, ChangeLog, LastViewedPopups, LauncherOptions, LauncherProfiles},
@@ -104,32 +109,6 @@ pub struct FileData {
     pub location: String,
 }
 
-#[derive(Deserialize)]
-struct MinecraftProfile {
-    properties: Vec<MinecraftProfileProperty>,
-}
-
-#[derive(Deserialize)]
-struct MinecraftProfileProperty {
-    name: String,
-    value: String,
-}
-
-#[derive(Deserialize)]
-struct PlayerDBData {
-    data: PlayerDBEntry,
-}
-
-#[derive(Deserialize)]
-struct PlayerDBEntry {
-    player: Option<PlayerDBPlayer>,
-}
-
-#[derive(Deserialize)]
-struct PlayerDBPlayer {
-    id: String,
-}
-
 #[tauri::command]
 async fn check_online_status() -> Result<OnlineStatusInfo, String> {
     ApiEndpoints::norisk_api_status()
@@ -188,16 +167,13 @@ async fn accept_privacy_policy() -> Result<(), String> {
     Ok(())
 }
 
+/// Returns the cape the way it would be uploaded, so the preview matches the uploaded cape
 #[tauri::command]
-async fn check_cape_resolution(image_data: &str) -> Result<bool, String> {
+async fn normalize_cape_image(image_data: &str) -> Result<String, String> {
     let image_data = base64::decode(image_data)
         .map_err(|e| format!("unable to decode base64 image data: {:?}", e))?;
 
-    let image = image::load_from_memory(&image_data)
-        .map_err(|e| format!("unable to load image from memory: {:?}", e))?;
-
-    let (width, height) = image.dimensions();
-    Ok(width == 512 && height == 256)
+    normalize_cape(&image_data).map(base64::encode)
 }
 
 #[tauri::command]
@@ -438,9 +414,9 @@ async fn request_trending_capes(
 async fn request_user_capes(
     norisk_token: &str,
     uuid: &str,
-    username: &str,
+    target_uuid: &str,
 ) -> Result<Vec<Cape>, String> {
-    CapeApiEndpoints::request_user_capes(norisk_token, uuid, username)
+    CapeApiEndpoints::request_user_capes(norisk_token, uuid, target_uuid)
         .await
         .map_err(|e| format!("unable to request user capes: {:?}", e))
 }
@@ -457,28 +433,17 @@ async fn download_template_and_open_explorer() -> Result<(), String> {
     let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir())
         .await
         .unwrap_or_default();
-    let template_url = if options.experimental_mode {
-        "https://cdn.norisk.gg/capes-staging/template.png"
-    } else {
-        "https://cdn.norisk.gg/capes/template.png"
-    };
+    let template_url = format!(
+        "{}/{}/template.png",
+        ENDPOINTS.norisk_cdn,
+        if options.experimental_mode { "capes-staging" } else { "capes" }
+    );
     let user_dirs = UserDirs::new().unwrap();
     let downloads_dir = user_dirs.download_dir().unwrap();
     debug!("Downloads directory: {:?}", downloads_dir);
-    let response = HTTP_CLIENT
-        .get(template_url)
-        .send()
+    download_file_to(&template_url, downloads_dir.join("nrc_cape_template.png"), |_, _| {})
         .await
         .map_err(|e| format!("Error downloading template: {:?}", e))?;
-    let template_bytes = response
-        .bytes()
-        .await
-        .map_err(|e| format!("Error reading template bytes: {:?}", e))?;
-
-    let mut file = File::create(downloads_dir.join("nrc_cape_template.png"))
-        .map_err(|e| format!("Error creating file: {:?}", e))?;
-    file.write_all(&template_bytes)
-        .map_err(|e| format!("Error writing file: {:?}", e))?;
 
     CapeApiEndpoints::show_in_folder(
         downloads_dir
@@ -501,6 +466,12 @@ async fn import_launcher_profile(file_location: &str) -> Result<(), String> {
     LauncherProfiles::import(file_location).await
 }
 
+/// Imports a Prism, MultiMC, CurseForge or Modrinth App instance as new profile of the branch
+#[tauri::command]
+async fn import_instance(path: String, branch: String, app: tauri::AppHandle) -> Result<ImportReport, Error> {
+    InstanceImporter::import(Path::new(&path), &branch, &app).await
+}
+
 #[tauri::command]
 async fn get_mobile_app_token(norisk_token: &str, uuid: &str) -> Result<String, String> {
     ApiEndpoints::get_mcreal_app_token(norisk_token, uuid)
@@ -747,13 +718,20 @@ pub async fn minecraft_auth_users() -> Result<Vec<Credentials>, Error> {
     Ok(accounts.users.values().cloned().collect())
 }
 
+/// Adds an offline account for local testing, it can only launch vanilla versions
+#[tauri::command]
+pub async fn minecraft_auth_add_offline_user(username: String) -> Result<Credentials, Error> {
+    let mut accounts = minecraft_auth_get_store().await?;
+    Ok(accounts.add_offline(username.trim()).await?)
+}
+
 #[tauri::command]
 pub async fn minecraft_auth_update_norisk_token(
     credentials: Credentials,
 ) -> Result<Credentials, Error> {
     let mut accounts = minecraft_auth_get_store().await?;
     Ok(accounts
-        .refresh_norisk_token_if_necessary(&credentials, false)
+        .refresh_norisk_token_if_necessary(&credentials, false, true)
         .await?)
 }
 
@@ -763,7 +741,7 @@ pub async fn minecraft_auth_update_mojang_and_norisk_token(
 ) -> Result<Credentials, Error> {
     return Ok(minecraft_auth_get_store()
         .await?
-        .update_norisk_and_microsoft_token(&credentials)
+        .update_norisk_and_microsoft_token(&credentials, true)
         .await?
         .ok_or(ErrorKind::NoCredentialsError)?);
 }
@@ -771,7 +749,8 @@ pub async fn minecraft_auth_update_mojang_and_norisk_token(
 #[tauri::command]
 pub async fn get_options() -> Result<LauncherOptions, crate::error::Error> {
     let config_dir = LAUNCHER_DIRECTORY.config_dir();
-    Ok(LauncherOptions::load(config_dir).await.unwrap_or_default()) // default to basic options if unable to load
+    let options = LauncherOptions::load(config_dir).await.unwrap_or_default(); // default to basic options if unable to load
+    Ok(options)
 }
 
 #[tauri::command]
@@ -926,91 +905,73 @@ async fn save_custom_datapack_to_folder(
 }
 
 #[tauri::command]
-async fn enable_keep_local_assets() -> Result<(), String> {
-    let config_dir = LAUNCHER_DIRECTORY.config_dir();
-
-    let file = config_dir.join("keepLocalAssets");
-
-    if !file.exists() {
-        info!("Creating keepLocalAssets file: {:?}", file);
-        let content = "Hi, what are you doing here?\nMsg me on discord (aim_shock) for a free cookie!\n\nBye <3";
-        fs::write(&file, Vec::from(content))
+async fn get_asset_override_directory() -> Result<Option<String>, String> {
+    // The override directory replaced the keepLocalAssets marker file, which skipped all Norisk assets
+    let marker = LAUNCHER_DIRECTORY.config_dir().join("keepLocalAssets");
+    if marker.exists() {
+        info!("Removing obsolete keepLocalAssets file: {:?}", marker);
+        fs::remove_file(&marker)
             .await
-            .map_err(|e| format!("unable to create keepLocalAssets file: {:?}", e))?;
+            .map_err(|e| format!("unable to remove keepLocalAssets file: {:?}", e))?;
     }
 
-    Ok(())
+    Ok(AssetOverrideSettings::load().await.directory)
 }
 
 #[tauri::command]
-async fn disable_keep_local_assets() -> Result<(), String> {
-    let config_dir = LAUNCHER_DIRECTORY.config_dir();
+async fn set_asset_override_directory(directory: String) -> Result<(), String> {
+    if !PathBuf::from(&directory).is_dir() {
+        return Err(format!("Asset override directory {} doesn't exist", directory));
+    }
 
-    let file = config_dir.join("keepLocalAssets");
+    info!("Setting asset override directory to {}", directory);
+    AssetOverrideSettings { directory: Some(directory) }
+        .store()
+        .await
+        .map_err(|e| format!("unable to store asset override directory: {:?}", e))
+}
 
-    if file.exists() {
-        info!("Removing keepLocalAssets file: {:?}", file);
-        fs::remove_file(&file)
+#[tauri::command]
+async fn clear_asset_override_directory() -> Result<(), String> {
+    let settings = AssetOverrideSettings::load().await;
+    if settings.directory.is_some() {
+        info!("Clearing asset override directory");
+        AssetOverrideSettings::default()
+            .store()
             .await
-            .map_err(|e| format!("unable to remove keepLocalAssets file: {:?}", e))?;
+            .map_err(|e| format!("unable to store asset override directory: {:?}", e))?;
     }
 
     Ok(())
 }
 
 #[tauri::command]
-pub fn get_keep_local_assets() -> Result<bool, String> {
-    let config_dir = LAUNCHER_DIRECTORY.config_dir();
-
-    let file = config_dir.join("keepLocalAssets");
-
-    Ok(file.exists())
+async fn get_player_skins(uuid: String) -> Result<Vec<String>, String> {
+    PROFILE_CACHE
+        .textures_by_uuid(&uuid)
+        .await
+        .map_err(|e| format!("Failed to retrieve Minecraft profile: {:?}", e))
 }
 
 #[tauri::command]
-async fn get_player_skins(uuid: String) -> Result<Vec<String>, String> {
-    let minecraft_profile: Result<MinecraftProfile, reqwest::Error> = HTTP_CLIENT
-        .get(format!(
-            "https://sessionserver.mojang.com/session/minecraft/profile/{}",
-            uuid
-        ))
-        .send()
+async fn mc_uuids_by_names(names: Vec<String>) -> Result<HashMap<String, String>, String> {
+    PROFILE_CACHE
+        .uuids_by_names(&names)
         .await
-        .map_err(|e| format!("unable to connect to sessionserver.mojang.com: {:}", e))?
-        .error_for_status()
-        .map_err(|e| format!("sessionserver.mojang.com returned an error: {:}", e))?
-        .json()
-        .await;
-
-    match minecraft_profile {
-        Ok(profile) => {
-            let mut textures: Vec<String> = vec![];
-            for property in profile.properties.iter() {
-                if property.name == "textures" {
-                    textures.push(property.value.clone())
-                }
-            }
-            Ok(textures)
-        }
-        Err(_) => Err("Failed to retrieve Minecraft profile".to_string()), // You can provide a custom error message here.
-    }
+        .map_err(|e| format!("unable to get mc uuids by names: {:?}", e))
 }
 
-#[tauri::command]
-async fn save_player_skin(
-    location: String,
-    slim: bool,
-    access_token: String,
-) -> Result<(), String> {
-    let file_data = match tokio::fs::read(&location).await {
-        Ok(data) => data,
-        Err(e) => return Err(e.to_string()),
-    };
+#[derive(Deserialize)]
+struct SkinUploadProfile {
+    id: String,
+}
 
+/// Uploads the skin to the Minecraft profile of the access token
+async fn upload_player_skin(file_data: Vec<u8>, slim: bool, access_token: &str) -> Result<(), String> {
     let part = Part::bytes(file_data).file_name("skin.png");
 
     let response = HTTP_CLIENT
-        .post("https://api.minecraftservices.com/minecraft/profile/skins")
+        .post(format!("{}/minecraft/profile/skins", ENDPOINTS.minecraft_services))
         .bearer_auth(access_token)
         .multipart(
             Form::new()
@@ -1022,7 +983,10 @@ async fn save_player_skin(
         .map_err(|e| format!("Failed to send request: {}", e))?;
 
     if response.status().is_success() {
-        info!("Skin {} saved successfully.", &location);
+        // The response is the updated profile, its cached textures are outdated now
+        if let Ok(profile) = response.json::<SkinUploadProfile>().await {
+            PROFILE_CACHE.invalidate_profile(&profile.id);
+        }
         Ok(())
     } else {
         Err(format!(
@@ -1032,6 +996,142 @@ async fn save_player_skin(
     }
 }
 
+#[tauri::command]
+async fn save_player_skin(
+    location: String,
+    slim: bool,
+    access_token: String,
+) -> Result<(), String> {
+    let file_data = match tokio::fs::read(&location).await {
+        Ok(data) => data,
+        Err(e) => return Err(e.to_string()),
+    };
+
+    upload_player_skin(file_data.clone(), slim, &access_token).await?;
+    info!("Skin {} saved successfully.", &location);
+
+    // The upload already succeeded, a full library is no reason to fail it
+    let name = Path::new(&location).file_stem().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let added = match SkinLibrary::load().await {
+        Ok(mut library) => library.add(&file_data, &name, SkinVariant::from_slim(slim)).await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = added {
+        error!("Failed to add skin {} to the library: {:?}", &location, err);
+    }
+
+    Ok(())
+}
+
+async fn load_skin_library() -> Result<SkinLibrary, String> {
+    SkinLibrary::load().await.map_err(|e| format!("Failed to load skin library: {:?}", e))
+}
+
+#[tauri::command]
+async fn get_skin_library() -> Result<Vec<LibrarySkin>, String> {
+    Ok(load_skin_library().await?.sorted())
+}
+
+#[tauri::command]
+async fn import_library_skin(location: String, slim: bool, name: Option<String>) -> Result<LibrarySkin, String> {
+    let file_data = fs::read(&location)
+        .await
+        .map_err(|e| format!("Failed to read the file: {}", e))?;
+    let name = name.unwrap_or_else(|| {
+        Path::new(&location).file_stem().map(|name| name.to_string_lossy().to_string()).unwrap_or_default()
+    });
+
+    load_skin_library()
+        .await?
+        .add(&file_data, &name, SkinVariant::from_slim(slim))
+        .await
+        .map_err(|e| format!("Failed to import skin: {:?}", e))
+}
+
+#[tauri::command]
+async fn rename_library_skin(hash: String, name: String) -> Result<(), String> {
+    load_skin_library()
+        .await?
+        .rename(&hash, name.trim())
+        .await
+        .map_err(|e| format!("Failed to rename skin: {:?}", e))
+}
+
+#[tauri::command]
+async fn delete_library_skin(hash: String) -> Result<(), String> {
+    let mut library = load_skin_library().await?;
+    library
+        .remove(&hash)
+        .await
+        .map_err(|e| format!("Failed to delete skin: {:?}", e))?;
+
+    // Renders of remote textures get rendered again when they are needed, so only the library skins keep theirs
+    let hashes = library.skins.iter().map(|skin| skin.hash.clone()).collect::<Vec<_>>();
+    if let Err(err) = SkinRenderer::prune(&hashes).await {
+        error!("Failed to remove renders of skin {}: {:?}", hash, err);
+    }
+    Ok(())
+}
+
+#[tauri::command]
+async fn read_library_skin(hash: String) -> Result<String, String> {
+    let path = load_skin_library().await?.skin_path(&hash).map_err(|e| e.to_string())?;
+    let file_data = fs::read(path)
+        .await
+        .map_err(|e| format!("Failed to read skin {}: {}", hash, e))?;
+    Ok(base64::encode(file_data))
+}
+
+/// Uploads a skin of the library again with its stored variant
+#[tauri::command]
+async fn apply_library_skin(hash: String, access_token: String) -> Result<(), String> {
+    let mut library = load_skin_library().await?;
+    let variant = library.get(&hash).map_err(|e| e.to_string())?.variant;
+    let file_data = fs::read(library.skin_path(&hash).map_err(|e| e.to_string())?)
+        .await
+        .map_err(|e| format!("Failed to read skin {}: {}", hash, e))?;
+
+    upload_player_skin(file_data, variant.is_slim(), &access_token).await?;
+    info!("Skin {} of the library applied successfully.", &hash);
+
+    library.touch(&hash).await.map_err(|e| format!("Failed to update skin library: {:?}", e))
+}
+
+#[tauri::command]
+async fn render_skin_preview(source: String, kind: RenderKind, slim: bool, scale: u32) -> Result<String, String> {
+    let texture = SkinRenderer::load_texture(&source)
+        .await
+        .map_err(|e| format!("Failed to load skin: {:?}", e))?;
+    SkinRenderer::render(texture, kind, slim, scale)
+        .await
+        .map(base64::encode)
+        .map_err(|e| format!("Failed to render skin: {:?}", e))
+}
+
+#[tauri::command]
+async fn render_cape_preview(source: String, scale: u32) -> Result<String, String> {
+    let texture = SkinRenderer::load_texture(&source)
+        .await
+        .map_err(|e| format!("Failed to load cape: {:?}", e))?;
+    SkinRenderer::render(texture, RenderKind::Cape, false, scale)
+        .await
+        .map(base64::encode)
+        .map_err(|e| format!("Failed to render cape: {:?}", e))
+}
+
+#[tauri::command]
+async fn render_library_skin_preview(hash: String, kind: RenderKind, scale: u32) -> Result<String, String> {
+    let library = load_skin_library().await?;
+    let variant = library.get(&hash).map_err(|e| e.to_string())?.variant;
+    let texture = fs::read(library.skin_path(&hash).map_err(|e| e.to_string())?)
+        .await
+        .map_err(|e| format!("Failed to read skin {}: {}", hash, e))?;
+    SkinRenderer::render(texture, kind, variant.is_slim(), scale)
+        .await
+        .map(base64::encode)
+        .map_err(|e| format!("Failed to render skin: {:?}", e))
+}
+
 #[tauri::command]
 async fn read_local_skin_file(location: String) -> Result<String, String> {
     match fs::File::open(&location).await {
@@ -1048,25 +1148,21 @@ async fn read_local_skin_file(location: String) -> Result<String, String> {
 
 #[tauri::command]
 async fn read_remote_image_file(location: String) -> Result<String, String> {
-    let response = HTTP_CLIENT
-        .get(&location)
-        .send()
+    download_bytes_with_priority(&location, DownloadPriority::Background)
         .await
-        .map_err(|e| format!("unable to connect to {}: {:}", e, location))?
-        .error_for_status()
-        .map_err(|e| format!("{} returned an error: {:}", location, e))?
-        .bytes()
-        .await;
-
-    match response {
-        Ok(bytes) => Ok(base64::encode(&bytes)),
-        Err(_) => Err("Failed to fetch cape from remote resource".to_string()),
-    }
+        .map(base64::encode)
+        .map_err(|e| format!("Failed to fetch {}: {:?}", location, e))
 }
 
 #[tauri::command]
-async fn store_options(options: LauncherOptions) -> Result<(), String> {
+async fn store_options(mut options: LauncherOptions) -> Result<(), String> {
     let config_dir = LAUNCHER_DIRECTORY.config_dir();
+    // The frontend never gets the secrets, the http client still needs the stored proxy password
+    options.load_secrets(config_dir);
+    options.apply_download_limits();
+    options
+        .apply_network_settings()
+        .map_err(|e| format!("invalid network settings: {:?}", e))?;
     options
         .store(config_dir)
         .await
@@ -1100,15 +1196,15 @@ async fn get_norisk_user(
     )
     .await?;
 
-    // ensure user does not have keepLocalAssets enabled depending on rank
+    // ensure user does not have asset overrides enabled depending on rank
     let allowed_ranks = vec![
         "ADMIN".to_string(),
         "DEVELOPER".to_string(),
         "DESIGNER".to_string(),
     ];
     if !allowed_ranks.contains(&user.rank) {
-        disable_keep_local_assets().await.map_err(|e| {
-            ErrorKind::LauncherError(format!("Disable Keep Local Assets: {:?}", e)).as_error()
+        clear_asset_override_directory().await.map_err(|e| {
+            ErrorKind::LauncherError(format!("Clear Asset Override Directory: {:?}", e)).as_error()
         })?;
     }
     Ok(user)
@@ -1176,12 +1272,8 @@ async fn discord_auth_link(
         .get_token(options.experimental_mode)
         .await?;
     let url = format!(
-        "https://api{}.norisk.gg/api/v1/core/oauth/discord?token={}",
-        if options.experimental_mode.clone() {
-            "-staging"
-        } else {
-            ""
-        },
+        "{}/core/oauth/discord?token={}",
+        get_api_base(options.experimental_mode),
         token
     );
 
@@ -1213,7 +1305,7 @@ async fn discord_auth_link(
         if window
             .url()
             .as_str()
-            .starts_with("https://api.norisk.gg/api/v1/core/oauth/discord/complete")
+            .starts_with(&format!("{}/core/oauth/discord/complete", get_api_base(false)))
         {
             tokio::time::sleep(std::time::Duration::from_millis(1000)).await;
             window.close()?;
@@ -1311,32 +1403,7 @@ async fn microsoft_auth(app: tauri::AppHandle) -> Result<Option<Credentials>, Er
                     .login_finish(&code.clone(), flow, app.get_window("main").unwrap())
                     .await?;
 
-                app.get_window("main")
-                    .unwrap()
-                    .emit("microsoft-output", "signIn.step.noriskToken")
-                    .unwrap_or_default();
-                match accounts
-                    .refresh_norisk_token_if_necessary(&credentials.clone(), true)
-                    .await
-                {
-                    Ok(credentials_with_norisk) => {
-                        debug!("After Microsoft Auth: Successfully received NoRiskClient Token");
-                        return Ok(Some(credentials_with_norisk));
-                    }
-                    Err(err) => {
-                        //Ist uns aber egal Microsoft Auth hat geklappt
-                        debug!(
-                            "After Microsoft Auth: Error Fetching NoRiskClient Token {:?}",
-                            err
-                        );
-                        app.get_window("main")
-                            .unwrap()
-                            .emit("microsoft-output", "signIn.step.notWhitelisted")
-                            .unwrap_or_default();
-                    }
-                }
-
-                return Ok(Some(credentials.clone()));
+                return Ok(Some(fetch_norisk_token_after_login(&app, &mut accounts, credentials).await));
             }
         }
 
@@ -1347,6 +1414,101 @@ async fn microsoft_auth(app: tauri::AppHandle) -> Result<Option<Credentials>, Er
     Ok(None)
 }
 
+/// Signs in with a code that is entered on the Microsoft verification page, the code is sent with the
+/// `microsoft-device-code` event. Returns none if the code expired or the login was cancelled.
+#[tauri::command]
+async fn microsoft_device_auth(
+    app: tauri::AppHandle,
+    app_state: tauri::State<'_, AppState>,
+) -> Result<Option<Credentials>, Error> {
+    // Registered before the code is requested, so closing the modal right away cancels the login too
+    let cancellation = CancellationToken::new();
+    if let Some(previous) = app_state.device_login.lock().unwrap().replace(cancellation.clone()) {
+        previous.cancel();
+    }
+
+    let result = device_login(&app, &cancellation).await;
+
+    // A newer login may have replaced the token already
+    let mut current = app_state.device_login.lock().unwrap();
+    if current.as_ref() == Some(&cancellation) {
+        *current = None;
+    }
+    result
+}
+
+async fn device_login(app: &tauri::AppHandle, cancellation: &CancellationToken) -> Result<Option<Credentials>, Error> {
+    let mut accounts = MinecraftAuthStore::init(None).await?;
+    let main_window = app.get_window("main").unwrap();
+
+    let flow = match cancellation.run(device_code()).await {
+        Ok(flow) => flow?,
+        Err(_) => {
+            emit_sign_in_cancelled(app);
+            return Ok(None);
+        }
+    };
+    main_window.emit("microsoft-device-code", &flow)?;
+
+    let result = accounts.login_device_code(&flow, cancellation, main_window).await;
+    if cancellation.is_cancelled() {
+        emit_sign_in_cancelled(app);
+        return Ok(None);
+    }
+
+    match result? {
+        Some(credentials) => Ok(Some(fetch_norisk_token_after_login(app, &mut accounts, credentials).await)),
+        None => {
+            emit_sign_in_cancelled(app);
+            Ok(None)
+        }
+    }
+}
+
+#[tauri::command]
+async fn cancel_microsoft_device_auth(app_state: tauri::State<'_, AppState>) -> Result<(), Error> {
+    if let Some(cancellation) = app_state.device_login.lock().unwrap().take() {
+        cancellation.cancel();
+    }
+    Ok(())
+}
+
+fn emit_sign_in_cancelled(app: &tauri::AppHandle) {
+    app.get_window("main")
+        .unwrap()
+        .emit("microsoft-output", "signIn.step.cancelled")
+        .unwrap_or_default();
+}
+
+/// The NoRisk token is optional, a login still succeeds if the user isn't whitelisted
+async fn fetch_norisk_token_after_login(app: &tauri::AppHandle, accounts: &mut MinecraftAuthStore, credentials: Credentials) -> Credentials {
+    app.get_window("main")
+        .unwrap()
+        .emit("microsoft-output", "signIn.step.noriskToken")
+        .unwrap_or_default();
+    match accounts
+        .refresh_norisk_token_if_necessary(&credentials.clone(), true, true)
+        .await
+    {
+        Ok(credentials_with_norisk) => {
+            debug!("After Microsoft Auth: Successfully received NoRiskClient Token");
+            credentials_with_norisk
+        }
+        Err(err) => {
+            //Ist uns aber egal Microsoft Auth hat geklappt
+            debug!(
+                "After Microsoft Auth: Error Fetching NoRiskClient Token {:?}",
+                err
+            );
+            app.get_window("main")
+                .unwrap()
+                .emit("microsoft-output", "signIn.step.notWhitelisted")
+                .unwrap_or_default();
+            credentials
+        }
+    }
+}
+
 fn handle_stdout(window: &Arc<Mutex<Window>>, data: &[u8], uuid: Uuid) -> anyhow::Result<()> {
     let data = String::from_utf8(data.to_vec())?;
     if data.is_empty() {
@@ -1561,155 +1723,150 @@ async fn copy_branch_data(
     McDataHandler::copy_branch_data(old_branch, new_branch, app).await
 }
 
-#[tauri::command]
-async fn run_client(
-    branch: String,
-    options: LauncherOptions,
-    force_server: Option<String>,
-    mods: Vec<LoaderMod>,
-    window: Window,
-    app_state: tauri::State<'_, AppState>,
-    app: tauri::AppHandle,
-) -> Result<Uuid, Error> {
-    debug!("Starting Client with branch {}", branch);
-    let runner_id = Uuid::new_v4(); // Erzeuge eine neue UUID für die Instanz
-    window.emit("open-start-progress", runner_id);
-
-    fs::create_dir_all(&LAUNCHER_DIRECTORY.data_dir().join("nrc_cache")).await?;
-
+/// Credentials of the given account or the default account.
+/// The stored credentials are used if the tokens can't be refreshed, e.g. while offline
+async fn get_launch_credentials(account_id: Option<Uuid>) -> Result<Credentials, Error> {
     let mut accounts = minecraft_auth_get_store().await?;
 
-    let credentials = match accounts.get_default_credential().await {
+    // Profiles can still reference removed accounts, they are launched with the default account then
+    let account_id = account_id.filter(|id| accounts.users.contains_key(id));
+    let refreshed = match account_id {
+        Some(account_id) => accounts.get_credential(account_id).await,
+        None => accounts.get_default_credential().await,
+    };
+
+    Ok(match refreshed {
         Ok(creds) => creds,
         Err(_) => Option::from(
             accounts
                 .users
-                .get(&accounts.default_user.ok_or(ErrorKind::NoCredentialsError)?)
+                .get(&account_id.or(accounts.default_user).ok_or(ErrorKind::NoCredentialsError)?)
                 .ok_or(ErrorKind::NoCredentialsError)?
                 .clone(),
         ),
     }
-    .ok_or(ErrorKind::NoCredentialsError)?;
+    .ok_or(ErrorKind::NoCredentialsError)?)
+}
 
-    debug!("Starting Minecraft with Account {:?}", credentials.username);
+/// Launch parameters shared by NoRisk and vanilla launches
+fn launching_parameter(options: &LauncherOptions, credentials: &Credentials, force_server: Option<String>) -> LaunchingParameter {
+    // The frontend never gets the secrets, the repositories and the proxy need the stored ones
+    let mut options = options.clone();
+    options.load_secrets(LAUNCHER_DIRECTORY.config_dir());
 
-    let window_mutex = Arc::new(std::sync::Mutex::new(window));
-
-    let parameters = LaunchingParameter {
+    LaunchingParameter {
         dev_mode: options.experimental_mode,
-        force_server: force_server,
+        force_server,
         memory: options.memory_limit,
         data_path: options.data_path_buf(),
         custom_java_path: if !options.custom_java_path.is_empty() {
-            Some(options.custom_java_path)
+            Some(options.custom_java_path.clone())
         } else {
             None
         },
-        custom_java_args: options.custom_java_args,
-        auth_player_name: credentials.username,
+        custom_java_args: options.custom_java_args.clone(),
+        auth_player_name: credentials.username.clone(),
         auth_uuid: credentials.id.to_string(),
-        auth_access_token: credentials.access_token,
+        auth_access_token: credentials.access_token.clone(),
         auth_xuid: "x".to_string(),
         clientid: auth::AZURE_CLIENT_ID.to_string(),
-        user_type: "msa".to_string(),
+        // Offline accounts are launched like legacy accounts of offline mode servers
+        user_type: if credentials.is_offline() { "legacy" } else { "msa" }.to_string(),
         keep_launcher_open: options.keep_launcher_open,
         concurrent_downloads: options.concurrent_downloads,
-    };
-
-    let token = if options.experimental_mode {
-        credentials
-            .norisk_credentials
-            .experimental
-            .ok_or(ErrorKind::NoCredentialsError)?
-            .value
-    } else {
-        credentials
-            .norisk_credentials
-            .production
-            .ok_or(ErrorKind::NoCredentialsError)?
-            .value
-    };
-
-    info!("Loading launch manifest...");
-    let launch_manifest = get_launch_manifest(&branch, &token, credentials.id).await?;
+        custom_repositories: options.custom_repositories.clone(),
+        proxy: options.proxy.clone(),
+        trusted_certificates: options.trusted_certificates.clone(),
+        full_verify: options.full_verify,
+    }
+}
 
+/// Registers a runner instance and runs the launch on its own thread.
+/// `launch` is told whether this is the first instance of the branch, failures are reported to the frontend
+/// and the instance is removed again once the client exited.
+fn spawn_instance<F, Fut>(
+    runner_id: Uuid,
+    branch: String,
+    account: InstanceAccount,
+    keep_launcher_open: bool,
+    window: Window,
+    app_state: &AppState,
+    launch: F,
+) where
+    F: FnOnce(bool, LauncherData<Arc<Mutex<Window>>>, Arc<Mutex<Window>>) -> Fut + Send + 'static,
+    Fut: Future<Output = anyhow::Result<()>>,
+{
+    let window_mutex = Arc::new(Mutex::new(window));
     let (terminator_tx, terminator_rx) = tokio::sync::oneshot::channel();
+    let cancellation = CancellationToken::new();
 
-    let runner_instances = Arc::clone(&app_state.runner_instances); // Verwende Arc für die Zustandsverwaltung
+    let runner_instances = Arc::clone(&app_state.runner_instances);
     runner_instances.lock().unwrap().push(RunnerInstance {
         terminator: Some(terminator_tx),
-        id: runner_id.clone(), // Speichern der ID
+        cancellation: Some(cancellation.clone()),
+        id: runner_id,
         progress_updates: Vec::new(),
         p_id: None,
         is_attached: true,
         branch: branch.clone(),
+        account: Some(account),
     });
 
     thread::spawn(move || {
-        let runner_instances = runner_instances.clone(); // Der Zustand ist jetzt für den Thread verfügbar
-
         let is_first_instance_of_branch = !runner_instances
             .lock()
             .unwrap()
             .iter()
-            .filter(|instance| {
-                return instance.id != runner_id;
-            })
-            .any(|instance| {
-                return instance.branch == branch;
-            });
+            .filter(|instance| instance.id != runner_id)
+            .any(|instance| instance.branch == branch);
 
         tokio::runtime::Builder::new_current_thread()
             .enable_all()
             .build()
             .unwrap()
             .block_on(async {
-                let keep_launcher_open = parameters.keep_launcher_open;
-
-                if let Err(e) = prelauncher::launch(
-                    options.multiple_instances || is_first_instance_of_branch,
-                    &token,
-                    &credentials.id.to_string(),
-                    launch_manifest,
-                    parameters,
-                    mods,
-                    LauncherData {
-                        instance_id: runner_id,
-                        instances: runner_instances.clone(),
-                        on_stdout: handle_stdout,
-                        on_stderr: handle_stderr,
-                        on_progress: handle_progress,
-                        data: Box::new(window_mutex.clone()),
-                        terminator: terminator_rx,
-                    },
-                    window_mutex.clone(),
-                    runner_id.clone(),
-                )
-                .await
-                {
+                let launcher_data = LauncherData {
+                    instance_id: runner_id,
+                    instances: runner_instances.clone(),
+                    on_stdout: handle_stdout,
+                    on_stderr: handle_stderr,
+                    on_progress: handle_progress,
+                    data: Box::new(window_mutex.clone()),
+                    terminator: terminator_rx,
+                    cancellation,
+                };
+
+                if let Err(e) = launch(is_first_instance_of_branch, launcher_data, window_mutex.clone()).await {
                     if !keep_launcher_open {
                         window_mutex.lock().unwrap().show().unwrap();
                     }
 
-                    window_mutex
-                        .lock()
-                        .unwrap()
-                        .emit("client-error", format!("Failed to launch client: {:?}", e))
+                    if let Some(LauncherError::Cancelled) = e.downcast_ref::<LauncherError>() {
+                        info!("Launch of {} was cancelled", runner_id);
+                        window_mutex
+                            .lock()
+                            .unwrap()
+                            .emit("client-cancelled", runner_id)
+                            .unwrap();
+                    } else {
+                        window_mutex
+                            .lock()
+                            .unwrap()
+                            .emit("client-error", format!("Failed to launch client: {:?}", e))
+                            .unwrap();
+                        handle_stderr(
+                            &window_mutex,
+                            format!("Failed to launch client: {:?}", e).as_bytes(),
+                            runner_id,
+                        )
                         .unwrap();
-                    handle_stderr(
-                        &window_mutex,
-                        format!("Failed to launch client: {:?}", e).as_bytes(),
-                        runner_id,
-                    )
-                    .unwrap();
+                    }
                 }
 
                 // Entferne die Instanz aus der Liste, wenn der Client geschlossen wurde
                 let mut mut_runner_instances = runner_instances.lock().unwrap();
-
-                // Suchen der Instanz anhand der ID
                 if let Some(pos) = mut_runner_instances.iter().position(|r| r.id == runner_id) {
-                    mut_runner_instances.remove(pos); // Entfernen der Instanz
+                    mut_runner_instances.remove(pos);
                     debug!("Removed runner instance with id: {}", runner_id);
                 }
 
@@ -1720,6 +1877,125 @@ async fn run_client(
                     .unwrap();
             });
     });
+}
+
+#[tauri::command]
+async fn run_client(
+    branch: String,
+    options: LauncherOptions,
+    force_server: Option<String>,
+    mods: Vec<LoaderMod>,
+    account_id: Option<Uuid>,
+    window: Window,
+    app_state: tauri::State<'_, AppState>,
+    app: tauri::AppHandle,
+) -> Result<Uuid, Error> {
+    debug!("Starting Client with branch {}", branch);
+    let runner_id = Uuid::new_v4(); // Erzeuge eine neue UUID für die Instanz
+    window.emit("open-start-progress", runner_id);
+
+    fs::create_dir_all(&LAUNCHER_DIRECTORY.data_dir().join("nrc_cache")).await?;
+
+    let credentials = get_launch_credentials(account_id).await?;
+    let instance_account = InstanceAccount {
+        id: credentials.id,
+        username: credentials.username.clone(),
+    };
+
+    debug!("Starting Minecraft with Account {:?}", credentials.username);
+
+    // NoRisk branches need the NoRisk token and only join online mode servers
+    if credentials.is_offline() {
+        return Err(ErrorKind::InputError(format!(
+            "Offline account {} can only launch vanilla versions",
+            credentials.username
+        ))
+        .as_error());
+    }
+
+    let parameters = launching_parameter(&options, &credentials, force_server);
+
+    let token = if options.experimental_mode {
+        credentials
+            .norisk_credentials
+            .experimental
+            .ok_or(ErrorKind::NoCredentialsError)?
+            .value
+    } else {
+        credentials
+            .norisk_credentials
+            .production
+            .ok_or(ErrorKind::NoCredentialsError)?
+            .value
+    };
+
+    info!("Loading launch manifest...");
+    let launch_manifest = get_launch_manifest(&branch, &token, credentials.id).await?;
+
+    let multiple_instances = options.multiple_instances;
+    let uuid = credentials.id.to_string();
+    spawn_instance(runner_id, branch, instance_account, options.keep_launcher_open, window, &app_state, move |is_first_instance, launcher_data, window| async move {
+        prelauncher::launch(
+            multiple_instances || is_first_instance,
+            &token,
+            &uuid,
+            launch_manifest,
+            parameters,
+            mods,
+            launcher_data,
+            window,
+            runner_id,
+        )
+        .await
+    });
+
+    Ok(runner_id)
+}
+
+#[tauri::command]
+async fn get_vanilla_versions() -> Result<Vec<ManifestVersion>, Error> {
+    let cache = LAUNCHER_DIRECTORY.data_dir().join("nrc_cache");
+    fs::create_dir_all(&cache).await?;
+    Ok(VersionManifest::download(&cache).await?.versions)
+}
+
+#[tauri::command]
+async fn run_vanilla_client(
+    version_id: String,
+    options: LauncherOptions,
+    account_id: Option<Uuid>,
+    window: Window,
+    app_state: tauri::State<'_, AppState>,
+) -> Result<Uuid, Error> {
+    debug!("Starting vanilla client {}", version_id);
+    let runner_id = Uuid::new_v4();
+    window.emit("open-start-progress", runner_id);
+
+    // Vanilla versions are the only ones offline accounts can launch
+    let credentials = get_launch_credentials(account_id).await?;
+    let instance_account = InstanceAccount {
+        id: credentials.id,
+        username: credentials.username.clone(),
+    };
+
+    debug!("Starting vanilla Minecraft with Account {:?}", credentials.username);
+
+    let parameters = launching_parameter(&options, &credentials, None);
+
+    // Vanilla versions are tracked like a branch of their own
+    let instance_branch = format!("vanilla-{}", version_id);
+    let multiple_instances = options.multiple_instances;
+    spawn_instance(runner_id, instance_branch, instance_account, options.keep_launcher_open, window, &app_state, move |is_first_instance, launcher_data, window| async move {
+        prelauncher::launch_vanilla(
+            multiple_instances || is_first_instance,
+            &version_id,
+            parameters,
+            launcher_data,
+            window,
+            runner_id,
+        )
+        .await
+    });
 
     Ok(runner_id)
 }
@@ -1767,6 +2043,11 @@ async fn terminate(
     {
         let instance = &mut runner_instances[instance_position];
 
+        // Stops a launch that is still preparing, the game itself is closed by the terminator
+        if let Some(cancellation) = &instance.cancellation {
+            cancellation.cancel();
+        }
+
         // Falls ein Terminator existiert, schließen wir das Spiel damit
         if let Some(terminator) = instance.terminator.take() {
             info!("Closing Game {:?}...", instance_id);
@@ -1847,20 +2128,11 @@ async fn add_player_to_whitelist(
     norisk_token: &str,
     request_uuid: &str,
 ) -> Result<bool, String> {
-    let response = HTTP_CLIENT
-        .get(format!(
-            "https://playerdb.co/api/player/minecraft/{}",
-            identifier
-        ))
-        .send()
-        .await
-        .map_err(|e| format!("invalid username: {:}", e))
-        .unwrap();
-    let response_text = response.json::<PlayerDBData>().await.unwrap();
-    let uuid = match response_text.data.player {
-        Some(player) => player.id,
-        None => return Err("invalid username / uuid".to_string()),
-    };
+    let uuid = match Uuid::parse_str(identifier) {
+        Ok(uuid) => PROFILE_CACHE.profile(&uuid.to_string()).await.map(|_| uuid.to_string()),
+        Err(_) => PROFILE_CACHE.uuid_by_name(identifier).await,
+    }
+    .map_err(|e| format!("invalid username / uuid: {:?}", e))?;
     ApiEndpoints::whitelist_add_user(&uuid, norisk_token, request_uuid)
         .await
         .map_err(|e| format!("unable to add player to whitelist: {:?}", e))
@@ -2401,6 +2673,12 @@ pub fn gui_main() {
         .plugin(tauri_plugin_fs_watch::init())
         .setup(|app| {
             NRCCache::initialize_app_state(app);
+            // Later changes of the limits are applied when the options are stored
+            match tauri::async_runtime::block_on(LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir())) {
+                Ok(options) => options.apply_download_limits(),
+                Err(err) => error!("Failed to load the download limits: {:?}", err),
+            }
+            start_token_refresher(app.handle());
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
@@ -2419,6 +2697,7 @@ pub fn gui_main() {
             minecraft_auth_get_default_user_no_check,
             minecraft_auth_remove_user,
             minecraft_auth_users,
+            minecraft_auth_add_offline_user,
             minecraft_auth_update_norisk_token,
             minecraft_auth_update_mojang_and_norisk_token,
             store_options,
@@ -2429,17 +2708,29 @@ pub fn gui_main() {
             discord_auth_link,
             discord_auth_status,
             discord_auth_unlink,
-            check_cape_resolution,
+            normalize_cape_image,
             upload_cape,
             equip_cape,
             delete_cape,
             get_player_skins,
+            mc_uuids_by_names,
             save_player_skin,
+            get_skin_library,
+            import_library_skin,
+            rename_library_skin,
+            delete_library_skin,
+            read_library_skin,
+            apply_library_skin,
             read_local_skin_file,
+            render_skin_preview,
+            render_cape_preview,
+            render_library_skin_preview,
             read_remote_image_file,
             get_cape_hash_by_uuid,
             mc_name_by_uuid,
             microsoft_auth,
+            microsoft_device_auth,
+            cancel_microsoft_device_auth,
             unequip_cape,
             search_mods,
             get_mod_author,
@@ -2460,6 +2751,8 @@ pub fn gui_main() {
             copy_mc_data,
             copy_branch_data,
             run_client,
+            get_vanilla_versions,
+            run_vanilla_client,
             enable_experimental_mode,
             download_template_and_open_explorer,
             request_trending_capes,
@@ -2510,9 +2803,9 @@ pub fn gui_main() {
             get_datapack,
             download_datapack,
             get_world_folders,
-            enable_keep_local_assets,
-            disable_keep_local_assets,
-            get_keep_local_assets,
+            get_asset_override_directory,
+            set_asset_override_directory,
+            clear_asset_override_directory,
             upload_logs,
             get_launch_manifest,
             default_data_folder_path,
@@ -2548,7 +2841,8 @@ pub fn gui_main() {
             check_feature_whitelist,
             get_full_feature_whitelist,
             export_profile_and_open_explorer,
-            import_launcher_profile
+            import_launcher_profile,
+            import_instance
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
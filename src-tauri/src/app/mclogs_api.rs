@@ -1,6 +1,6 @@
 use log::info;
 use serde::{Deserialize, Serialize};
-use crate::HTTP_CLIENT;
+use crate::{ENDPOINTS, HTTP_CLIENT};
 
 /// Placeholder struct for API endpoints implementation
 pub struct McLogsApiEndpoints;
@@ -9,7 +9,7 @@ impl McLogsApiEndpoints {
     pub async fn upload_logs(log: String) -> anyhow::Result<McLogsUploadResponse> {
         info!("Uploading Logs...");
         let form = reqwest::multipart::Form::new().text("content", log);
-        Ok(HTTP_CLIENT.post("https://api.mclo.gs/1/log")
+        Ok(HTTP_CLIENT.post(format!("{}/log", ENDPOINTS.mclogs_api))
             .multipart(form)
             .send().await?
             .error_for_status()?
@@ -1,8 +1,12 @@
 pub mod api;
 pub mod app_data;
 pub mod cape_api;
+pub mod endpoints;
 pub mod gui;
 pub mod mclogs_api;
 pub mod modrinth_api;
 
 pub mod nrc_cache;
+pub mod profile_cache;
+pub mod skin_library;
+pub mod skin_render;
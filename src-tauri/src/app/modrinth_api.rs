@@ -6,14 +6,14 @@ use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 
 use crate::app::api::{LoaderMod, ModSource};
-use crate::HTTP_CLIENT;
+use crate::{ENDPOINTS, HTTP_CLIENT};
 
 /// Placeholder struct for API endpoints implementation
 pub struct ModrinthApiEndpoints;
 
 impl ModrinthApiEndpoints {
     pub async fn get_project_members(slug: &str) -> Result<Vec<ModrinthTeamMember>, crate::error::Error> {
-        let url = format!("https://api.modrinth.com/v2/project/{}/members", slug);
+        let url = format!("{}/project/{}/members", ENDPOINTS.modrinth_api, slug);
         Ok(HTTP_CLIENT.get(url)
             .send()
             .await?
@@ -22,7 +22,7 @@ impl ModrinthApiEndpoints {
     }
 
     pub async fn search_projects<T: DeserializeOwned>(params: &ModrinthSearchRequestParams, custom: Option<HashMap<String, String>>) -> Result<T, crate::error::Error> {
-        let url = format!("https://api.modrinth.com/v2/search?facets={}&index={}&limit={}&offset={}&query={}{}", params.facets, params.index, params.limit, params.offset, params.query, if custom.is_some() { format!("&{}", custom.unwrap().iter().map(|(key, value)| format!("{}={}", key, value)).collect::<Vec<String>>().join("&") ) } else { "".to_string() });
+        let url = format!("{}/search?facets={}&index={}&limit={}&offset={}&query={}{}", ENDPOINTS.modrinth_api, params.facets, params.index, params.limit, params.offset, params.query, if custom.is_some() { format!("&{}", custom.unwrap().iter().map(|(key, value)| format!("{}={}", key, value)).collect::<Vec<String>>().join("&") ) } else { "".to_string() });
         Ok(HTTP_CLIENT.get(url)
             .send()
             .await?
@@ -31,7 +31,7 @@ impl ModrinthApiEndpoints {
     }
 
     pub async fn get_project<T: DeserializeOwned>(slug_or_id: &str) -> Result<T, crate::error::Error> {
-        let url = format!("https://api.modrinth.com/v2/project/{}", slug_or_id);
+        let url = format!("{}/project/{}", ENDPOINTS.modrinth_api, slug_or_id);
         Ok(HTTP_CLIENT.get(url)
             .send()
             .await?
@@ -40,13 +40,34 @@ impl ModrinthApiEndpoints {
     }
 
     pub async fn get_project_version(slug: &str, params: &str) -> Result<Vec<ModrinthProject>, crate::error::Error> {
-        let url = format!("https://api.modrinth.com/v2/project/{}/version{}", slug, params);
+        let url = format!("{}/project/{}/version{}", ENDPOINTS.modrinth_api, slug, params);
         Ok(HTTP_CLIENT.get(url)
             .send()
             .await?
             .json::<Vec<ModrinthProject>>()
             .await?)
     }
+
+    /// Looks up the versions of mod files by their sha1 hash, unknown files are missing in the result
+    pub async fn get_versions_by_hashes(hashes: &[String]) -> Result<HashMap<String, ModrinthProject>, crate::error::Error> {
+        let url = format!("{}/version_files", ENDPOINTS.modrinth_api);
+        Ok(HTTP_CLIENT.post(url)
+            .json(&serde_json::json!({ "hashes": hashes, "algorithm": "sha1" }))
+            .send()
+            .await?
+            .json::<HashMap<String, ModrinthProject>>()
+            .await?)
+    }
+
+    pub async fn get_projects<T: DeserializeOwned>(ids: &[String]) -> Result<Vec<T>, crate::error::Error> {
+        let url = format!("{}/projects", ENDPOINTS.modrinth_api);
+        Ok(HTTP_CLIENT.get(url)
+            .query(&[("ids", serde_json::to_string(ids)?)])
+            .send()
+            .await?
+            .json::<Vec<T>>()
+            .await?)
+    }
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -120,6 +141,15 @@ pub struct Mod {
     pub icon_url: Option<String>,
 }
 
+//Minified response from https://api.modrinth.com/v2/projects?ids=[...]
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ModrinthProjectSummary {
+    pub id: String,
+    pub slug: String,
+    pub title: String,
+    pub icon_url: Option<String>,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct ShaderInfo {
     pub slug: String,
@@ -14,6 +14,7 @@ use crate::app::app_data::LauncherOptions;
 use crate::error::Error;
 use crate::minecraft::minecraft_auth::Credentials;
 use crate::minecraft::progress::ProgressUpdate;
+use crate::utils::CancellationToken;
 use crate::LAUNCHER_DIRECTORY;
 
 use super::gui::NoRiskBranch;
@@ -31,6 +32,8 @@ pub struct RunnerInstance {
     #[serde(skip_serializing)]
     #[serde(skip_deserializing)]
     pub terminator: Option<tokio::sync::oneshot::Sender<()>>,
+    #[serde(skip)]
+    pub cancellation: Option<CancellationToken>,
     pub id: Uuid,  // Speichert die ID direkt als Uuid
     #[serde(rename = "progressUpdates")]
     pub progress_updates: Vec<ProgressUpdate>, // Die Liste der Fortschritts-Updates
@@ -38,23 +41,36 @@ pub struct RunnerInstance {
     #[serde(rename = "isAttached")]
     pub is_attached: bool, //Für LiveLogs
     pub branch: String,
+    /// Account the instance was launched with, missing for instances of older launcher versions
+    #[serde(default)]
+    pub account: Option<InstanceAccount>,
+}
+
+#[derive(serde::Serialize, Deserialize, Clone, Debug)]
+pub struct InstanceAccount {
+    pub id: Uuid,
+    pub username: String,
 }
 
 impl Default for RunnerInstance {
     fn default() -> Self {
         RunnerInstance {
             terminator: None,
+            cancellation: None,
             id: Uuid::new_v4(), // Oder ein Standardwert, den du verwenden möchtest
             progress_updates: Vec::new(),
             p_id: None,
             is_attached: false,
             branch: "".to_string(),
+            account: None,
         }
     }
 }
 
 pub struct AppState {
     pub runner_instances: Arc<Mutex<Vec<RunnerInstance>>>,
+    /// Cancels the running device code login
+    pub device_login: Arc<Mutex<Option<CancellationToken>>>,
 }
 
 impl NoRiskLaunchManifest {
@@ -160,11 +176,13 @@ impl NRCCache {
             })
             .map(|instance| RunnerInstance {
                 terminator: None, // Terminator wird nicht serialisiert
+                cancellation: None,
                 id: instance.id.clone(), // Die ID wird serialisiert
                 progress_updates: instance.progress_updates.clone(),
                 p_id: instance.p_id.clone(),
                 is_attached: instance.terminator.is_some(), //Für LiveLogs
                 branch: instance.branch.clone(),
+                account: instance.account.clone(),
             })
             .collect();
 
@@ -226,6 +244,7 @@ impl NRCCache {
         let instances = Arc::new(Mutex::new(runner_instances));
         app.manage(AppState {
             runner_instances: instances.clone(),
+            device_login: Arc::new(Mutex::new(None)),
         });
         // Store the instances immediately after loading
         NRCCache::store_running_instances(&instances).ok();
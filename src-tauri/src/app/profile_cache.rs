@@ -0,0 +1,384 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{bail, Result};
+use chrono::{DateTime, Duration, Utc};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use uuid::Uuid;
+
+use crate::app::api::get_api_base;
+use crate::app::app_data::LauncherOptions;
+use crate::app::cape_api::McProfile;
+use crate::{ENDPOINTS, HTTP_CLIENT, LAUNCHER_DIRECTORY};
+
+/// Names rarely change, textures and capes should show up soon after they were changed
+const NAME_TTL_HOURS: i64 = 12;
+const TEXTURES_TTL_MINUTES: i64 = 5;
+const CAPE_TTL_MINUTES: i64 = 5;
+
+/// Mojang resolves at most 10 names per bulk request
+const BULK_LOOKUP_LIMIT: usize = 10;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SessionProfile {
+    pub id: String,
+    pub name: String,
+    #[serde(default)]
+    pub properties: Vec<SessionProfileProperty>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct SessionProfileProperty {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+struct Cached<T> {
+    value: T,
+    fetched: DateTime<Utc>,
+}
+
+impl<T> Cached<T> {
+    fn new(value: T) -> Self {
+        Cached { value, fetched: Utc::now() }
+    }
+
+    fn is_fresh(&self, ttl: Duration) -> bool {
+        Utc::now() - self.fetched < ttl
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct ProfileCacheData {
+    /// Session profiles by uuid
+    #[serde(default)]
+    profiles: HashMap<String, Cached<SessionProfile>>,
+    /// Profiles by lowercase name
+    #[serde(default)]
+    names: HashMap<String, Cached<McProfile>>,
+    /// NoRisk cape hashes by api and uuid, see `cape_key`
+    #[serde(default)]
+    capes: HashMap<String, Cached<String>>,
+}
+
+/// Persistent cache of Mojang profile lookups, so browsing capes or managing the whitelist doesn't get rate limited.
+/// Concurrent lookups of the same key share one request and expired entries are still served if the lookup fails.
+pub struct ProfileCache {
+    path: PathBuf,
+    data: Mutex<ProfileCacheData>,
+    pending: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+    store_lock: tokio::sync::Mutex<()>,
+}
+
+/// Uuids are stored hyphenated, Mojang returns them without hyphens
+fn uuid_key(uuid: &str) -> String {
+    Uuid::parse_str(uuid).map(|uuid| uuid.to_string()).unwrap_or_else(|_| uuid.to_lowercase())
+}
+
+/// The experimental and the production api know different capes. The prefix also keeps
+/// cape keys apart from profile keys, which share the pending lookups
+fn cape_key(uuid: &str, experimental_mode: bool) -> String {
+    format!("{}:{}", if experimental_mode { "experimental" } else { "production" }, uuid_key(uuid))
+}
+
+impl ProfileCache {
+    /// Loads the cache, an unreadable cache is treated as empty
+    pub fn load(path: PathBuf) -> Self {
+        let data = match std::fs::read(&path) {
+            Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|err| {
+                warn!("Failed to read profile cache {:?}, starting with an empty cache: {}", path, err);
+                ProfileCacheData::default()
+            }),
+            Err(_) => ProfileCacheData::default(),
+        };
+
+        ProfileCache { path, data: Mutex::new(data), pending: Mutex::new(HashMap::new()), store_lock: tokio::sync::Mutex::new(()) }
+    }
+
+    pub fn default_path() -> PathBuf {
+        LAUNCHER_DIRECTORY.data_dir().join("profile_cache.json")
+    }
+
+    /// Stores are serialized and write a temporary file first, so the cache file always holds one complete snapshot
+    pub async fn store(&self) -> Result<()> {
+        let _guard = self.store_lock.lock().await;
+        let content = serde_json::to_string(&*self.data.lock().unwrap())?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let temp_path = self.path.with_extension("json.tmp");
+        fs::write(&temp_path, content).await?;
+        fs::rename(&temp_path, &self.path).await?;
+        Ok(())
+    }
+
+    async fn store_logged(&self) {
+        if let Err(err) = self.store().await {
+            warn!("Failed to store profile cache: {:?}", err);
+        }
+    }
+
+    /// Returns the cached value if it is fresh, otherwise fetches it. Only one fetch per key runs at a time,
+    /// lookups waiting for it get its result from the cache.
+    async fn cached<T, F, Fut>(
+        &self,
+        key: String,
+        ttl: Duration,
+        entries: fn(&mut ProfileCacheData) -> &mut HashMap<String, Cached<T>>,
+        fetch: F,
+    ) -> Result<T>
+    where
+        T: Clone,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let cached = |cache: &Self| entries(&mut cache.data.lock().unwrap()).get(&key).cloned();
+        if let Some(entry) = cached(self).filter(|entry| entry.is_fresh(ttl)) {
+            return Ok(entry.value);
+        }
+
+        let lock = self.pending.lock().unwrap().entry(key.clone()).or_default().clone();
+        let _guard = lock.lock().await;
+
+        let stale = match cached(self) {
+            Some(entry) if entry.is_fresh(ttl) => return Ok(entry.value),
+            entry => entry,
+        };
+
+        let result = fetch().await;
+        if let Ok(value) = &result {
+            entries(&mut self.data.lock().unwrap()).insert(key.clone(), Cached::new(value.clone()));
+        }
+        // Lookups already waiting keep their handle of the lock, later ones find the fetched value in the cache
+        self.pending.lock().unwrap().remove(&key);
+
+        match result {
+            Ok(value) => {
+                self.store_logged().await;
+                Ok(value)
+            }
+            Err(err) => match stale {
+                Some(entry) => {
+                    warn!("Lookup of {} failed, using the cached value from {}: {:?}", key, entry.fetched, err);
+                    Ok(entry.value)
+                }
+                None => Err(err),
+            },
+        }
+    }
+
+    /// Session profile with name and textures
+    pub async fn profile(&self, uuid: &str) -> Result<SessionProfile> {
+        self.session_profile(uuid, Duration::minutes(TEXTURES_TTL_MINUTES)).await
+    }
+
+    async fn session_profile(&self, uuid: &str, ttl: Duration) -> Result<SessionProfile> {
+        let key = uuid_key(uuid);
+        self.cached(key.clone(), ttl, |data| &mut data.profiles, || async move {
+            debug!("Requesting Minecraft Profile {}", key);
+            let url = format!("{}/session/minecraft/profile/{}", ENDPOINTS.mojang_session, key);
+            let response = HTTP_CLIENT.get(url).send().await?.error_for_status()?;
+            // Unknown uuids have no content instead of an error status
+            if response.status() == reqwest::StatusCode::NO_CONTENT {
+                bail!("No Minecraft profile with the uuid {}", key);
+            }
+            Ok(response.json::<SessionProfile>().await?)
+        }).await
+    }
+
+    pub async fn name_by_uuid(&self, uuid: &str) -> Result<String> {
+        Ok(self.session_profile(uuid, Duration::hours(NAME_TTL_HOURS)).await?.name)
+    }
+
+    /// Base64 encoded textures properties of the profile
+    pub async fn textures_by_uuid(&self, uuid: &str) -> Result<Vec<String>> {
+        Ok(self.profile(uuid).await?
+            .properties
+            .into_iter()
+            .filter(|property| property.name == "textures")
+            .map(|property| property.value)
+            .collect())
+    }
+
+    /// Returns the uuid of the player, hyphenated
+    pub async fn uuid_by_name(&self, name: &str) -> Result<String> {
+        let key = name.to_lowercase();
+        let profile = self.cached(key.clone(), Duration::hours(NAME_TTL_HOURS), |data| &mut data.names, || async move {
+            debug!("Requesting Minecraft UUID {}", key);
+            let url = format!("{}/users/profiles/minecraft/{}", ENDPOINTS.mojang_api, key);
+            let response = HTTP_CLIENT.get(url).send().await?.error_for_status()?;
+            if response.status() == reqwest::StatusCode::NO_CONTENT {
+                bail!("No Minecraft profile with the name {}", key);
+            }
+            Ok(response.json::<McProfile>().await?)
+        }).await?;
+        Ok(uuid_key(&profile.id))
+    }
+
+    /// Looks up the uuids of many players with as few requests as possible, unknown names are left out.
+    /// The result is keyed by the lowercase name, uuids are hyphenated.
+    pub async fn uuids_by_names(&self, names: &[String]) -> Result<HashMap<String, String>> {
+        let ttl = Duration::hours(NAME_TTL_HOURS);
+        let mut uuids = HashMap::new();
+        let mut missing = vec![];
+        {
+            let data = self.data.lock().unwrap();
+            for name in names.iter().map(|name| name.to_lowercase()) {
+                match data.names.get(&name).filter(|entry| entry.is_fresh(ttl)) {
+                    Some(entry) => { uuids.insert(name, uuid_key(&entry.value.id)); }
+                    None if !missing.contains(&name) => missing.push(name),
+                    None => {}
+                }
+            }
+        }
+
+        for chunk in missing.chunks(BULK_LOOKUP_LIMIT) {
+            debug!("Requesting Minecraft UUIDs of {:?}", chunk);
+            let response = async {
+                HTTP_CLIENT.post(format!("{}/profiles/minecraft", ENDPOINTS.mojang_api))
+                    .json(chunk)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json::<Vec<McProfile>>()
+                    .await
+            }.await;
+
+            let mut data = self.data.lock().unwrap();
+            match response {
+                Ok(profiles) => {
+                    for profile in profiles {
+                        let name = profile.name.to_lowercase();
+                        uuids.insert(name.clone(), uuid_key(&profile.id));
+                        data.names.insert(name, Cached::new(profile));
+                    }
+                }
+                Err(err) => {
+                    let stale = chunk.iter()
+                        .filter_map(|name| data.names.get(name).map(|entry| (name.clone(), uuid_key(&entry.value.id))))
+                        .collect::<Vec<_>>();
+                    if stale.len() < chunk.len() {
+                        return Err(err.into());
+                    }
+                    warn!("Bulk lookup failed, using cached uuids of {:?}: {:?}", chunk, err);
+                    uuids.extend(stale);
+                }
+            }
+        }
+
+        if !missing.is_empty() {
+            self.store_logged().await;
+        }
+        Ok(uuids)
+    }
+
+    /// Hash of the NoRisk cape the player has equipped, the api answers with a message if there is none
+    pub async fn cape_hash_by_uuid(&self, uuid: &str) -> Result<String> {
+        let experimental_mode = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default().experimental_mode;
+        let uuid = uuid_key(uuid);
+        let key = cape_key(&uuid, experimental_mode);
+        self.cached(key, Duration::minutes(CAPE_TTL_MINUTES), |data| &mut data.capes, move || async move {
+            debug!("Requesting Cape Hash {}", uuid);
+            let url = format!("{}/cosmetics/user/{}/cape", get_api_base(experimental_mode), uuid);
+            let response = HTTP_CLIENT.get(url).send().await?;
+            if response.status().is_server_error() {
+                bail!("NoRisk api returned {}", response.status());
+            }
+            Ok(response.text().await?)
+        }).await
+    }
+
+    /// Forgets the textures of the player, used after the skin was changed
+    pub fn invalidate_profile(&self, uuid: &str) {
+        self.data.lock().unwrap().profiles.remove(&uuid_key(uuid));
+    }
+
+    /// Forgets the cape of the player, used after a cape was equipped, unequipped, uploaded or deleted
+    pub fn invalidate_cape(&self, uuid: &str) {
+        let mut data = self.data.lock().unwrap();
+        data.capes.remove(&cape_key(uuid, false));
+        data.capes.remove(&cape_key(uuid, true));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use anyhow::anyhow;
+
+    use super::*;
+
+    fn temp_cache() -> ProfileCache {
+        ProfileCache::load(std::env::temp_dir().join(format!("profile_cache_{}.json", Uuid::new_v4())))
+    }
+
+    #[test]
+    fn uuids_are_hyphenated() {
+        assert_eq!(uuid_key("069A79F444E94726A5BEFCA90E38AAF5"), "069a79f4-44e9-4726-a5be-fca90e38aaf5");
+        assert_eq!(uuid_key("069a79f4-44e9-4726-a5be-fca90e38aaf5"), "069a79f4-44e9-4726-a5be-fca90e38aaf5");
+    }
+
+    #[test]
+    fn capes_are_cached_per_api() {
+        let uuid = "069a79f444e94726a5befca90e38aaf5";
+        assert_ne!(cape_key(uuid, true), cape_key(uuid, false));
+        assert_ne!(cape_key(uuid, false), uuid_key(uuid));
+    }
+
+    #[tokio::test]
+    async fn concurrent_lookups_share_one_fetch() {
+        let cache = temp_cache();
+        let fetches = AtomicUsize::new(0);
+        let lookup = || {
+            let fetches = &fetches;
+            cache.cached("key".to_string(), Duration::minutes(5), |data| &mut data.capes, move || async move {
+                fetches.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+                Ok("hash".to_string())
+            })
+        };
+
+        let (first, second) = tokio::join!(lookup(), lookup());
+        assert_eq!(first.unwrap(), "hash");
+        assert_eq!(second.unwrap(), "hash");
+        assert_eq!(fetches.load(Ordering::SeqCst), 1);
+        assert!(cache.pending.lock().unwrap().is_empty());
+
+        let _ = std::fs::remove_file(&cache.path);
+    }
+
+    #[tokio::test]
+    async fn expired_values_are_used_if_the_lookup_fails() {
+        let cache = temp_cache();
+        let ttl = Duration::minutes(5);
+        cache.data.lock().unwrap().capes.insert("key".to_string(), Cached {
+            value: "old".to_string(),
+            fetched: Utc::now() - Duration::hours(1),
+        });
+
+        let value = cache.cached("key".to_string(), ttl, |data| &mut data.capes, || async { Err(anyhow!("offline")) }).await;
+        assert_eq!(value.unwrap(), "old");
+
+        let missing = cache.cached("other".to_string(), ttl, |data| &mut data.capes, || async { Err::<String, _>(anyhow!("offline")) }).await;
+        assert!(missing.is_err());
+        assert!(cache.pending.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn fetched_values_are_stored() {
+        let cache = temp_cache();
+        cache.cached("key".to_string(), Duration::minutes(5), |data| &mut data.capes, || async { Ok("hash".to_string()) }).await.unwrap();
+
+        let loaded = ProfileCache::load(cache.path.clone());
+        assert_eq!(loaded.data.lock().unwrap().capes.get("key").map(|entry| entry.value.clone()), Some("hash".to_string()));
+        assert!(!cache.path.with_extension("json.tmp").exists());
+
+        std::fs::remove_file(&cache.path).unwrap();
+    }
+}
@@ -0,0 +1,187 @@
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use image::GenericImageView;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use tokio::fs;
+
+use crate::LAUNCHER_DIRECTORY;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SkinVariant {
+    Classic,
+    Slim,
+}
+
+impl SkinVariant {
+    pub fn from_slim(slim: bool) -> Self {
+        if slim { SkinVariant::Slim } else { SkinVariant::Classic }
+    }
+
+    pub fn is_slim(self) -> bool {
+        self == SkinVariant::Slim
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LibrarySkin {
+    /// Sha1 of the png, the same skin is only stored once
+    pub hash: String,
+    pub name: String,
+    pub variant: SkinVariant,
+    /// Last time the skin was added or applied
+    pub timestamp: DateTime<Utc>,
+}
+
+/// Every uploaded or imported skin, stored as `<hash>.png` in the skins folder of the config directory
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct SkinLibrary {
+    pub skins: Vec<LibrarySkin>,
+}
+
+impl SkinLibrary {
+    fn directory() -> PathBuf {
+        LAUNCHER_DIRECTORY.config_dir().join("skins")
+    }
+
+    fn path() -> PathBuf {
+        Self::directory().join("skins.json")
+    }
+
+    fn file_path(hash: &str) -> PathBuf {
+        Self::directory().join(format!("{}.png", hash))
+    }
+
+    /// Png of a skin in the library, hashes of other skins or other files are rejected
+    pub fn skin_path(&self, hash: &str) -> Result<PathBuf> {
+        self.get(hash)?;
+        Ok(Self::file_path(hash))
+    }
+
+    /// An unreadable skins.json is an error, storing an empty library over it would lose every skin
+    pub async fn load() -> Result<Self> {
+        match fs::read(Self::path()).await {
+            Ok(content) => serde_json::from_slice(&content).context("skins.json is not a valid skin library"),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err).context("Unable to read skins.json"),
+        }
+    }
+
+    /// Writes a temporary file first, so an interrupted store never leaves a truncated skins.json behind
+    pub async fn store(&self) -> Result<()> {
+        fs::create_dir_all(Self::directory()).await?;
+        let path = Self::path();
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_string_pretty(self)?).await?;
+        fs::rename(&temp_path, &path).await?;
+        Ok(())
+    }
+
+    /// Newest skins first
+    pub fn sorted(&self) -> Vec<LibrarySkin> {
+        let mut skins = self.skins.clone();
+        skins.sort_by(|a, b| b.timestamp.cmp(&a.timestamp));
+        skins
+    }
+
+    /// Adds the skin, a skin which is already in the library only gets the new variant and timestamp
+    pub async fn add(&mut self, data: &[u8], name: &str, variant: SkinVariant) -> Result<LibrarySkin> {
+        validate_skin(data)?;
+        let hash = base16ct::lower::encode_string(&Sha1::digest(data));
+
+        let skin = match self.skins.iter_mut().find(|skin| skin.hash == hash) {
+            Some(skin) => {
+                skin.variant = variant;
+                skin.timestamp = Utc::now();
+                skin.clone()
+            }
+            None => {
+                fs::create_dir_all(Self::directory()).await?;
+                fs::write(Self::file_path(&hash), data).await?;
+                let skin = LibrarySkin { hash, name: name.to_string(), variant, timestamp: Utc::now() };
+                self.skins.push(skin.clone());
+                skin
+            }
+        };
+
+        self.store().await?;
+        Ok(skin)
+    }
+
+    pub fn get(&self, hash: &str) -> Result<&LibrarySkin> {
+        if !is_valid_hash(hash) {
+            bail!("{:?} is not a skin hash", hash);
+        }
+        self.skins.iter().find(|skin| skin.hash == hash).with_context(|| format!("Skin {} is not in the library", hash))
+    }
+
+    pub async fn rename(&mut self, hash: &str, name: &str) -> Result<()> {
+        let skin = self.skins.iter_mut().find(|skin| skin.hash == hash).with_context(|| format!("Skin {} is not in the library", hash))?;
+        skin.name = name.to_string();
+        self.store().await
+    }
+
+    /// Marks the skin as used now, so it moves to the top of the history
+    pub async fn touch(&mut self, hash: &str) -> Result<()> {
+        if let Some(skin) = self.skins.iter_mut().find(|skin| skin.hash == hash) {
+            skin.timestamp = Utc::now();
+        }
+        self.store().await
+    }
+
+    pub async fn remove(&mut self, hash: &str) -> Result<()> {
+        let path = self.skin_path(hash)?;
+        self.skins.retain(|skin| skin.hash != hash);
+        if path.exists() {
+            fs::remove_file(path).await?;
+        }
+        self.store().await
+    }
+}
+
+/// Skins are stored by the lowercase hex sha1 of the png, anything else could point outside the skins folder
+fn is_valid_hash(hash: &str) -> bool {
+    hash.len() == 40 && hash.bytes().all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Minecraft only accepts 64x64 skins and legacy 64x32 skins
+fn validate_skin(data: &[u8]) -> Result<()> {
+    let image = image::load_from_memory_with_format(data, image::ImageFormat::Png).context("The skin is not a valid png")?;
+    match image.dimensions() {
+        (64, 64) | (64, 32) => Ok(()),
+        (width, height) => bail!("The skin has to be 64x64 or 64x32 pixels, not {}x{}", width, height),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_sha1_hashes_are_valid() {
+        assert!(is_valid_hash("da39a3ee5e6b4b0d3255bfef95601890afd80709"));
+        assert!(!is_valid_hash("DA39A3EE5E6B4B0D3255BFEF95601890AFD80709"));
+        assert!(!is_valid_hash("da39a3ee5e6b4b0d3255bfef95601890afd8070"));
+        assert!(!is_valid_hash("../../../../../../../../../../etc/passwd"));
+        assert!(!is_valid_hash("da39a3ee5e6b4b0d3255bfef95601890afd8070/"));
+    }
+
+    #[test]
+    fn unknown_skins_have_no_path() {
+        let library = SkinLibrary {
+            skins: vec![LibrarySkin {
+                hash: "da39a3ee5e6b4b0d3255bfef95601890afd80709".to_string(),
+                name: "skin".to_string(),
+                variant: SkinVariant::Classic,
+                timestamp: Utc::now(),
+            }],
+        };
+
+        assert!(library.get("da39a3ee5e6b4b0d3255bfef95601890afd80709").is_ok());
+        assert!(library.get("0000000000000000000000000000000000000000").is_err());
+        assert!(library.get("../skins.json").is_err());
+    }
+}
@@ -0,0 +1,362 @@
+use std::io::Cursor;
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use image::imageops::{self, FilterType};
+use image::{GenericImageView, ImageOutputFormat, RgbaImage};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use tokio::fs;
+
+use crate::utils::{download_bytes_with_priority, DownloadPriority};
+use crate::LAUNCHER_DIRECTORY;
+
+/// Largest scale a preview can be rendered with, a front preview is 16x32 pixels at scale 1
+const MAX_SCALE: u32 = 32;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RenderKind {
+    Front,
+    Back,
+    Head,
+    Cape,
+}
+
+impl RenderKind {
+    fn name(self) -> &'static str {
+        match self {
+            RenderKind::Front => "front",
+            RenderKind::Back => "back",
+            RenderKind::Head => "head",
+            RenderKind::Cape => "cape",
+        }
+    }
+}
+
+/// Position of a face on the skin texture
+#[derive(Clone, Copy)]
+struct Face {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+const fn face(x: u32, y: u32, width: u32, height: u32) -> Face {
+    Face { x, y, width, height }
+}
+
+/// Front and back face of a body part together with its overlay layer
+struct Part {
+    front: Face,
+    back: Face,
+    front_overlay: Face,
+    back_overlay: Face,
+    /// Only the hat is part of legacy skins
+    legacy_overlay: bool,
+}
+
+const HEAD: Part = Part { front: face(8, 8, 8, 8), back: face(24, 8, 8, 8), front_overlay: face(40, 8, 8, 8), back_overlay: face(56, 8, 8, 8), legacy_overlay: true };
+const BODY: Part = Part { front: face(20, 20, 8, 12), back: face(32, 20, 8, 12), front_overlay: face(20, 36, 8, 12), back_overlay: face(32, 36, 8, 12), legacy_overlay: false };
+const RIGHT_LEG: Part = Part { front: face(4, 20, 4, 12), back: face(12, 20, 4, 12), front_overlay: face(4, 36, 4, 12), back_overlay: face(12, 36, 4, 12), legacy_overlay: false };
+const LEFT_LEG: Part = Part { front: face(20, 52, 4, 12), back: face(28, 52, 4, 12), front_overlay: face(4, 52, 4, 12), back_overlay: face(12, 52, 4, 12), legacy_overlay: false };
+
+const fn right_arm(width: u32) -> Part {
+    Part { front: face(44, 20, width, 12), back: face(48 + width, 20, width, 12), front_overlay: face(44, 36, width, 12), back_overlay: face(48 + width, 36, width, 12), legacy_overlay: false }
+}
+
+const fn left_arm(width: u32) -> Part {
+    Part { front: face(36, 52, width, 12), back: face(40 + width, 52, width, 12), front_overlay: face(52, 52, width, 12), back_overlay: face(56 + width, 52, width, 12), legacy_overlay: false }
+}
+
+/// Front of the cape on a 64x32 cape texture
+const CAPE: Face = face(1, 1, 10, 16);
+
+/// Renders flat previews of skins and capes, rendered previews are cached in the renders folder by texture hash
+pub struct SkinRenderer;
+
+impl SkinRenderer {
+    fn directory() -> PathBuf {
+        LAUNCHER_DIRECTORY.data_dir().join("renders")
+    }
+
+    /// Loads a texture from an url or a local file
+    pub async fn load_texture(source: &str) -> Result<Vec<u8>> {
+        if source.starts_with("http://") || source.starts_with("https://") {
+            download_bytes_with_priority(source, DownloadPriority::Background).await
+        } else {
+            fs::read(source).await.with_context(|| format!("Failed to read texture {}", source))
+        }
+    }
+
+    /// Returns the preview as png, the cape kind ignores `slim`
+    pub async fn render(texture: Vec<u8>, kind: RenderKind, slim: bool, scale: u32) -> Result<Vec<u8>> {
+        if scale == 0 || scale > MAX_SCALE {
+            bail!("The scale has to be between 1 and {}", MAX_SCALE);
+        }
+
+        let hash = base16ct::lower::encode_string(&Sha1::digest(&texture));
+        let variant = if slim && kind != RenderKind::Cape && kind != RenderKind::Head { "slim" } else { "classic" };
+        let path = Self::directory().join(format!("{}-{}-{}-{}.png", hash, kind.name(), variant, scale));
+        if let Ok(cached) = fs::read(&path).await {
+            return Ok(cached);
+        }
+
+        // Decoding and scaling large capes takes a while, it must not block the async runtime
+        let png = tokio::task::spawn_blocking(move || render_png(&texture, kind, slim, scale)).await??;
+
+        fs::create_dir_all(Self::directory()).await?;
+        fs::write(&path, &png).await?;
+        Ok(png)
+    }
+
+    /// Removes the renders of every texture not in `keep`, e.g. of skins deleted from the library
+    pub async fn prune(keep: &[String]) -> Result<()> {
+        let mut entries = match fs::read_dir(Self::directory()).await {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err.into()),
+        };
+
+        while let Some(entry) = entries.next_entry().await? {
+            let file_name = entry.file_name();
+            let hash = file_name.to_string_lossy().split('-').next().unwrap_or_default().to_string();
+            if !keep.contains(&hash) {
+                debug!("Removing render {:?}", entry.path());
+                fs::remove_file(entry.path()).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn render_png(texture: &[u8], kind: RenderKind, slim: bool, scale: u32) -> Result<Vec<u8>> {
+    let image = image::load_from_memory_with_format(texture, image::ImageFormat::Png).context("The texture is not a valid png")?;
+    let preview = match kind {
+        RenderKind::Front => render_body(&image, slim, false)?,
+        RenderKind::Back => render_body(&image, slim, true)?,
+        RenderKind::Head => render_head(&image)?,
+        RenderKind::Cape => render_cape(&image)?,
+    };
+    let preview = imageops::resize(&preview, preview.width() * scale, preview.height() * scale, FilterType::Nearest);
+
+    let mut png = Vec::new();
+    preview.write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)?;
+    Ok(png)
+}
+
+struct Skin {
+    image: RgbaImage,
+    /// Legacy 64x32 skins have no left limbs and no overlays besides the hat
+    legacy: bool,
+}
+
+impl Skin {
+    fn parse(image: &image::DynamicImage) -> Result<Self> {
+        let legacy = match image.dimensions() {
+            (64, 64) => false,
+            (64, 32) => true,
+            (width, height) => bail!("The skin has to be 64x64 or 64x32 pixels, not {}x{}", width, height),
+        };
+
+        let mut image = image.to_rgba8();
+        if legacy {
+            clear_opaque_hat(&mut image);
+        }
+        Ok(Skin { image, legacy })
+    }
+
+    fn face(&self, face: Face) -> RgbaImage {
+        imageops::crop_imm(&self.image, face.x, face.y, face.width, face.height).to_image()
+    }
+
+    /// Draws the part and its overlay, `mirror` takes the faces of the right limb for legacy skins
+    fn draw(&self, canvas: &mut RgbaImage, part: &Part, mirror: Option<&Part>, back: bool, x: i64, y: i64) {
+        match mirror.filter(|_| self.legacy) {
+            Some(mirror) => {
+                let face = if back { mirror.back } else { mirror.front };
+                imageops::overlay(canvas, &imageops::flip_horizontal(&self.face(face)), x, y);
+            }
+            None => {
+                let (face, overlay) = if back { (part.back, part.back_overlay) } else { (part.front, part.front_overlay) };
+                imageops::overlay(canvas, &self.face(face), x, y);
+                if !self.legacy || part.legacy_overlay {
+                    imageops::overlay(canvas, &self.face(overlay), x, y);
+                }
+            }
+        }
+    }
+}
+
+/// Old skins often filled the unused hat area, Minecraft ignores the hat of legacy skins without any transparent pixel
+fn clear_opaque_hat(image: &mut RgbaImage) {
+    let opaque = (32..64).all(|x| (0..16).all(|y| image.get_pixel(x, y)[3] == 255));
+    if opaque {
+        for x in 32..64 {
+            for y in 0..16 {
+                image.get_pixel_mut(x, y)[3] = 0;
+            }
+        }
+    }
+}
+
+fn render_head(image: &image::DynamicImage) -> Result<RgbaImage> {
+    let skin = Skin::parse(image)?;
+    let mut canvas = RgbaImage::new(8, 8);
+    skin.draw(&mut canvas, &HEAD, None, false, 0, 0);
+    Ok(canvas)
+}
+
+/// Front and back previews are 16x32, the back view shows the left limbs on the left side
+fn render_body(image: &image::DynamicImage, slim: bool, back: bool) -> Result<RgbaImage> {
+    let skin = Skin::parse(image)?;
+    let arm_width = if slim { 3 } else { 4 };
+    let (right_arm, left_arm) = (right_arm(arm_width), left_arm(arm_width));
+
+    let mut canvas = RgbaImage::new(16, 32);
+    skin.draw(&mut canvas, &HEAD, None, back, 4, 0);
+    skin.draw(&mut canvas, &BODY, None, back, 4, 8);
+
+    let arm_x = 4 - arm_width as i64;
+    let (first_arm, second_arm) = if back { ((&left_arm, Some(&right_arm)), (&right_arm, None)) } else { ((&right_arm, None), (&left_arm, Some(&right_arm))) };
+    skin.draw(&mut canvas, first_arm.0, first_arm.1, back, arm_x, 8);
+    skin.draw(&mut canvas, second_arm.0, second_arm.1, back, 12, 8);
+
+    let (first_leg, second_leg) = if back { ((&LEFT_LEG, Some(&RIGHT_LEG)), (&RIGHT_LEG, None)) } else { ((&RIGHT_LEG, None), (&LEFT_LEG, Some(&RIGHT_LEG))) };
+    skin.draw(&mut canvas, first_leg.0, first_leg.1, back, 4, 20);
+    skin.draw(&mut canvas, second_leg.0, second_leg.1, back, 8, 20);
+
+    Ok(canvas)
+}
+
+/// Capes are laid out like a 64x32 texture, higher resolutions scale every face by `width / 64`
+fn render_cape(image: &image::DynamicImage) -> Result<RgbaImage> {
+    let (width, height) = image.dimensions();
+    let factor = match (width, height) {
+        (22, 17) => 1,
+        (width, height) if width >= 64 && width % 64 == 0 && height * 2 == width => width / 64,
+        (width, height) => bail!("Unsupported cape resolution {}x{}", width, height),
+    };
+
+    Ok(image.view(CAPE.x * factor, CAPE.y * factor, CAPE.width * factor, CAPE.height * factor).to_image())
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{DynamicImage, Rgba};
+
+    use super::*;
+
+    /// Every pixel stores its own position, overlay areas are transparent
+    fn coded_skin(height: u32) -> DynamicImage {
+        let overlay = |x: u32, y: u32| (y < 16 && x >= 32) || (32..48).contains(&y) || (y >= 48 && !(16..48).contains(&x));
+        DynamicImage::ImageRgba8(RgbaImage::from_fn(64, height, |x, y| {
+            Rgba([x as u8, y as u8, 0, if overlay(x, y) { 0 } else { 255 }])
+        }))
+    }
+
+    /// Position on the texture the preview pixel was taken from
+    fn source(preview: &RgbaImage, x: u32, y: u32) -> Option<(u8, u8)> {
+        let pixel = preview.get_pixel(x, y);
+        (pixel[3] == 255).then_some((pixel[0], pixel[1]))
+    }
+
+    #[test]
+    fn front_shows_the_right_limbs_on_the_left() {
+        let preview = render_body(&coded_skin(64), false, false).unwrap();
+        assert_eq!(preview.dimensions(), (16, 32));
+        assert_eq!(source(&preview, 4, 0), Some((8, 8)));
+        assert_eq!(source(&preview, 4, 8), Some((20, 20)));
+        assert_eq!(source(&preview, 0, 8), Some((44, 20)));
+        assert_eq!(source(&preview, 12, 8), Some((36, 52)));
+        assert_eq!(source(&preview, 4, 20), Some((4, 20)));
+        assert_eq!(source(&preview, 8, 20), Some((20, 52)));
+    }
+
+    #[test]
+    fn back_shows_the_left_limbs_on_the_left() {
+        let preview = render_body(&coded_skin(64), false, true).unwrap();
+        assert_eq!(source(&preview, 4, 0), Some((24, 8)));
+        assert_eq!(source(&preview, 4, 8), Some((32, 20)));
+        assert_eq!(source(&preview, 0, 8), Some((44, 52)));
+        assert_eq!(source(&preview, 12, 8), Some((52, 20)));
+        assert_eq!(source(&preview, 4, 20), Some((28, 52)));
+        assert_eq!(source(&preview, 8, 20), Some((12, 20)));
+    }
+
+    #[test]
+    fn slim_arms_are_three_pixels_wide() {
+        let preview = render_body(&coded_skin(64), true, false).unwrap();
+        assert_eq!(source(&preview, 0, 8), None);
+        assert_eq!(source(&preview, 1, 8), Some((44, 20)));
+        assert_eq!(source(&preview, 14, 8), Some((38, 52)));
+        assert_eq!(source(&preview, 15, 8), None);
+
+        let back = render_body(&coded_skin(64), true, true).unwrap();
+        assert_eq!(source(&back, 1, 8), Some((43, 52)));
+        assert_eq!(source(&back, 12, 8), Some((51, 20)));
+    }
+
+    #[test]
+    fn legacy_skins_mirror_the_right_limbs() {
+        let preview = render_body(&coded_skin(32), false, false).unwrap();
+        assert_eq!(source(&preview, 12, 8), Some((47, 20)));
+        assert_eq!(source(&preview, 8, 20), Some((7, 20)));
+    }
+
+    #[test]
+    fn overlays_are_drawn_over_the_faces() {
+        let mut skin = coded_skin(64).to_rgba8();
+        skin.put_pixel(40, 8, Rgba([1, 2, 3, 255]));
+        skin.put_pixel(20, 36, Rgba([4, 5, 6, 255]));
+
+        let head = render_head(&DynamicImage::ImageRgba8(skin.clone())).unwrap();
+        assert_eq!(head.dimensions(), (8, 8));
+        assert_eq!(source(&head, 0, 0), Some((1, 2)));
+        assert_eq!(source(&head, 1, 0), Some((9, 8)));
+
+        let front = render_body(&DynamicImage::ImageRgba8(skin), false, false).unwrap();
+        assert_eq!(source(&front, 4, 8), Some((4, 5)));
+    }
+
+    #[test]
+    fn opaque_legacy_hats_are_hidden() {
+        let mut skin = coded_skin(32).to_rgba8();
+        for x in 32..64 {
+            for y in 0..16 {
+                skin.put_pixel(x, y, Rgba([1, 2, 3, 255]));
+            }
+        }
+
+        let head = render_head(&DynamicImage::ImageRgba8(skin)).unwrap();
+        assert_eq!(source(&head, 0, 0), Some((8, 8)));
+    }
+
+    #[test]
+    fn capes_are_cropped_at_every_resolution() {
+        let cape = |width, height| DynamicImage::ImageRgba8(RgbaImage::from_fn(width, height, |x, y| Rgba([x as u8, y as u8, 0, 255])));
+
+        let preview = render_cape(&cape(64, 32)).unwrap();
+        assert_eq!(preview.dimensions(), (10, 16));
+        assert_eq!(source(&preview, 0, 0), Some((1, 1)));
+
+        let preview = render_cape(&cape(128, 64)).unwrap();
+        assert_eq!(preview.dimensions(), (20, 32));
+        assert_eq!(source(&preview, 0, 0), Some((2, 2)));
+
+        assert_eq!(render_cape(&cape(22, 17)).unwrap().dimensions(), (10, 16));
+        assert!(render_cape(&cape(50, 50)).is_err());
+    }
+
+    #[test]
+    fn previews_are_scaled() {
+        let mut png = Vec::new();
+        coded_skin(64).write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png).unwrap();
+
+        let preview = image::load_from_memory(&render_png(&png, RenderKind::Head, false, 4).unwrap()).unwrap();
+        assert_eq!(preview.dimensions(), (32, 32));
+        assert!(render_png(b"not a png", RenderKind::Head, false, 1).is_err());
+    }
+}
@@ -5,7 +5,7 @@ use log::{debug, error, info};
 use tauri::Window;
 use tokio::{fs, process::Child};
 
-use crate::{app::{api::ApiEndpoints, app_data::LauncherOptions, gui::get_options}, custom_servers::{models::{CustomServerEventPayload, CustomServerTokenResponse}, providers::forwarding_manager::ForwardingManagerProvider}, minecraft::{java::{find_java_binary, jre_downloader, JavaRuntime}, progress::ProgressUpdate}, LAUNCHER_DIRECTORY};
+use crate::{app::{api::ApiEndpoints, app_data::LauncherOptions, gui::get_options}, custom_servers::{models::{CustomServerEventPayload, CustomServerTokenResponse}, providers::forwarding_manager::ForwardingManagerProvider}, minecraft::{java::{find_java_binary, jre_downloader, JavaRuntime}, progress::{ProgressTracker, ProgressUpdate, ProgressUpdateSteps}}, LAUNCHER_DIRECTORY};
 use crate::app::gui::minecraft_auth_get_default_user;
 
 use super::{models::{CustomServer, CustomServerProgressEventPayload, CustomServerType, LatestRunningServer}, providers::{forge::ForgeProvider, vanilla::VanillaProvider}};
@@ -19,14 +19,34 @@ impl CustomServerManager {
             CustomServerType::VANILLA => {
                 let label = ProgressUpdate::SetLabel("Downloading server jar...".to_owned());
                 let _ = Self::handle_progress(&window, &server.id, label);
-                let _ = VanillaProvider::download_server_jar(&server, &additional_data.clone().unwrap_or_default()).await;
+                let tracker = ProgressTracker::new(ProgressUpdateSteps::DownloadCustomServerJar, 1, 0);
+                let _ = Self::handle_progress(&window, &server.id, ProgressUpdate::SetState(tracker.state()));
+                let _ = VanillaProvider::download_server_jar(&server, &additional_data.clone().unwrap_or_default(), |a, b| {
+                    if let Some(state) = tracker.set_bytes(a, b) {
+                        let _ = Self::handle_progress(&window, &server.id, ProgressUpdate::SetProgress(a * 100 / b.max(1)));
+                        let _ = Self::handle_progress(&window, &server.id, ProgressUpdate::SetState(state));
+                    }
+                }).await;
                 let _ = Self::create_eula_file(&server).await;
+                if let Some(state) = tracker.complete_item(0, "server.jar") {
+                    let _ = Self::handle_progress(&window, &server.id, ProgressUpdate::SetState(state));
+                }
             }
             CustomServerType::FORGE => {
                 let label = ProgressUpdate::SetLabel("Downloading installer jar...".to_owned());
                 let _ = Self::handle_progress(&window, &server.id, label);
-                let _ = ForgeProvider::download_installer_jar(&server).await;
+                let tracker = ProgressTracker::new(ProgressUpdateSteps::DownloadCustomServerInstallerJar, 1, 0);
+                let _ = Self::handle_progress(&window, &server.id, ProgressUpdate::SetState(tracker.state()));
+                let _ = ForgeProvider::download_installer_jar(&server, |a, b| {
+                    if let Some(state) = tracker.set_bytes(a, b) {
+                        let _ = Self::handle_progress(&window, &server.id, ProgressUpdate::SetProgress(a * 100 / b.max(1)));
+                        let _ = Self::handle_progress(&window, &server.id, ProgressUpdate::SetState(state));
+                    }
+                }).await;
                 let _ = Self::create_eula_file(&server).await;
+                if let Some(state) = tracker.complete_item(0, "installer.jar") {
+                    let _ = Self::handle_progress(&window, &server.id, ProgressUpdate::SetState(state));
+                }
             },
             CustomServerType::FABRIC => todo!(),
             CustomServerType::NEO_FORGE => todo!(),
@@ -70,8 +90,12 @@ impl CustomServerManager {
                         
                         info!("Download JRE...");
                         let _ = Self::handle_progress(&window_mutex, &custom_server.id, ProgressUpdate::SetLabel("Download JRE...".to_owned()))?;
+                        let tracker = ProgressTracker::new(ProgressUpdateSteps::DownloadJRE, 1, 0);
                         jre_downloader::jre_download(&runtimes_folder, 21, |a, b| {
-                            let _ = Self::handle_progress(&window_mutex, &custom_server.id, ProgressUpdate::SetProgress((a / b) * 100));
+                            if let Some(state) = tracker.set_bytes(a, b) {
+                                let _ = Self::handle_progress(&window_mutex, &custom_server.id, ProgressUpdate::SetProgress(a * 100 / b.max(1)));
+                                let _ = Self::handle_progress(&window_mutex, &custom_server.id, ProgressUpdate::SetState(state));
+                            }
                         }).await?
                     }
                 }
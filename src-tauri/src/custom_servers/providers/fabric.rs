@@ -5,37 +5,35 @@ use tokio::fs;
 use log::info;
 
 use crate::custom_servers::models::CustomServer;
-use crate::utils::download_file;
-use crate::{HTTP_CLIENT, LAUNCHER_DIRECTORY};
+use crate::utils::download_file_to;
+use crate::{ENDPOINTS, HTTP_CLIENT, LAUNCHER_DIRECTORY};
 
 /// Placeholder struct for API endpoints implementation
 pub struct FabricProvider;
 
-static FABRIC_API_BASE: &str = "https://meta.fabricmc.net/v2";
 
 impl FabricProvider {
     /// Request all available minecraft versions
     pub async fn get_all_game_versions() -> Result<Vec<FabricVersion>> {
-        Self::request_from_endpoint(FABRIC_API_BASE, "versions/game").await
+        Self::request_from_endpoint(&ENDPOINTS.fabric_meta, "versions/game").await
     }
     
     /// Request all available loader versions
     pub async fn get_all_loader_versions(mc_version: &str) -> Result<Vec<FabricLoaderVersion>> {
-        Self::request_from_endpoint(FABRIC_API_BASE, &format!("versions/loader/{}", mc_version)).await
+        Self::request_from_endpoint(&ENDPOINTS.fabric_meta, &format!("versions/loader/{}", mc_version)).await
     }
     
     /// Request all available installer versions
     pub async fn get_all_installer_versions() -> Result<Vec<FabricInstallerVersion>> {
-        Self::request_from_endpoint(FABRIC_API_BASE, "versions/installer").await
+        Self::request_from_endpoint(&ENDPOINTS.fabric_meta, "versions/installer").await
     }
 
     pub async fn download_server_jar<F>(custom_server: &CustomServer, on_progress: F) -> Result<()> where F : Fn(u64, u64) {
         let path = LAUNCHER_DIRECTORY.data_dir().join("custom_servers").join(&custom_server.id);
         fs::create_dir_all(&path).await?;
         let installer_version = Self::get_all_installer_versions().await?.first().unwrap().version.clone();
-        let url = format!("{}/versions/loader/{}/{}/{}/server/jar", FABRIC_API_BASE, &custom_server.mc_version, custom_server.loader_version.clone().unwrap_or_default(), installer_version);
-        let content = download_file(&url, on_progress).await?;
-        let _ = fs::write(path.join("server.jar"), content).await.map_err(|e| e);
+        let url = format!("{}/versions/loader/{}/{}/{}/server/jar", ENDPOINTS.fabric_meta, &custom_server.mc_version, custom_server.loader_version.clone().unwrap_or_default(), installer_version);
+        download_file_to(&url, path.join("server.jar"), on_progress).await?;
         Ok(())
     }
 
@@ -5,23 +5,22 @@ use tokio::fs;
 use log::info;
 
 use crate::custom_servers::models::CustomServer;
-use crate::utils::download_file;
-use crate::{HTTP_CLIENT, LAUNCHER_DIRECTORY};
+use crate::utils::download_file_to;
+use crate::{ENDPOINTS, HTTP_CLIENT, LAUNCHER_DIRECTORY};
 
 /// Placeholder struct for API endpoints implementation
 pub struct FoliaProvider;
 
-static FOLIA_API_BASE: &str = "https://api.papermc.io/v2/projects/folia";
 
 impl FoliaProvider {
     /// Request all available minecraft versions
     pub async fn get_all_game_versions() -> Result<FoliaManifest> {
-        Self::request_from_endpoint(FOLIA_API_BASE, "").await
+        Self::request_from_endpoint(&ENDPOINTS.folia_api, "").await
     }
     
     /// Request all available loader versions
     pub async fn get_all_build_versions(mc_version: &str) -> Result<FoliaBuilds> {
-        Self::request_from_endpoint(FOLIA_API_BASE, &format!("versions/{}/builds", mc_version)).await
+        Self::request_from_endpoint(&ENDPOINTS.folia_api, &format!("versions/{}/builds", mc_version)).await
     }
 
     pub async fn download_server_jar<F>(custom_server: &CustomServer, on_progress: F) -> Result<()> where F : Fn(u64, u64) {
@@ -30,9 +29,8 @@ impl FoliaProvider {
         let mut build_version = Self::get_all_build_versions(&custom_server.mc_version).await?.builds;
         build_version.reverse();
         let latest_build = build_version.first().unwrap();
-        let url = format!("{}/versions/{}/builds/{}/downloads/{}", FOLIA_API_BASE, &custom_server.mc_version, custom_server.loader_version.clone().unwrap_or_default(), format!("server-{}-{}.jar", custom_server.mc_version, latest_build.build));
-        let content = download_file(&url, on_progress).await?;
-        let _ = fs::write(path.join("server.jar"), content).await.map_err(|e| e);
+        let url = format!("{}/versions/{}/builds/{}/downloads/{}", ENDPOINTS.folia_api, &custom_server.mc_version, custom_server.loader_version.clone().unwrap_or_default(), format!("server-{}-{}.jar", custom_server.mc_version, latest_build.build));
+        download_file_to(&url, path.join("server.jar"), on_progress).await?;
         Ok(())
     }
 
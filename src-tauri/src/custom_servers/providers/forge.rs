@@ -5,27 +5,27 @@ use tokio::fs;
 use log::info;
 
 use crate::custom_servers::models::CustomServer;
-use crate::utils::download_file_untracked;
-use crate::{HTTP_CLIENT, LAUNCHER_DIRECTORY};
+use crate::utils::{download_file_to, MavenCoordinate};
+use crate::{ENDPOINTS, HTTP_CLIENT, LAUNCHER_DIRECTORY};
 
 /// Placeholder struct for API endpoints implementation
 pub struct ForgeProvider;
 
-static FORGE_MODRINTH_API_BASE: &str = "https://meta.modrinth.com/forge";
-static FORGE_MAVEN_REPO_BASE: &str = "https://maven.minecraftforge.net/net/minecraftforge/forge";
 
 impl ForgeProvider {
     /// Request all available minecraft versions
     pub async fn get_manifest() -> Result<ForgeManifest> {
-        Self::request_from_endpoint(FORGE_MODRINTH_API_BASE, "v0/manifest.json").await
+        Self::request_from_endpoint(&format!("{}/forge", ENDPOINTS.modrinth_meta), "v0/manifest.json").await
     }
 
-    pub async fn download_installer_jar(custom_server: &CustomServer) -> Result<()> {
+    pub async fn download_installer_jar<F>(custom_server: &CustomServer, on_progress: F) -> Result<()> where F : Fn(u64, u64) {
         let path = LAUNCHER_DIRECTORY.data_dir().join("custom_servers").join("installers");
-        if !path.join(format!("forge-{}-{}.jar", custom_server.mc_version, custom_server.loader_version.clone().unwrap_or_default())).exists() {
+        let installer_jar = path.join(format!("forge-{}-{}.jar", custom_server.mc_version, custom_server.loader_version.clone().unwrap_or_default()));
+        if !installer_jar.exists() {
             fs::create_dir_all(&path).await?;
-            let url = format!("{}/{mc}-{loader}/forge-{mc}-{loader}-installer.jar", FORGE_MAVEN_REPO_BASE, mc = custom_server.mc_version, loader = custom_server.loader_version.clone().unwrap_or_default());
-            download_file_untracked(&url, path.join(format!("forge-{}-{}.jar", custom_server.mc_version, custom_server.loader_version.clone().unwrap_or_default()))).await?;
+            let installer = MavenCoordinate::parse(&format!("net.minecraftforge:forge:{}-{}:installer", custom_server.mc_version, custom_server.loader_version.clone().unwrap_or_default()))?;
+            let url = installer.resolve_url(&ENDPOINTS.forge_maven, None).await?;
+            download_file_to(&url, &installer_jar, on_progress).await?;
         }
         Ok(())
     }
@@ -6,7 +6,7 @@ use tokio::process::Child;
 use crate::app::app_data::LauncherOptions;
 use crate::minecraft::java::JavaRuntime;
 use crate::utils::download_file_untracked;
-use crate::{HTTP_CLIENT, LAUNCHER_DIRECTORY};
+use crate::{ENDPOINTS, HTTP_CLIENT, LAUNCHER_DIRECTORY};
 
 /// Placeholder struct for API endpoints implementation
 pub struct ForwardingManagerProvider;
@@ -14,10 +14,10 @@ pub struct ForwardingManagerProvider;
 impl ForwardingManagerProvider {
     fn get_nrc_meta_api_base(is_experimental: bool) -> String {
         return if is_experimental {
-            String::from("https://dl-staging.norisk.gg/meta/forwarding-manager")
+            format!("{}/meta/forwarding-manager", ENDPOINTS.norisk_download(true))
         } else {
             // FIXME: Change to production URL
-            String::from("https://dl-staging.norisk.gg/meta/forwarding-manager")
+            format!("{}/meta/forwarding-manager", ENDPOINTS.norisk_download(true))
         };
     }
     
@@ -5,27 +5,25 @@ use tokio::fs;
 use log::info;
 
 use crate::custom_servers::models::CustomServer;
-use crate::utils::download_file;
-use crate::{HTTP_CLIENT, LAUNCHER_DIRECTORY};
+use crate::utils::{download_file_to, MavenCoordinate};
+use crate::{ENDPOINTS, HTTP_CLIENT, LAUNCHER_DIRECTORY};
 
 /// Placeholder struct for API endpoints implementation
 pub struct NeoForgeProvider;
 
-static NEO_FORGE_MODRINTH_API_BASE: &str = "https://meta.modrinth.com/neo";
-static NEO_FORGE_MAVEN_REPO_BASE: &str = "https://maven.neoforged.net/releases/net/neoforged/neoforge";
 
 impl NeoForgeProvider {
     /// Request all available minecraft versions
     pub async fn get_manifest() -> Result<NeoForgeManifest> {
-        Self::request_from_endpoint(NEO_FORGE_MODRINTH_API_BASE, "v0/manifest.json").await
+        Self::request_from_endpoint(&format!("{}/neo", ENDPOINTS.modrinth_meta), "v0/manifest.json").await
     }
 
     pub async fn download_installer_jar<F>(custom_server: &CustomServer, on_progress: F) -> Result<()> where F : Fn(u64, u64) {
         let path = LAUNCHER_DIRECTORY.data_dir().join("custom_servers").join("installers");
         fs::create_dir_all(&path).await?;
-        let url = format!("{}/{loader}/neoforge-{loader}-installer.jar", NEO_FORGE_MAVEN_REPO_BASE, loader = custom_server.loader_version.clone().unwrap_or_default());
-        let content = download_file(&url, on_progress).await?;
-        let _ = fs::write(path.join(format!("neoforge-{}.jar", custom_server.loader_version.clone().unwrap_or_default())), content).await.map_err(|e| e);
+        let installer = MavenCoordinate::parse(&format!("net.neoforged:neoforge:{}:installer", custom_server.loader_version.clone().unwrap_or_default()))?;
+        let url = installer.resolve_url(&ENDPOINTS.neoforge_maven, None).await?;
+        download_file_to(&url, path.join(format!("neoforge-{}.jar", custom_server.loader_version.clone().unwrap_or_default())), on_progress).await?;
         Ok(())
     }
 
@@ -5,26 +5,24 @@ use tokio::fs;
 use log::info;
 
 use crate::custom_servers::models::CustomServer;
-use crate::utils::download_file;
-use crate::{HTTP_CLIENT, LAUNCHER_DIRECTORY};
+use crate::utils::download_file_to;
+use crate::{ENDPOINTS, HTTP_CLIENT, LAUNCHER_DIRECTORY};
 
 /// Placeholder struct for API endpoints implementation
 pub struct PurpurProvider;
 
-static PURPUR_API_BASE: &str = "https://api.purpurmc.org/v2/purpur";
 
 impl PurpurProvider {
     /// Request all available minecraft versions
     pub async fn get_all_game_versions() -> Result<PurpurVersions> {
-        Self::request_from_endpoint(PURPUR_API_BASE, "").await
+        Self::request_from_endpoint(&ENDPOINTS.purpur_api, "").await
     }
 
     pub async fn download_server_jar<F>(custom_server: &CustomServer, on_progress: F) -> Result<()> where F : Fn(u64, u64) {
         let path = LAUNCHER_DIRECTORY.data_dir().join("custom_servers").join("installers");
         fs::create_dir_all(&path).await?;
-        let url = format!("{}/{mc}/latest/download", PURPUR_API_BASE, mc = custom_server.mc_version);
-        let content = download_file(&url, on_progress).await?;
-        let _ = fs::write(path.join(format!("purpur-{}.jar", custom_server.mc_version)), content).await.map_err(|e| e);
+        let url = format!("{}/{mc}/latest/download", ENDPOINTS.purpur_api, mc = custom_server.mc_version);
+        download_file_to(&url, path.join(format!("purpur-{}.jar", custom_server.mc_version)), on_progress).await?;
         Ok(())
     }
 
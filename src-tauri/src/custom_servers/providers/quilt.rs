@@ -5,27 +5,21 @@ use tokio::fs;
 use log::info;
 
 use crate::app::app_data::LauncherOptions;
-use crate::utils::download_file;
-use crate::{HTTP_CLIENT, LAUNCHER_DIRECTORY};
+use crate::utils::download_file_to;
+use crate::{ENDPOINTS, HTTP_CLIENT, LAUNCHER_DIRECTORY};
 
 /// Placeholder struct for API endpoints implementation
 pub struct QuiltProvider;
 
-static QUILT_MODRINTH_API_BASE: &str = "https://meta.modrinth.com/quilt";
-static QUILT_MAVEN_REPO_BASE: &str = "https://maven.quiltmc.org/repository/release/org/quiltmc";
 
 impl QuiltProvider {
     fn get_nrc_meta_api_base(is_experimental: bool) -> String {
-        return if is_experimental {
-            String::from("https://dl-staging.norisk.gg/meta/quilt")
-        } else {
-            String::from("https://dl.norisk.gg/meta/quilt")
-        };
+        format!("{}/meta/quilt", ENDPOINTS.norisk_download(is_experimental))
     }
     
     /// Request all available minecraft versions
     pub async fn get_manifest() -> Result<QuiltManifest> {
-        Self::request_from_endpoint(QUILT_MODRINTH_API_BASE, "v0/manifest.json").await
+        Self::request_from_endpoint(&format!("{}/quilt", ENDPOINTS.modrinth_meta), "v0/manifest.json").await
     }
     
     /// Request all available installer versions
@@ -38,9 +32,8 @@ impl QuiltProvider {
         let installer_version = Self::get_all_installer_versions().await?.first().unwrap().clone();
         let path = LAUNCHER_DIRECTORY.data_dir().join("custom_servers").join("installers");
         fs::create_dir_all(&path).await?;
-        let url = format!("{}/quilt-installer/{}/quilt-installer-{}.jar", QUILT_MAVEN_REPO_BASE, installer_version, installer_version);
-        let content = download_file(&url, on_progress).await?;
-        let _ = fs::write(path.join(format!("quilt-{}.jar", installer_version)), content).await.map_err(|e| e);
+        let url = format!("{}/quilt-installer/{}/quilt-installer-{}.jar", ENDPOINTS.quilt_maven, installer_version, installer_version);
+        download_file_to(&url, path.join(format!("quilt-{}.jar", installer_version)), on_progress).await?;
         Ok(())
     }
 
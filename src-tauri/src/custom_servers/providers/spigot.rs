@@ -5,21 +5,16 @@ use log::info;
 
 use crate::app::app_data::LauncherOptions;
 use crate::custom_servers::models::CustomServer;
-use crate::utils::download_file;
-use crate::{HTTP_CLIENT, LAUNCHER_DIRECTORY};
+use crate::utils::download_file_to;
+use crate::{ENDPOINTS, HTTP_CLIENT, LAUNCHER_DIRECTORY};
 
 /// Placeholder struct for API endpoints implementation
 pub struct SpigotProvider;
 
-static GETBUKKIT_API_BASE: &str = "https://download.getbukkit.org/spigot";
 
 impl SpigotProvider {
     fn get_nrc_meta_api_base(is_experimental: bool) -> String {
-        return if is_experimental {
-            String::from("https://dl-staging.norisk.gg/meta/spigot")
-        } else {
-            String::from("https://dl.norisk.gg/meta/spigot")
-        };
+        format!("{}/meta/spigot", ENDPOINTS.norisk_download(is_experimental))
     }
     
     /// Request all available minecraft versions
@@ -31,9 +26,8 @@ impl SpigotProvider {
     pub async fn download_server_jar<F>(custom_server: &CustomServer, on_progress: F) -> Result<()> where F : Fn(u64, u64) {
         let path = LAUNCHER_DIRECTORY.data_dir().join("custom_servers").join(&custom_server.mc_version);
         fs::create_dir_all(&path).await?;
-        let url = format!("{}/spigot-{}.jar", GETBUKKIT_API_BASE, custom_server.mc_version);
-        let content = download_file(&url, on_progress).await?;
-        let _ = fs::write(path.join("server.jar"), content).await.map_err(|e| e);
+        let url = format!("{}/spigot/spigot-{}.jar", ENDPOINTS.getbukkit, custom_server.mc_version);
+        download_file_to(&url, path.join("server.jar"), on_progress).await?;
         Ok(())
     }
 
@@ -5,30 +5,29 @@ use serde::de::DeserializeOwned;
 use tokio::fs;
 
 use crate::custom_servers::models::CustomServer;
-use crate::utils::download_file_untracked;
-use crate::{HTTP_CLIENT, LAUNCHER_DIRECTORY};
+use crate::utils::download_file_to;
+use crate::{ENDPOINTS, HTTP_CLIENT, LAUNCHER_DIRECTORY};
 
 /// Placeholder struct for API endpoints implementation
 pub struct VanillaProvider;
 
-static VANILLA_LAUNCHER_API: &str = "https://launchermeta.mojang.com";
 
 impl VanillaProvider {
     /// Request all available minecraft versions
     pub async fn get_all_versions() -> Result<VanillaVersions> {
-        Self::request_from_endpoint(VANILLA_LAUNCHER_API, "mc/game/version_manifest.json").await
+        Self::request_from_endpoint(&ENDPOINTS.mojang_launcher_meta, "mc/game/version_manifest.json").await
     }
 
     /// Request a vanilla version manifest
     pub async fn get_manifest(hash: &str, version: &str) -> Result<VanillaManifest> {
-        Self::request_from_endpoint(VANILLA_LAUNCHER_API, &format!("v1/packages/{}/{}.json", hash, version)).await
+        Self::request_from_endpoint(&ENDPOINTS.mojang_launcher_meta, &format!("v1/packages/{}/{}.json", hash, version)).await
     }
 
-    pub async fn download_server_jar(custom_server: &CustomServer, hash: &str) -> Result<()> {
+    pub async fn download_server_jar<F>(custom_server: &CustomServer, hash: &str, on_progress: F) -> Result<()> where F : Fn(u64, u64) {
         let path = LAUNCHER_DIRECTORY.data_dir().join("custom_servers").join(&custom_server.id);
         fs::create_dir_all(&path).await?;
         let manifest = Self::get_manifest(hash, &custom_server.mc_version).await?;
-        let _ = download_file_untracked(&manifest.downloads.server.url, path.join("server.jar")).await?;
+        download_file_to(&manifest.downloads.server.url, path.join("server.jar"), on_progress).await?;
         Ok(())
     }
 
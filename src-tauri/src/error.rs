@@ -7,6 +7,8 @@ pub enum LauncherError {
     InvalidVersionProfile(String),
     #[error("Unknown template parameter: {0}")]
     UnknownTemplateParameter(String),
+    #[error("Launch was cancelled")]
+    Cancelled,
 }
 
 
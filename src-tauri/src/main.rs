@@ -19,8 +19,10 @@ use log4rs::{
     encode::pattern::PatternEncoder,
 };
 use once_cell::sync::Lazy;
-use reqwest::Client;
-use crate::utils::{get_architecture, is_rosetta};
+use crate::app::app_data::LauncherOptions;
+use crate::app::endpoints::Endpoints;
+use crate::app::profile_cache::ProfileCache;
+use crate::utils::{get_architecture, is_rosetta, BandwidthLimiter, HttpClient, VerifiedFileIndex};
 
 pub mod app;
 pub mod minecraft;
@@ -44,16 +46,36 @@ env!("CARGO_PKG_NAME"),
 env!("CARGO_PKG_VERSION"),
 );
 
-/// HTTP Client with launcher agent
-static HTTP_CLIENT: Lazy<Client> = Lazy::new(|| {
-    let client = reqwest::ClientBuilder::new()
-        .user_agent(APP_USER_AGENT)
-        .build()
-        .unwrap_or_else(|_| Client::new());
+/// HTTP Client with launcher agent and the network settings of the launcher options
+static HTTP_CLIENT: Lazy<HttpClient> = Lazy::new(|| {
+    // The client can be needed before the frontend loaded the options, so they are read here directly.
+    // Later changes are applied when the options are stored.
+    let options = fs::read(LAUNCHER_DIRECTORY.config_dir().join("options.json")).ok()
+        .and_then(|options| serde_json::from_slice::<LauncherOptions>(&options).ok());
+
+    match options {
+        Some(mut options) => {
+            options.load_secrets(LAUNCHER_DIRECTORY.config_dir());
+            HttpClient::new(&options.proxy, &options.trusted_certificates)
+        }
+        None => HttpClient::new(&Default::default(), &[]),
+    }
+});
+
+/// Urls of all services, overridable for testing against local servers
+static ENDPOINTS: Lazy<Endpoints> = Lazy::new(Endpoints::load);
 
-    client
+/// Bandwidth limit shared by all downloads, configured by the launcher options
+static BANDWIDTH_LIMITER: Lazy<BandwidthLimiter> = Lazy::new(BandwidthLimiter::new);
+
+/// Hashes of verified game files, so unchanged libraries aren't hashed on every launch
+static VERIFIED_FILES: Lazy<VerifiedFileIndex> = Lazy::new(|| {
+    VerifiedFileIndex::load(LAUNCHER_DIRECTORY.data_dir().join("verified_files.json"))
 });
 
+/// Mojang profile lookups, shared so the cape browser and whitelist don't run into rate limits
+static PROFILE_CACHE: Lazy<ProfileCache> = Lazy::new(|| ProfileCache::load(ProfileCache::default_path()));
+
 const TRIGGER_FILE_SIZE: u64 = 2 * 1024 * 1000;
 
 /// Number of archive log files to keep
@@ -0,0 +1,534 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use futures::stream::{self, StreamExt};
+use log::{debug, info, warn};
+use once_cell::sync::Lazy;
+use path_absolutize::*;
+use regex::{Captures, Regex};
+use serde::Deserialize;
+use tokio::fs;
+use tokio::fs::OpenOptions;
+use walkdir::WalkDir;
+
+use crate::error::LauncherError;
+use crate::minecraft::java::JavaRuntime;
+use crate::minecraft::progress::{ProgressReceiver, ProgressTracker, ProgressUpdate, ProgressUpdateSteps};
+use crate::minecraft::rule_interpreter;
+use crate::minecraft::version::{Library, VersionProfile};
+use crate::utils::{download_file_to, sha1sum_blocking, zip_extract, zip_read_entry, MavenCoordinate, PartialPath, OS};
+
+static DATA_TOKEN_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{([A-Za-z0-9_]+)}").unwrap());
+
+///
+/// JSON struct of the install_profile.json shipped with modern Forge and NeoForge installers
+///
+#[derive(Deserialize)]
+pub struct InstallProfile {
+    #[serde(default)]
+    pub spec: i32,
+    pub version: String,
+    pub minecraft: String,
+    #[serde(default = "default_version_json")]
+    pub json: String,
+    #[serde(default)]
+    pub data: HashMap<String, SidedDataEntry>,
+    #[serde(default)]
+    pub processors: Vec<Processor>,
+    #[serde(default)]
+    pub libraries: Vec<Library>,
+}
+
+fn default_version_json() -> String {
+    "/version.json".to_string()
+}
+
+#[derive(Deserialize)]
+pub struct SidedDataEntry {
+    pub client: String,
+    pub server: String,
+}
+
+#[derive(Deserialize)]
+pub struct Processor {
+    pub jar: String,
+    #[serde(default)]
+    pub classpath: Vec<String>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub outputs: HashMap<String, String>,
+    pub sides: Option<Vec<String>>,
+}
+
+impl Processor {
+    fn runs_on_client(&self) -> bool {
+        self.sides.as_ref().map_or(true, |sides| sides.iter().any(|side| side == "client"))
+    }
+}
+
+/// Installer of a Forge or NeoForge version.
+/// The installer jar is downloaded and extracted once into the data directory and reused afterwards.
+pub struct ForgeInstaller {
+    installer_jar: PathBuf,
+    extracted_dir: PathBuf,
+    profile: InstallProfile,
+}
+
+impl ForgeInstaller {
+    pub async fn prepare(installer_url: &str, data: &Path) -> Result<Self> {
+        let installers_folder = data.join("loader_installers");
+        fs::create_dir_all(&installers_folder).await?;
+
+        let file_name = installer_url.trim_end_matches('/').rsplit('/').next().unwrap_or_default();
+        if file_name.is_empty() || !file_name.ends_with(".jar") {
+            return Err(LauncherError::InvalidVersionProfile(format!("Invalid loader installer url: {}", installer_url)).into());
+        }
+
+        let installer_jar = installers_folder.join(file_name);
+        let extracted_dir = installers_folder.join(file_name.trim_end_matches(".jar"));
+
+        if !installer_jar.exists() {
+            info!("Downloading loader installer {}", installer_url);
+            // The jar only gets its final name once it is complete, so an interrupted download is never reused
+            download_file_to(installer_url, &installer_jar, |_, _| {}).await?;
+        }
+
+        if !extracted_dir.join("install_profile.json").exists() {
+            info!("Extracting loader installer {:?}", installer_jar);
+            if extracted_dir.exists() {
+                fs::remove_dir_all(&extracted_dir).await?;
+            }
+            let partial = PartialPath::new(&extracted_dir);
+            let file = OpenOptions::new().read(true).open(&installer_jar).await?;
+            if let Err(err) = zip_extract(file, &extracted_dir).await {
+                // A broken installer has to be downloaded again
+                fs::remove_file(&installer_jar).await?;
+                return Err(err);
+            }
+            partial.keep();
+        }
+
+        let install_profile = fs::read(extracted_dir.join("install_profile.json")).await?;
+        let raw_profile = serde_json::from_slice::<serde_json::Value>(&install_profile)?;
+        if raw_profile.get("install").is_some() && raw_profile.get("versionInfo").is_some() {
+            return Err(LauncherError::InvalidVersionProfile("Legacy Forge installers (1.12.2 and older) are not supported".to_string()).into());
+        }
+        let profile = serde_json::from_value::<InstallProfile>(raw_profile)?;
+
+        debug!("Loaded install profile {} (spec {}) for minecraft {}", profile.version, profile.spec, profile.minecraft);
+
+        // Libraries bundled with the installer are not available in any repository
+        let libraries_folder = data.join("libraries");
+        let bundled_maven = extracted_dir.join("maven");
+        if bundled_maven.exists() {
+            for entry in WalkDir::new(&bundled_maven).into_iter().filter_map(|e| e.ok()).filter(|e| e.file_type().is_file()) {
+                let relative_path = entry.path().strip_prefix(&bundled_maven)?;
+                let target = libraries_folder.join(relative_path);
+                if !target.exists() {
+                    fs::create_dir_all(target.parent().unwrap()).await?;
+                    fs::copy(entry.path(), &target).await?;
+                    debug!("Copied bundled library {:?}", relative_path);
+                }
+            }
+        }
+
+        Ok(ForgeInstaller { installer_jar, extracted_dir, profile })
+    }
+
+    /// The version profile of the loader, it inherits from the vanilla version
+    pub async fn version_profile(&self) -> Result<VersionProfile> {
+        let path = self.extracted_dir.join(self.profile.json.trim_start_matches('/'));
+        Ok(serde_json::from_slice::<VersionProfile>(&fs::read(path).await?)?)
+    }
+
+    /// Downloads the processor libraries and runs the client processors to produce the patched client.
+    /// Processors whose outputs already exist with the expected hashes are skipped.
+    pub async fn install(&self, java_bin: &Path, client_jar: &Path, data: &Path, concurrent_downloads: usize, progress: Arc<impl ProgressReceiver>) -> Result<()> {
+        let libraries_folder = data.join("libraries");
+        let processors = self.profile.processors.iter().filter(|processor| processor.runs_on_client()).collect::<Vec<_>>();
+
+        if processors.is_empty() {
+            return Ok(());
+        }
+
+        let installed_marker = self.extracted_dir.join("installed");
+        let data_entries = self.resolve_data_entries(client_jar, &libraries_folder, data)?;
+
+        // Skip the whole installation if it already ran and all outputs are still valid
+        if installed_marker.exists() {
+            let mut outputs_valid = true;
+            for processor in &processors {
+                if !self.outputs_valid(processor, &data_entries, &libraries_folder).await? {
+                    outputs_valid = false;
+                    break;
+                }
+            }
+
+            if outputs_valid {
+                info!("Loader {} is already installed", self.profile.version);
+                return Ok(());
+            }
+            fs::remove_file(&installed_marker).await?;
+        }
+
+        progress.progress_update(ProgressUpdate::set_label("translation.downloadingLoaderLibraries"));
+
+        let features: HashSet<String> = HashSet::new();
+        let libraries = self.profile.libraries.iter()
+            .filter(|library| rule_interpreter::check_condition(&library.rules, &features).unwrap_or(false))
+            .collect::<Vec<_>>();
+        let libraries_max = libraries.len() as u64;
+
+        let downloads: Vec<Result<PathBuf>> = stream::iter(
+            libraries.into_iter().map(|library| {
+                let libraries_folder = libraries_folder.clone();
+
+                async move {
+                    let artifact = library.get_library_download().await?;
+                    artifact.download(library.name.clone(), &libraries_folder, false).await.map(|(path, _)| path)
+                }
+            })
+        ).buffer_unordered(concurrent_downloads.max(1)).collect().await;
+
+        for download in downloads {
+            download?;
+        }
+        debug!("Downloaded {} loader libraries", libraries_max);
+
+        let java_runtime = JavaRuntime::new(java_bin.to_path_buf());
+        let processors_max = processors.len();
+        let tracker = ProgressTracker::new(ProgressUpdateSteps::InstallLoader, processors_max as u64, 0);
+
+        progress.progress_update(ProgressUpdate::set_label("translation.runningLoaderProcessors"));
+        for (idx, processor) in processors.into_iter().enumerate() {
+            progress.progress_state(tracker.set_current_file(&processor.jar));
+
+            if !processor.outputs.is_empty() && self.outputs_valid(processor, &data_entries, &libraries_folder).await? {
+                info!("Skipping processor {}, outputs are up to date", processor.jar);
+            } else {
+                self.run_processor(&java_runtime, processor, &data_entries, &libraries_folder, data).await?;
+
+                if !self.outputs_valid(processor, &data_entries, &libraries_folder).await? {
+                    bail!("Processor {} produced outputs with unexpected hashes", processor.jar);
+                }
+            }
+
+            progress.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::InstallLoader, idx as u64 + 1, processors_max as u64));
+            progress.progress_state(tracker.complete_item(0, &processor.jar));
+        }
+
+        fs::write(&installed_marker, &self.profile.version).await?;
+        info!("Installed loader {}", self.profile.version);
+        Ok(())
+    }
+
+    async fn run_processor(&self, java_runtime: &JavaRuntime, processor: &Processor, data_entries: &HashMap<String, String>, libraries_folder: &Path, data: &Path) -> Result<()> {
+        let processor_jar = library_path(libraries_folder, &processor.jar)?;
+
+        let manifest = zip_read_entry(OpenOptions::new().read(true).open(&processor_jar).await?, "META-INF/MANIFEST.MF").await?
+            .ok_or_else(|| LauncherError::InvalidVersionProfile(format!("Processor {} has no manifest", processor.jar)))?;
+        let main_class = String::from_utf8_lossy(&manifest)
+            .lines()
+            .find_map(|line| line.strip_prefix("Main-Class:").map(|main_class| main_class.trim().to_string()))
+            .ok_or_else(|| LauncherError::InvalidVersionProfile(format!("Processor {} has no main class", processor.jar)))?;
+
+        let mut class_path = vec![processor_jar];
+        for library in &processor.classpath {
+            class_path.push(library_path(libraries_folder, library)?);
+        }
+        let class_path = class_path.iter()
+            .map(|path| path.to_string_lossy().to_string())
+            .collect::<Vec<_>>()
+            .join(OS.get_path_separator()?);
+
+        let arguments = processor.args.iter()
+            .map(|argument| self.resolve_argument(argument, data_entries, libraries_folder))
+            .collect::<Result<Vec<_>>>()?;
+
+        info!("Running processor {} ({})", processor.jar, main_class);
+        debug!("Processor arguments: {:?}", arguments);
+
+        let output = java_runtime.run_class(&class_path, &main_class, &arguments, data).await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            warn!("Processor {} failed: {}", processor.jar, stderr);
+            bail!("Processor {} exited with {}", processor.jar, output.status);
+        }
+
+        Ok(())
+    }
+
+    async fn outputs_valid(&self, processor: &Processor, data_entries: &HashMap<String, String>, libraries_folder: &Path) -> Result<bool> {
+        for (file, hash) in &processor.outputs {
+            let file = PathBuf::from(self.resolve_argument(file, data_entries, libraries_folder)?);
+            let hash = self.resolve_argument(hash, data_entries, libraries_folder)?;
+
+            if !file.exists() || sha1sum_blocking(file.clone()).await? != hash {
+                debug!("Processor output {:?} is missing or outdated", file);
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Resolves the data entries of the install profile for the client side
+    fn resolve_data_entries(&self, client_jar: &Path, libraries_folder: &Path, data: &Path) -> Result<HashMap<String, String>> {
+        let mut entries = HashMap::new();
+
+        for (key, entry) in &self.profile.data {
+            let value = &entry.client;
+            let resolved = if value.starts_with('[') && value.ends_with(']') {
+                absolute(&library_path(libraries_folder, &value[1..value.len() - 1])?)?
+            } else if value.starts_with('\'') && value.ends_with('\'') && value.len() >= 2 {
+                value[1..value.len() - 1].to_string()
+            } else if value.starts_with('/') {
+                absolute(&self.extracted_dir.join(value.trim_start_matches('/')))?
+            } else {
+                value.clone()
+            };
+            entries.insert(key.clone(), resolved);
+        }
+
+        entries.insert("SIDE".to_string(), "client".to_string());
+        entries.insert("MINECRAFT_JAR".to_string(), absolute(client_jar)?);
+        entries.insert("MINECRAFT_VERSION".to_string(), self.profile.minecraft.clone());
+        entries.insert("ROOT".to_string(), absolute(data)?);
+        entries.insert("INSTALLER".to_string(), absolute(&self.installer_jar)?);
+        entries.insert("LIBRARY_DIR".to_string(), absolute(libraries_folder)?);
+
+        Ok(entries)
+    }
+
+    /// Replaces `{DATA}` tokens and resolves `[artifact]` references to library paths
+    fn resolve_argument(&self, argument: &str, data_entries: &HashMap<String, String>, libraries_folder: &Path) -> Result<String> {
+        if argument.starts_with('[') && argument.ends_with(']') {
+            return absolute(&library_path(libraries_folder, &argument[1..argument.len() - 1])?);
+        }
+
+        let mut missing = None;
+        let resolved = DATA_TOKEN_REGEX.replace_all(argument, |captures: &Captures| {
+            match data_entries.get(&captures[1]) {
+                Some(value) => value.clone(),
+                None => {
+                    missing = Some(captures[1].to_string());
+                    String::new()
+                }
+            }
+        }).to_string();
+
+        if let Some(missing) = missing {
+            return Err(LauncherError::InvalidVersionProfile(format!("Unknown install profile data entry {}", missing)).into());
+        }
+
+        Ok(resolved)
+    }
+}
+
+fn library_path(libraries_folder: &Path, artifact: &str) -> Result<PathBuf> {
+    Ok(libraries_folder.join(MavenCoordinate::parse(artifact)?.path()))
+}
+
+fn absolute(path: &Path) -> Result<String> {
+    Ok(path.absolutize()?.to_string_lossy().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use async_zip::write::ZipFileWriter;
+    use async_zip::{Compression, ZipEntryBuilder};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use uuid::Uuid;
+
+    use super::*;
+
+    async fn installer_jar() -> Vec<u8> {
+        let install_profile = br#"{"spec": 1, "version": "1.20.1-forge-47.2.0", "minecraft": "1.20.1", "json": "/version.json"}"#;
+        let mut jar = Vec::new();
+        let mut writer = ZipFileWriter::new(&mut jar);
+        writer.write_entry_whole(ZipEntryBuilder::new("install_profile.json".to_string(), Compression::Stored), install_profile).await.unwrap();
+        writer.write_entry_whole(ZipEntryBuilder::new("maven/net/minecraftforge/forge/1.20.1-47.2.0/forge-1.20.1-47.2.0-universal.jar".to_string(), Compression::Stored), b"universal").await.unwrap();
+        writer.close().await.unwrap();
+        jar
+    }
+
+    /// Serves every request with the given body, `truncate` announces more bytes than are sent
+    async fn serve(body: Vec<u8>, truncate: bool) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).await;
+                let length = if truncate { body.len() * 2 } else { body.len() };
+                let header = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", length);
+                let _ = stream.write_all(header.as_bytes()).await;
+                let _ = stream.write_all(&body).await;
+            }
+        });
+        format!("http://{}/net/minecraftforge/forge/1.20.1-47.2.0/forge-1.20.1-47.2.0-installer.jar", address)
+    }
+
+    fn data_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("nrc-forge-installer-{}", Uuid::new_v4()))
+    }
+
+    struct NoProgress;
+
+    impl ProgressReceiver for NoProgress {
+        fn progress_update(&self, _update: ProgressUpdate) {}
+    }
+
+    fn installer(data: &Path, profile: serde_json::Value) -> ForgeInstaller {
+        let installers = data.join("loader_installers");
+        ForgeInstaller {
+            installer_jar: installers.join("forge-installer.jar"),
+            extracted_dir: installers.join("forge-installer"),
+            profile: serde_json::from_value(profile).unwrap(),
+        }
+    }
+
+    /// Install profile with one client processor producing `{PATCHED}` with the hash `{PATCHED_SHA}`
+    fn processor_profile(data: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({
+            "version": "1.20.1-forge-47.2.0",
+            "minecraft": "1.20.1",
+            "data": data,
+            "processors": [{
+                "jar": "net.minecraftforge:binarypatcher:1.1.1",
+                "args": ["--output", "{PATCHED}"],
+                "outputs": {"{PATCHED}": "{PATCHED_SHA}"},
+                "sides": ["client"]
+            }]
+        })
+    }
+
+    #[test]
+    fn data_entries_are_resolved() {
+        let data = data_dir();
+        let libraries = data.join("libraries");
+        let installer = installer(&data, processor_profile(serde_json::json!({
+            "MAPPINGS": {"client": "[de.oceanlabs.mcp:mcp_config:1.20.1:mappings@txt]", "server": "[de.oceanlabs.mcp:mcp_config:1.20.1:server-mappings@txt]"},
+            "MC_HASH": {"client": "'4a5f1c2b'", "server": "'ffffffff'"},
+            "BINPATCH": {"client": "/data/client.lzma", "server": "/data/server.lzma"},
+            "PLAIN": {"client": "value", "server": "other"}
+        })));
+
+        let entries = installer.resolve_data_entries(&data.join("client.jar"), &libraries, &data).unwrap();
+        assert_eq!(entries["MAPPINGS"], absolute(&libraries.join("de/oceanlabs/mcp/mcp_config/1.20.1/mcp_config-1.20.1-mappings.txt")).unwrap());
+        assert_eq!(entries["MC_HASH"], "4a5f1c2b");
+        assert_eq!(entries["BINPATCH"], absolute(&installer.extracted_dir.join("data/client.lzma")).unwrap());
+        assert_eq!(entries["PLAIN"], "value");
+        assert_eq!(entries["SIDE"], "client");
+        assert_eq!(entries["MINECRAFT_VERSION"], "1.20.1");
+        assert_eq!(entries["MINECRAFT_JAR"], absolute(&data.join("client.jar")).unwrap());
+    }
+
+    #[test]
+    fn arguments_are_resolved() {
+        let data = data_dir();
+        let libraries = data.join("libraries");
+        let installer = installer(&data, processor_profile(serde_json::json!({})));
+        let entries = HashMap::from([("SIDE".to_string(), "client".to_string()), ("ROOT".to_string(), "/root".to_string())]);
+
+        assert_eq!(installer.resolve_argument("--side={SIDE}", &entries, &libraries).unwrap(), "--side=client");
+        assert_eq!(installer.resolve_argument("{ROOT}/{SIDE}", &entries, &libraries).unwrap(), "/root/client");
+        assert_eq!(
+            installer.resolve_argument("[net.minecraftforge:forge:1.20.1-47.2.0:client]", &entries, &libraries).unwrap(),
+            absolute(&libraries.join("net/minecraftforge/forge/1.20.1-47.2.0/forge-1.20.1-47.2.0-client.jar")).unwrap()
+        );
+        assert!(installer.resolve_argument("--input={UNKNOWN}", &entries, &libraries).is_err());
+    }
+
+    #[tokio::test]
+    async fn outputs_are_checked_against_their_hashes() {
+        let data = data_dir();
+        let libraries = data.join("libraries");
+        let installer = installer(&data, processor_profile(serde_json::json!({})));
+        let processor = &installer.profile.processors[0];
+
+        let output = data.join("patched.jar");
+        fs::create_dir_all(&data).await.unwrap();
+        fs::write(&output, b"patched").await.unwrap();
+        let hash = sha1sum_blocking(output.clone()).await.unwrap();
+        let entries = HashMap::from([("PATCHED".to_string(), absolute(&output).unwrap()), ("PATCHED_SHA".to_string(), hash)]);
+
+        assert!(installer.outputs_valid(processor, &entries, &libraries).await.unwrap());
+
+        fs::write(&output, b"modified").await.unwrap();
+        assert!(!installer.outputs_valid(processor, &entries, &libraries).await.unwrap());
+
+        fs::remove_file(&output).await.unwrap();
+        assert!(!installer.outputs_valid(processor, &entries, &libraries).await.unwrap());
+
+        fs::remove_dir_all(&data).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn installed_loaders_are_not_installed_again() {
+        let data = data_dir();
+        let output = data.join("libraries/net/minecraftforge/forge/1.20.1-47.2.0/forge-1.20.1-47.2.0-client.jar");
+        fs::create_dir_all(output.parent().unwrap()).await.unwrap();
+        fs::write(&output, b"patched").await.unwrap();
+        let hash = sha1sum_blocking(output.clone()).await.unwrap();
+
+        let installer = installer(&data, processor_profile(serde_json::json!({
+            "PATCHED": {"client": "[net.minecraftforge:forge:1.20.1-47.2.0:client]", "server": ""},
+            "PATCHED_SHA": {"client": format!("'{}'", hash), "server": ""}
+        })));
+        let marker = installer.extracted_dir.join("installed");
+        fs::create_dir_all(&installer.extracted_dir).await.unwrap();
+        fs::write(&marker, "1.20.1-forge-47.2.0").await.unwrap();
+
+        // Neither java nor the processor jar exist, the install has to be skipped
+        let java = data.join("java");
+        installer.install(&java, &data.join("client.jar"), &data, 1, Arc::new(NoProgress)).await.unwrap();
+        assert!(marker.exists());
+
+        // Outdated outputs invalidate the marker and run the processors again
+        fs::write(&output, b"modified").await.unwrap();
+        assert!(installer.install(&java, &data.join("client.jar"), &data, 1, Arc::new(NoProgress)).await.is_err());
+        assert!(!marker.exists());
+
+        fs::remove_dir_all(&data).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn prepares_served_installer() {
+        let data = data_dir();
+        let url = serve(installer_jar().await, false).await;
+
+        let installer = ForgeInstaller::prepare(&url, &data).await.unwrap();
+        assert_eq!(installer.profile.version, "1.20.1-forge-47.2.0");
+        assert_eq!(installer.profile.minecraft, "1.20.1");
+
+        let installers = data.join("loader_installers");
+        assert!(installers.join("forge-1.20.1-47.2.0-installer.jar").exists());
+        assert!(!installers.join("forge-1.20.1-47.2.0-installer.jar.part").exists());
+        assert!(data.join("libraries/net/minecraftforge/forge/1.20.1-47.2.0/forge-1.20.1-47.2.0-universal.jar").exists());
+
+        // The cached installer is reused without another download
+        let cached = ForgeInstaller::prepare("http://127.0.0.1:1/net/minecraftforge/forge/1.20.1-47.2.0/forge-1.20.1-47.2.0-installer.jar", &data).await.unwrap();
+        assert_eq!(cached.profile.version, "1.20.1-forge-47.2.0");
+
+        fs::remove_dir_all(&data).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn interrupted_download_is_not_cached() {
+        let data = data_dir();
+        let url = serve(installer_jar().await, true).await;
+
+        assert!(ForgeInstaller::prepare(&url, &data).await.is_err());
+
+        let installers = data.join("loader_installers");
+        assert!(!installers.join("forge-1.20.1-47.2.0-installer.jar").exists());
+        assert!(!installers.join("forge-1.20.1-47.2.0-installer.jar.part").exists());
+
+        fs::remove_dir_all(&data).await.unwrap();
+    }
+}
@@ -6,7 +6,7 @@ use path_absolutize::Absolutize;
 use tokio::fs;
 use crate::app::api::ApiEndpoints;
 
-use crate::utils::{download_file, get_architecture, tar_gz_extract, zip_extract, OperatingSystem, OS};
+use crate::utils::{download_file, get_architecture, tar_gz_extract, zip_extract, OperatingSystem, PartialPath, OS};
 
 /// Find java binary in JRE folder
 pub async fn find_java_binary(runtimes_folder: &Path, jre_version: u32) -> Result<PathBuf> {
@@ -67,6 +67,7 @@ pub async fn jre_download<F>(runtimes_folder: &Path, jre_version: u32, on_progre
 
     // Download from JRE source and extract runtime files
     fs::create_dir_all(&runtime_path).await?;
+    let partial = PartialPath::new(&runtime_path);
 
     let retrieved_bytes = download_file(&jre_source.download_url, on_progress).await?;
     let cursor = Cursor::new(&retrieved_bytes[..]);
@@ -78,6 +79,8 @@ pub async fn jre_download<F>(runtimes_folder: &Path, jre_version: u32, on_progre
     }
 
     // Find JRE afterwards
-    find_java_binary(runtimes_folder, jre_version).await
+    let java_bin = find_java_binary(runtimes_folder, jre_version).await?;
+    partial.keep();
+    Ok(java_bin)
 }
 
@@ -1,7 +1,9 @@
 pub(crate) mod jre_downloader;
 mod runtime;
+mod trust_store;
 
 pub use {
     jre_downloader::*,
     runtime::*,
+    trust_store::*,
 };
\ No newline at end of file
@@ -1,5 +1,5 @@
 use std::path::{Path, PathBuf};
-use std::process::Stdio;
+use std::process::{Output, Stdio};
 use anyhow::{Result, bail};
 use tokio::{io::AsyncReadExt, sync::oneshot::Receiver, process::{Child, Command}};
 use log::debug;
@@ -45,6 +45,23 @@ impl JavaRuntime {
         Ok(child)
     }
 
+    /// Runs the main class of the given class path to completion, used for loader install processors
+    pub async fn run_class(&self, class_path: &str, main_class: &str, arguments: &[String], working_dir: &Path) -> Result<Output> {
+        let mut command = Command::new(&self.0);
+        command.current_dir(working_dir);
+        command.arg("-cp").arg(class_path);
+        command.arg(main_class);
+        command.args(arguments);
+
+        // A cancelled launch drops this future, the processor must not outlive it
+        command
+            .stderr(Stdio::piped())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true);
+
+        Ok(command.output().await?)
+    }
+
     pub async fn handle_io<D: Send + Sync>(&self, running_task: &mut Child, on_stdout: fn(&D, &[u8], Uuid) -> Result<()>, on_stderr: fn(&D, &[u8], Uuid) -> Result<()>, terminator: Receiver<()>, data: &D, instance_id: Uuid) -> Result<()> {
         let mut stdout = running_task.stdout.take().unwrap();
         let mut stderr = running_task.stderr.take().unwrap();
@@ -0,0 +1,80 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use log::info;
+use sha1::{Digest, Sha1};
+use tokio::fs;
+use tokio::process::Command;
+
+use crate::utils::PartialPath;
+
+/// Default password of the java cacerts, the trust store only contains public certificates
+const TRUST_STORE_PASSWORD: &str = "changeit";
+
+/// Java only trusts its own cacerts and ignores the certificates of the launcher, so they are imported into a copy
+/// of the cacerts of the runtime with its keytool. The store is reused until the runtime or a certificate changes.
+pub async fn trust_store_args(java_bin: &Path, certificates: &[String], cache_dir: &Path) -> Result<Vec<String>> {
+    if certificates.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // java_bin is <java home>/bin/java
+    let java_home = java_bin.parent().and_then(Path::parent).context("Java binary has no java home")?;
+    let keytool = java_bin.with_file_name(if cfg!(windows) { "keytool.exe" } else { "keytool" });
+    // Java 8 JDKs keep the cacerts of their bundled JRE in jre/lib/security
+    let cacerts = [java_home.join("lib/security/cacerts"), java_home.join("jre/lib/security/cacerts")]
+        .into_iter()
+        .find(|path| path.exists())
+        .with_context(|| format!("{:?} has no cacerts", java_home))?;
+
+    let mut hasher = Sha1::new();
+    hasher.update(java_bin.to_string_lossy().as_bytes());
+    hasher.update(fs::read(&cacerts).await?);
+    for certificate in certificates {
+        hasher.update(fs::read(certificate).await.with_context(|| format!("Unable to read certificate {:?}", certificate))?);
+    }
+    let trust_store = cache_dir.join("trust_stores").join(base16ct::lower::encode_string(&hasher.finalize()));
+
+    if !trust_store.exists() {
+        build_trust_store(&keytool, &cacerts, certificates, &trust_store).await?;
+    }
+
+    Ok(vec![
+        format!("-Djavax.net.ssl.trustStore={}", trust_store.to_string_lossy()),
+        format!("-Djavax.net.ssl.trustStorePassword={}", TRUST_STORE_PASSWORD),
+    ])
+}
+
+async fn build_trust_store(keytool: &Path, cacerts: &Path, certificates: &[String], trust_store: &Path) -> Result<()> {
+    let mut temp_name = trust_store.as_os_str().to_owned();
+    temp_name.push(".part");
+    let temp_path = PathBuf::from(temp_name);
+    let guard = PartialPath::new(temp_path.clone());
+
+    if let Some(parent) = trust_store.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+    // The copy keeps the store type of the runtime, JKS for java 8 and PKCS12 for newer versions
+    fs::copy(cacerts, &temp_path).await?;
+
+    for (index, certificate) in certificates.iter().enumerate() {
+        let output = Command::new(keytool)
+            .arg("-importcert")
+            .arg("-noprompt")
+            .arg("-alias").arg(format!("noriskclient-{}", index))
+            .arg("-file").arg(certificate)
+            .arg("-keystore").arg(&temp_path)
+            .arg("-storepass").arg(TRUST_STORE_PASSWORD)
+            .output()
+            .await
+            .with_context(|| format!("Unable to run {:?}", keytool))?;
+        if !output.status.success() {
+            bail!("Failed to import certificate {:?}: {}", certificate, String::from_utf8_lossy(&output.stdout).trim());
+        }
+    }
+
+    fs::rename(&temp_path, trust_store).await?;
+    guard.keep();
+    info!("Created java trust store {:?} with {} additional certificates", trust_store, certificates.len());
+    Ok(())
+}
@@ -3,29 +3,59 @@ use std::collections::HashSet;
 use std::fmt::Write;
 use std::process::exit;
 use std::sync::{Arc, Mutex};
-use std::sync::atomic::{AtomicU64, Ordering};
 
 use anyhow::Result;
 use futures::stream::{self, StreamExt};
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use path_absolutize::*;
 use tokio::{fs, fs::OpenOptions};
 use uuid::Uuid;
-use walkdir::WalkDir;
 
-use crate::{app::api::ApiEndpoints, LAUNCHER_VERSION, minecraft::version::AssetObject, utils::{OS, OS_VERSION}};
+use crate::{app::api::ApiEndpoints, LAUNCHER_VERSION, VERIFIED_FILES, minecraft::version::AssetObject, utils::{OS, OS_VERSION}};
 use crate::app::api::NoRiskLaunchManifest;
-use crate::app::gui::get_keep_local_assets;
+use crate::app::app_data::{MavenRepository, ProxySettings};
 use crate::app::nrc_cache::{NRCCache, RunnerInstance};
 use crate::error::LauncherError;
-use crate::minecraft::java::{find_java_binary, JavaRuntime, jre_downloader};
-use crate::minecraft::progress::{get_max, get_progress, ProgressReceiver, ProgressUpdate, ProgressUpdateSteps};
+use crate::minecraft::forge_installer::ForgeInstaller;
+use crate::minecraft::java::{find_java_binary, JavaRuntime, jre_downloader, trust_store_args};
+use crate::minecraft::norisk_assets::{backup_unrecorded_asset, is_cosmetic_asset, non_cosmetic_asset_state, norisk_asset_path, verify_norisk_assets, AssetOverrideSettings, InstalledNoRiskAssets, NonCosmeticAssetState};
+use crate::minecraft::progress::{get_max, get_progress, ProgressReceiver, ProgressTracker, ProgressUpdate, ProgressUpdateSteps};
 use crate::minecraft::rule_interpreter;
-use crate::minecraft::version::LibraryDownloadInfo;
-use crate::utils::{download_file, sha1sum, zip_extract};
+use crate::minecraft::version::{Library, LibraryDownloadInfo};
+use crate::utils::{download_file_to, zip_extract, CancellationToken, DirectoryLock};
 
 use super::version::VersionProfile;
 
+/// What is being launched, NoRisk branches get their mods, assets and token while vanilla versions are launched unmodified
+pub enum LaunchTarget {
+    NoRisk { norisk_token: String, uuid: String, manifest: NoRiskLaunchManifest },
+    Vanilla { version_id: String },
+}
+
+impl LaunchTarget {
+    /// Vanilla versions get their own game directory so they don't share any files with a branch
+    pub fn game_dir(&self, data: &Path) -> PathBuf {
+        match self {
+            LaunchTarget::NoRisk { manifest, .. } => data.join("gameDir").join(&manifest.build.branch),
+            LaunchTarget::Vanilla { version_id } => data.join("gameDir").join("vanilla").join(version_id),
+        }
+    }
+
+    fn jre_version(&self, version_profile: &VersionProfile) -> u32 {
+        match self {
+            LaunchTarget::NoRisk { manifest, .. } => manifest.build.jre_version,
+            LaunchTarget::Vanilla { .. } => version_profile.java_version.as_ref().map_or(8, |java_version| java_version.major_version),
+        }
+    }
+
+    fn norisk_token(&self) -> Option<&str> {
+        match self {
+            LaunchTarget::NoRisk { norisk_token, .. } => Some(norisk_token),
+            LaunchTarget::Vanilla { .. } => None,
+        }
+    }
+}
+
 pub struct LauncherData<D: Send + Sync> {
     pub instance_id: Uuid,
     pub instances: Arc<Mutex<Vec<RunnerInstance>>>,
@@ -34,6 +64,7 @@ pub struct LauncherData<D: Send + Sync> {
     pub(crate) on_progress: fn(&D, ProgressUpdate, Uuid, Arc<Mutex<Vec<RunnerInstance>>>) -> Result<()>,
     pub(crate) data: Box<D>,
     pub(crate) terminator: tokio::sync::oneshot::Receiver<()>,
+    pub(crate) cancellation: CancellationToken,
 }
 
 impl<D: Send + Sync> LauncherData<D> {
@@ -42,6 +73,11 @@ impl<D: Send + Sync> LauncherData<D> {
         NRCCache::store_running_instances(&self.instances)?;
         Ok(())
     }
+
+    /// Whether another instance of the branch is currently running or launching
+    pub fn has_other_instance(&self, branch: &str) -> bool {
+        self.instances.lock().unwrap().iter().any(|instance| instance.id != self.instance_id && instance.branch == branch)
+    }
 }
 
 
@@ -55,7 +91,8 @@ impl<D: Send + Sync> ProgressReceiver for LauncherData<D> {
     }
 }
 
-pub async fn launch<D: Send + Sync>(multiple_instances: bool, norisk_token: &str, uuid: &str, data: &Path, manifest: NoRiskLaunchManifest, version_profile: VersionProfile, launching_parameter: LaunchingParameter, launcher_data: LauncherData<D>, window: Arc<Mutex<tauri::Window>>, instance_id: Uuid) -> Result<()> {
+pub async fn launch<D: Send + Sync>(multiple_instances: bool, target: LaunchTarget, data: &Path, version_profile: VersionProfile, forge_installer: Option<ForgeInstaller>, launching_parameter: LaunchingParameter, launcher_data: LauncherData<D>, prepare_lock: DirectoryLock, window: Arc<Mutex<tauri::Window>>, instance_id: Uuid) -> Result<()> {
+    let cancellation = launcher_data.cancellation.clone();
     let launcher_data_arc = Arc::new(launcher_data);
 
     let features: HashSet<String> = HashSet::new();
@@ -68,22 +105,27 @@ pub async fn launch<D: Send + Sync>(multiple_instances: bool, norisk_token: &str
         fs::create_dir(&runtimes_folder).await?;
     }
 
+    let jre_version = target.jre_version(&version_profile);
     let java_bin = match &launching_parameter.custom_java_path {
         Some(path) => PathBuf::from(path),
         None => {
             info!("Checking for JRE...");
             launcher_data_arc.progress_update(ProgressUpdate::set_label("translation.checkingJRE"));
 
-            match find_java_binary(&runtimes_folder, manifest.build.jre_version).await {
+            match find_java_binary(&runtimes_folder, jre_version).await {
                 Ok(jre) => jre,
                 Err(e) => {
                     error!("Failed to find JRE: {}", e);
 
                     info!("Download JRE...");
                     launcher_data_arc.progress_update(ProgressUpdate::set_label("translation.downloadingJRE"));
-                    jre_downloader::jre_download(&runtimes_folder, manifest.build.jre_version, |a, b| {
-                        launcher_data_arc.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadJRE, get_progress(0, a, b), get_max(1)));
-                    }).await?
+                    let jre_tracker = ProgressTracker::new(ProgressUpdateSteps::DownloadJRE, 1, 0);
+                    cancellation.run(jre_downloader::jre_download(&runtimes_folder, jre_version, |a, b| {
+                        if let Some(state) = jre_tracker.set_bytes(a, b) {
+                            launcher_data_arc.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadJRE, get_progress(0, a, b), get_max(1)));
+                            launcher_data_arc.progress_state(Some(state));
+                        }
+                    })).await??
                 }
             }
         }
@@ -95,14 +137,13 @@ pub async fn launch<D: Send + Sync>(multiple_instances: bool, norisk_token: &str
 
     // Client
     let versions_folder = data.join("versions");
+    let client_folder = versions_folder.join(&version_profile.id);
+    let client_jar = client_folder.join(format!("{}.jar", &version_profile.id));
 
     // Check if json has client download (or doesn't require one)
     if let Some(client_download) = version_profile.downloads.as_ref().and_then(|x| x.client.as_ref()) {
-        let client_folder = versions_folder.join(&version_profile.id);
         fs::create_dir_all(&client_folder).await?;
 
-        let client_jar = client_folder.join(format!("{}.jar", &version_profile.id));
-
         // Add client jar to class path
         write!(class_path, "{}{}", &client_jar.absolutize().unwrap().to_str().unwrap(), OS.get_path_separator()?)?;
 
@@ -111,9 +152,9 @@ pub async fn launch<D: Send + Sync>(multiple_instances: bool, norisk_token: &str
             debug!("Client Jar doesn't exists");
             true
         } else {
-            let hash = sha1sum(&client_jar)?;
-            debug!("Client Jar Hash {:?} {:?}",hash,client_download.sha1);
-            hash != client_download.sha1
+            let verified = VERIFIED_FILES.verify_sha1(&client_jar, &client_download.sha1, launching_parameter.full_verify).await?;
+            debug!("Client Jar verified {:?} {:?}", verified, client_download.sha1);
+            !verified
         };
 
         debug!("Downloading Client jar {:?}", requires_download);
@@ -121,15 +162,17 @@ pub async fn launch<D: Send + Sync>(multiple_instances: bool, norisk_token: &str
         if requires_download {
             launcher_data_arc.progress_update(ProgressUpdate::set_label("translation.downloadingClient"));
 
-            let retrieved_bytes = download_file(&client_download.url, |a, b| {
-                launcher_data_arc.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadClientJar, get_progress(0, a, b), get_max(1)));
-            }).await?;
-
-            fs::write(&client_jar, retrieved_bytes).await?;
+            let client_tracker = ProgressTracker::new(ProgressUpdateSteps::DownloadClientJar, 1, client_download.size.max(0) as u64);
+            client_tracker.set_current_file(format!("{}.jar", &version_profile.id));
+            cancellation.run(download_file_to(&client_download.url, &client_jar, |a, b| {
+                if let Some(state) = client_tracker.set_bytes(a, b) {
+                    launcher_data_arc.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadClientJar, get_progress(0, a, b), get_max(1)));
+                    launcher_data_arc.progress_state(Some(state));
+                }
+            })).await??;
 
             // After downloading, check sha1
-            let hash = sha1sum(&client_jar)?;
-            if hash != client_download.sha1 {
+            if !VERIFIED_FILES.verify_sha1(&client_jar, &client_download.sha1, true).await? {
                 anyhow::bail!("Client JAR download failed. SHA1 mismatch.");
             }
         }
@@ -147,55 +190,44 @@ pub async fn launch<D: Send + Sync>(multiple_instances: bool, norisk_token: &str
     }
     fs::create_dir_all(&natives_folder).await?;
 
-    let libraries_to_download = version_profile.libraries.iter().map(|x| x.to_owned()).collect::<Vec<_>>();
-    // let libraries_downloaded = Arc::new(AtomicU64::new(0));
+    let libraries_to_download = version_profile.libraries.iter()
+        .filter(|library| rule_interpreter::check_condition(&library.rules, &features).unwrap_or(false))
+        .map(|x| x.to_owned())
+        .collect::<Vec<_>>();
     let libraries_max = libraries_to_download.len() as u64;
+    let libraries_size = libraries_to_download.iter()
+        .filter_map(|library| library.downloads.as_ref().and_then(|x| x.artifact.as_ref()).map(|artifact| artifact.size.max(0) as u64))
+        .sum();
+    let libraries_tracker = Arc::new(ProgressTracker::new(ProgressUpdateSteps::DownloadLibraries, libraries_max, libraries_size));
 
     launcher_data_arc.progress_update(ProgressUpdate::set_label("translation.checkingLibraries"));
     launcher_data_arc.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadLibraries, 0, libraries_max));
 
-    let class_paths: Vec<Result<Option<String>>> = stream::iter(
-        libraries_to_download.into_iter().filter_map(|library| {
-            // let download_count = libraries_downloaded.clone();
+    let class_paths: Vec<Result<Option<String>>> = cancellation.run(stream::iter(
+        libraries_to_download.into_iter().map(|library| {
             let data_clone = launcher_data_arc.clone();
             let folder_clone = libraries_folder.to_path_buf();
+            let tracker = libraries_tracker.clone();
+            let full_verify = launching_parameter.full_verify;
 
-            if !rule_interpreter::check_condition(&library.rules, &features).unwrap_or(false) {
-                return None;
-            }
-
-            Some(async move {
-                if let Some(natives) = &library.natives {
-                    if let Some(required_natives) = natives.get(OS.get_simple_name()?) {
-                        if let Some(classifiers) = library.downloads.as_ref().and_then(|x| x.classifiers.as_ref()) {
-                            if let Some(artifact) = classifiers.get(required_natives).map(LibraryDownloadInfo::from) {
-                                let path = artifact.download(library.name, folder_clone.as_path(), data_clone).await?;
-
-                                info!("Natives zip extract: {:?}", path);
-                                let file = OpenOptions::new().read(true).open(path).await?;
-                                zip_extract(file, natives_path).await?;
-                            }
-                        } else {
-                            return Err(LauncherError::InvalidVersionProfile("missing classifiers, but natives required.".to_string()).into());
-                        }
-                    }
+            async move {
+                let library_name = library.name.clone();
+                let library_size = library.downloads.as_ref().and_then(|x| x.artifact.as_ref()).map_or(0, |artifact| artifact.size.max(0) as u64);
+                let result = download_library(library, folder_clone, natives_path, full_verify).await;
 
-                    return Ok(None);
+                let state = match result {
+                    Ok((_, true)) => tracker.complete_item(library_size, &library_name),
+                    _ => tracker.skip_item(library_size, &library_name),
+                };
+                if let Some(state) = state {
+                    data_clone.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadLibraries, state.completed_items, libraries_max));
+                    data_clone.progress_state(Some(state));
                 }
 
-                // Download regular artifact
-                let artifact = library.get_library_download()?;
-                let path = artifact.download(library.name, folder_clone.as_path(), data_clone).await?;
-
-                // Natives are not included in the classpath
-                return if library.natives.is_none() {
-                    return Ok(path.absolutize()?.to_str().map(|x| x.to_string()));
-                } else {
-                    Ok(None)
-                };
-            })
+                result.map(|(class_path, _)| class_path)
+            }
         })
-    ).buffer_unordered(launching_parameter.concurrent_downloads as usize).collect().await;
+    ).buffer_unordered(launching_parameter.concurrent_downloads as usize).collect()).await?;
 
     for x in class_paths {
         if let Some(library_path) = x? {
@@ -205,6 +237,16 @@ pub async fn launch<D: Send + Sync>(multiple_instances: bool, norisk_token: &str
 
     launcher_data_arc.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadLibraries, libraries_max, libraries_max));
 
+    if let Err(err) = VERIFIED_FILES.store().await {
+        error!("Failed to store verified file index: {:?}", err);
+    }
+
+
+    // Loader install processors (Forge / NeoForge)
+    if let Some(forge_installer) = &forge_installer {
+        cancellation.run(forge_installer.install(&java_bin, &client_jar, data, launching_parameter.concurrent_downloads as usize, launcher_data_arc.clone())).await??;
+    }
+
     // Minecraft Assets
     let assets_folder = data.join("assets");
     let indexes_folder: PathBuf = assets_folder.join("indexes");
@@ -214,30 +256,31 @@ pub async fn launch<D: Send + Sync>(multiple_instances: bool, norisk_token: &str
     fs::create_dir_all(&objects_folder).await?;
 
     let asset_index_location = version_profile.asset_index_location.as_ref().ok_or_else(|| LauncherError::InvalidVersionProfile("Asset index unspecified".to_string()))?;
-    let asset_index = asset_index_location.load_asset_index(&indexes_folder).await?;
+    let asset_index = cancellation.run(asset_index_location.load_asset_index(&indexes_folder)).await??;
     let asset_objects_to_download = asset_index.objects.values().map(|x| x.to_owned()).collect::<Vec<_>>();
-    let assets_downloaded = Arc::new(AtomicU64::new(0));
     let asset_max = asset_objects_to_download.len() as u64;
+    let asset_size = asset_objects_to_download.iter().map(|asset_object| asset_object.size.max(0) as u64).sum();
+    let assets_tracker = Arc::new(ProgressTracker::new(ProgressUpdateSteps::DownloadAssets, asset_max, asset_size));
 
     launcher_data_arc.progress_update(ProgressUpdate::set_label("translation.checkingMinecraftAssets"));
     launcher_data_arc.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadAssets, 0, asset_max));
 
-    let _: Vec<Result<()>> = stream::iter(
+    let _: Vec<Result<()>> = cancellation.run(stream::iter(
         asset_objects_to_download.into_iter().map(|asset_object| {
-            let download_count = assets_downloaded.clone();
+            let tracker = assets_tracker.clone();
             let data_clone = launcher_data_arc.clone();
             let folder_clone = objects_folder.clone();
 
             async move {
                 let hash = asset_object.hash.clone();
-                match asset_object.download_destructing(folder_clone, data_clone.clone()).await {
+                let size = asset_object.size.max(0) as u64;
+                match asset_object.download_destructing(folder_clone).await {
                     Ok(downloaded) => {
-                        let curr = download_count.fetch_add(1, Ordering::Relaxed);
-
-                        if downloaded {
-                            // the progress bar is only being updated when a asset has been downloaded to improve speeds
-                            data_clone.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadAssets, curr, asset_max));
-                            data_clone.progress_update(ProgressUpdate::set_label(format!("translation.downloadedMinecraftAsset&hash%{}", hash)));
+                        // the progress is rate limited by the tracker to improve speeds
+                        let state = if downloaded { tracker.complete_item(size, &hash) } else { tracker.skip_item(size, &hash) };
+                        if let Some(state) = state {
+                            data_clone.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadAssets, state.completed_items, asset_max));
+                            data_clone.progress_state(Some(state));
                         }
                     }
                     Err(err) => error!("Unable to download asset {}: {:?}", hash, err)
@@ -246,26 +289,27 @@ pub async fn launch<D: Send + Sync>(multiple_instances: bool, norisk_token: &str
                 Ok(())
             }
         })
-    ).buffer_unordered(launching_parameter.concurrent_downloads as usize).collect().await;
+    ).buffer_unordered(launching_parameter.concurrent_downloads as usize).collect()).await?;
 
     launcher_data_arc.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadAssets, asset_max, asset_max));
 
-    let game_dir = data.join("gameDir").join(manifest.build.branch.clone());
+    let game_dir = target.game_dir(data);
+    fs::create_dir_all(&game_dir).await?;
 
     // Norisk Assets
-    let keep_local_assets = match get_keep_local_assets() {
-        Ok(keep_local_assets) => keep_local_assets,
-        Err(err) => {
-            error!("Error fetching keep_local_assets: {}", err);
-            false
+    if let LaunchTarget::NoRisk { norisk_token, uuid, manifest } = &target {
+        // Local overrides take priority over the assets of the CDN, everything else is still synced
+        let asset_overrides = AssetOverrideSettings::load().await.overrides();
+        if let Some(overrides) = &asset_overrides {
+            for (location, path) in &overrides.files {
+                info!("Using Norisk asset override {} from {}", location, path.display());
+            }
         }
-    };
 
-    if !keep_local_assets {
         let norisk_asset_dir = game_dir.join("NoRiskClient").join("assets");
         fs::create_dir_all(&norisk_asset_dir).await?;
 
-        let json_data = ApiEndpoints::assets(manifest.build.branch.clone(), norisk_token, uuid).await;
+        let json_data = cancellation.run(ApiEndpoints::assets(manifest.build.branch.clone(), norisk_token, uuid)).await?;
 
         let norisk_asset_objects_to_download: HashMap<String, AssetObject> = match json_data {
             Ok(norisk_assets) => norisk_assets.objects,
@@ -276,39 +320,86 @@ pub async fn launch<D: Send + Sync>(multiple_instances: bool, norisk_token: &str
         };
 
         if norisk_asset_objects_to_download.len() > 0 {
-            let norisk_assets_downloaded = Arc::new(AtomicU64::new(0));
-            let norisk_asset_max = norisk_asset_objects_to_download.values().map(|x| x.to_owned()).collect::<Vec<_>>().len() as u64;
+            let norisk_asset_max = norisk_asset_objects_to_download.len() as u64;
+            let norisk_asset_size = norisk_asset_objects_to_download.values().map(|asset_object| asset_object.size.max(0) as u64).sum();
+            let norisk_assets_tracker = Arc::new(ProgressTracker::new(ProgressUpdateSteps::DownloadNoRiskAssets, norisk_asset_max, norisk_asset_size));
 
             launcher_data_arc.progress_update(ProgressUpdate::set_label("translation.checkingNoriskAssets"));
             launcher_data_arc.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadNoRiskAssets, 0, norisk_asset_max));
 
-            let _: Vec<Result<()>> = stream::iter(
-                norisk_asset_objects_to_download.clone().into_iter().map(|asset_object| {
-                    let download_count = norisk_assets_downloaded.clone();
+            // Non-cosmetic assets are only replaced if the user didn't change them
+            let installed_assets = Arc::new(Mutex::new(InstalledNoRiskAssets::load(&game_dir).await));
+            let asset_conflicts = Arc::new(Mutex::new(Vec::new()));
+
+            let _: Vec<Result<()>> = cancellation.run(stream::iter(
+                norisk_asset_objects_to_download.clone().into_iter()
+                    .filter(|(location, _)| !asset_overrides.as_ref().map_or(false, |overrides| overrides.contains(location)))
+                    .map(|asset_object| {
+                    let tracker = norisk_assets_tracker.clone();
                     let data_clone = launcher_data_arc.clone();
                     let folder_clone = norisk_asset_dir.clone();
                     let game_dir_clone = game_dir.clone();
                     let branch_clone = manifest.build.branch.clone();
+                    let installed_assets = installed_assets.clone();
+                    let asset_conflicts = asset_conflicts.clone();
 
-                    let is_non_cosmetic = !asset_object.0.starts_with("nrc-cosmetics/");
+                    let is_non_cosmetic = !is_cosmetic_asset(&asset_object.0);
 
                     async move {
-                        if is_non_cosmetic && game_dir_clone.join(&asset_object.0).exists() {
-                            let curr = download_count.fetch_add(1, Ordering::Relaxed);
-                            data_clone.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadNoRiskAssets, curr, norisk_asset_max));
-                            data_clone.progress_update(ProgressUpdate::set_label(format!("translation.verifiedNoriskAsset&fileName%{}", asset_object.1.hash)));
-                            info!("Skipping Norisk asset download for non-cosmetic asset: {} since the file already exists!", asset_object.0);
+                        let size = asset_object.1.size.max(0) as u64;
+                        let asset_state = if is_non_cosmetic {
+                            let (installed, unrecorded) = {
+                                let installed_assets = installed_assets.lock().unwrap();
+                                (installed_assets.assets.get(&asset_object.0).cloned(), installed_assets.unrecorded)
+                            };
+                            let path = norisk_asset_path(&asset_object.0, &folder_clone, &game_dir_clone);
+                            match non_cosmetic_asset_state(&path, &asset_object.1, installed.as_ref(), unrecorded).await {
+                                // Without a backup the file is kept like a modified one
+                                NonCosmeticAssetState::Unrecorded => match backup_unrecorded_asset(&path).await {
+                                    Ok(backup) => {
+                                        info!("Moved Norisk asset {} without a record to {}", asset_object.0, backup.display());
+                                        NonCosmeticAssetState::Unrecorded
+                                    }
+                                    Err(err) => {
+                                        error!("Failed to back up Norisk asset {}: {:?}", asset_object.0, err);
+                                        NonCosmeticAssetState::Modified
+                                    }
+                                },
+                                asset_state => asset_state,
+                            }
+                        } else {
+                            NonCosmeticAssetState::Missing
+                        };
+
+                        if matches!(asset_state, NonCosmeticAssetState::UpToDate | NonCosmeticAssetState::Modified) {
+                            if asset_state == NonCosmeticAssetState::UpToDate {
+                                installed_assets.lock().unwrap().record(&asset_object.0, &asset_object.1.hash);
+                            } else {
+                                asset_conflicts.lock().unwrap().push(asset_object.0.clone());
+                            }
+
+                            if let Some(state) = tracker.skip_item(size, &asset_object.0) {
+                                data_clone.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadNoRiskAssets, state.completed_items, norisk_asset_max));
+                                data_clone.progress_state(Some(state));
+                            }
                         } else {
                             let hash = asset_object.1.hash.clone();
-    
-                            match asset_object.1.download_norisk_cosmetic_destructing(branch_clone, asset_object.0, norisk_token.to_string(), if is_non_cosmetic { game_dir_clone } else { folder_clone }, data_clone.clone()).await {
+
+                            let location = asset_object.0.clone();
+                            match asset_object.1.download_norisk_cosmetic_destructing(branch_clone, asset_object.0, norisk_token.to_string(), if is_non_cosmetic { game_dir_clone } else { folder_clone }).await {
                                 Ok(downloaded) => {
-                                    let curr = download_count.fetch_add(1, Ordering::Relaxed);
-    
-                                    if downloaded {
-                                        // the progress bar is only being updated when a asset has been downloaded to improve speeds
-                                        data_clone.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadNoRiskAssets, curr, norisk_asset_max));
-                                        data_clone.progress_update(ProgressUpdate::set_label(format!("translation.downloadedNoriskAsset&hash%{}", hash)));
+                                    if is_non_cosmetic {
+                                        if matches!(asset_state, NonCosmeticAssetState::Outdated | NonCosmeticAssetState::Unrecorded) {
+                                            info!("Updated Norisk asset {}", location);
+                                        }
+                                        installed_assets.lock().unwrap().record(&location, &hash);
+                                    }
+
+                                    // the progress is rate limited by the tracker to improve speeds
+                                    let state = if downloaded { tracker.complete_item(size, &location) } else { tracker.skip_item(size, &location) };
+                                    if let Some(state) = state {
+                                        data_clone.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadNoRiskAssets, state.completed_items, norisk_asset_max));
+                                        data_clone.progress_state(Some(state));
                                     }
                                 }
                                 Err(err) => error!("Unable to download Norisk asset {}: {:?}", hash, err)
@@ -318,25 +409,48 @@ pub async fn launch<D: Send + Sync>(multiple_instances: bool, norisk_token: &str
                         Ok(())
                     }
                 })
-            ).buffer_unordered(launching_parameter.concurrent_downloads as usize).collect().await;
+            ).buffer_unordered(launching_parameter.concurrent_downloads as usize).collect()).await?;
 
             launcher_data_arc.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadNoRiskAssets, norisk_asset_max, norisk_asset_max));
 
+            let asset_conflicts = std::mem::take(&mut *asset_conflicts.lock().unwrap());
+            for location in &asset_conflicts {
+                warn!("Keeping Norisk asset {} since it was changed locally, the updated version is not installed", location);
+            }
+            if !asset_conflicts.is_empty() {
+                let _ = window.lock().unwrap().emit("norisk-asset-conflicts", &asset_conflicts);
+            }
+            let mut installed_assets = std::mem::take(&mut *installed_assets.lock().unwrap());
+            if let Some(overrides) = &asset_overrides {
+                if let Err(err) = overrides.apply(&norisk_asset_dir, &game_dir, &mut installed_assets).await {
+                    error!("Failed to apply Norisk asset overrides: {:?}", err);
+                }
+            }
+            if let Err(err) = installed_assets.store(&game_dir).await {
+                error!("Failed to store installed Norisk assets: {:?}", err);
+            }
+
             // Delete usused norisk assets
 
-            verify_norisk_assets(&norisk_asset_dir.clone(), norisk_asset_objects_to_download, launcher_data_arc.clone()).await;
+            if let Err(err) = cancellation.run(verify_norisk_assets(&norisk_asset_dir, &game_dir, &norisk_asset_objects_to_download, asset_overrides.as_ref(), launcher_data_arc.clone())).await? {
+                error!("Failed to verify Norisk assets: {:?}", err);
+            }
         }
-    } else {
-        info!("Skipping Norisk assets check & download.");
     }
 
     // Game
-    let java_runtime = JavaRuntime::new(java_bin);
-
     let mut command_arguments = Vec::new();
 
+    // A launch without the certificates only fails behind TLS intercepting proxies
+    match trust_store_args(&java_bin, &launching_parameter.trusted_certificates, &runtimes_folder).await {
+        Ok(args) => command_arguments.extend(args),
+        Err(err) => error!("Failed to pass the trusted certificates to java: {:?}", err),
+    }
+
+    let java_runtime = JavaRuntime::new(java_bin);
+
     // JVM Args
-    version_profile.arguments.add_jvm_args_to_vec(norisk_token, &mut command_arguments, &launching_parameter, &features)?;
+    version_profile.arguments.add_jvm_args_to_vec(target.norisk_token(), &mut command_arguments, &launching_parameter, &features)?;
 
     // Main class
     command_arguments.push(version_profile.main_class.as_ref().ok_or_else(|| LauncherError::InvalidVersionProfile("Main class unspecified".to_string()))?.to_owned());
@@ -363,6 +477,8 @@ pub async fn launch<D: Send + Sync>(multiple_instances: bool, norisk_token: &str
                     "launcher_name" => output.push_str("NoRiskClient"),
                     "launcher_version" => output.push_str(LAUNCHER_VERSION),
                     "classpath" => output.push_str(&class_path),
+                    "classpath_separator" => output.push_str(OS.get_path_separator()?),
+                    "library_directory" => output.push_str(libraries_folder.absolutize().unwrap().to_str().unwrap()),
                     "user_properties" => output.push_str("{}"),
                     "clientid" => output.push_str(&launching_parameter.clientid),
                     "auth_xuid" => output.push_str(&launching_parameter.auth_xuid),
@@ -376,9 +492,15 @@ pub async fn launch<D: Send + Sync>(multiple_instances: bool, norisk_token: &str
 
     launcher_data_arc.progress_update(ProgressUpdate::set_label("translation.launching"));
     launcher_data_arc.progress_update(ProgressUpdate::set_to_max());
+    launcher_data_arc.progress_state(Some(ProgressTracker::new(ProgressUpdateSteps::Launching, 0, 0).state()));
 
+    // Last chance to stop before the game is started, afterwards the terminator takes over
+    cancellation.check()?;
     let mut running_task = java_runtime.execute(mapped, &game_dir)?;
 
+    // Preparing is done, other launches of the branch may continue
+    drop(prepare_lock);
+
     if let Some(id) = running_task.id() {
         let mut runner_instances = launcher_data_arc.instances.lock().unwrap();
         if let Some(instance) = runner_instances.iter_mut().find(|r| r.id == instance_id) {
@@ -409,40 +531,39 @@ pub async fn launch<D: Send + Sync>(multiple_instances: bool, norisk_token: &str
     Ok(())
 }
 
-async fn verify_norisk_assets<D: Send + Sync>(dir: &Path, asset_objetcs: HashMap<String, AssetObject>, launcher_data_arc: Arc<LauncherData<D>>) {
-    let mut keys_vec: Vec<&str> = vec![];
-    for location in asset_objetcs.keys() {
-        let parts: Vec<&str> = location.split("/").collect();
-
-        if let Some(last_part) = parts.last() {
-            keys_vec.push(last_part);
-        }
-    }
-    keys_vec.push(".DS_Store");
-    let file_names: &[&str] = &keys_vec;
-    let mut verified: u64 = 0;
-
-    launcher_data_arc.progress_update(ProgressUpdate::set_label("translation.verifyingNoriskAssets"));
-    launcher_data_arc.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::VerifyNoRiskAssets, verified, file_names.len() as u64));
-    for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
-        let path = entry.path().to_owned();
-        if path.is_file() {
-            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
-            if !file_names.contains(&file_name.as_ref()) {
-                if let Err(err) = fs::remove_file(&path).await {
-                    info!("Failed to remove {}: {}", path.display(), err);
-                } else {
-                    info!("Removed file {} since it was not found in the asset objects for this branch.", path.display());
+/// Downloads a single library, natives are extracted and not returned as class path entry.
+/// Also returns whether anything had to be downloaded.
+async fn download_library(library: Library, libraries_folder: PathBuf, natives_path: &Path, full_verify: bool) -> Result<(Option<String>, bool)> {
+    if let Some(natives) = &library.natives {
+        let mut downloaded = false;
+        if let Some(required_natives) = natives.get(OS.get_simple_name()?) {
+            if let Some(classifiers) = library.downloads.as_ref().and_then(|x| x.classifiers.as_ref()) {
+                if let Some(artifact) = classifiers.get(required_natives).map(LibraryDownloadInfo::from) {
+                    let (path, natives_downloaded) = artifact.download(library.name, libraries_folder.as_path(), full_verify).await?;
+                    downloaded = natives_downloaded;
+
+                    info!("Natives zip extract: {:?}", path);
+                    let file = OpenOptions::new().read(true).open(path).await?;
+                    zip_extract(file, natives_path).await?;
                 }
             } else {
-                verified += 1;
-                launcher_data_arc.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::VerifyNoRiskAssets, verified, file_names.len() as u64));
-                launcher_data_arc.progress_update(ProgressUpdate::set_label(format!("translation.verifiedNoriskAsset&fileName%{}", file_name)));
+                return Err(LauncherError::InvalidVersionProfile("missing classifiers, but natives required.".to_string()).into());
             }
         }
+
+        return Ok((None, downloaded));
     }
 
-    launcher_data_arc.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::VerifyNoRiskAssets, file_names.len() as u64, file_names.len() as u64));
+    // Download regular artifact
+    let artifact = library.get_library_download().await?;
+    let (path, downloaded) = artifact.download(library.name, libraries_folder.as_path(), full_verify).await?;
+
+    // Natives are not included in the classpath
+    return if library.natives.is_none() {
+        return Ok((path.absolutize()?.to_str().map(|x| x.to_string()), downloaded));
+    } else {
+        Ok((None, downloaded))
+    };
 }
 
 pub struct LaunchingParameter {
@@ -460,6 +581,12 @@ pub struct LaunchingParameter {
     pub user_type: String,
     pub keep_launcher_open: bool,
     pub concurrent_downloads: i32,
+    pub custom_repositories: Vec<MavenRepository>,
+    pub proxy: ProxySettings,
+    /// Additional root certificates of the launcher options, imported into a trust store for the game
+    pub trusted_certificates: Vec<String>,
+    /// Hash all libraries and the client jar even if they are unchanged since their last verification
+    pub full_verify: bool,
 }
 
 fn process_templates<F: Fn(&mut String, &str) -> Result<()>>(input: &String, retriever: F) -> Result<String> {
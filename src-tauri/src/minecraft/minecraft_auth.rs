@@ -6,8 +6,9 @@ use base64::prelude::{BASE64_STANDARD, BASE64_URL_SAFE_NO_PAD};
 use byteorder::BigEndian;
 use chrono::{DateTime, Duration, Utc};
 use jsonwebtoken::{Algorithm, decode, DecodingKey, Validation};
-use log::{debug, error};
+use log::{debug, error, info};
 use machineid_rs::{Encryption, HWIDComponent, IdBuilder};
+use once_cell::sync::Lazy;
 use p256::ecdsa::{Signature, SigningKey, VerifyingKey};
 use p256::ecdsa::signature::Signer;
 use p256::pkcs8::{DecodePrivateKey, EncodePrivateKey, LineEnding};
@@ -23,11 +24,12 @@ use tauri::Window;
 use tokio::fs;
 use uuid::Uuid;
 
-use crate::{HTTP_CLIENT, LAUNCHER_DIRECTORY};
+use crate::{ENDPOINTS, HTTP_CLIENT, LAUNCHER_DIRECTORY};
 use crate::app::api::ApiEndpoints;
 use crate::app::app_data::LauncherOptions;
 use crate::error::ErrorKind;
 use crate::error::ErrorKind::OtherError;
+use crate::utils::{CancellationToken, SecretStore};
 
 #[derive(Debug, Clone, Copy)]
 pub enum MinecraftAuthStep {
@@ -40,6 +42,8 @@ pub enum MinecraftAuthStep {
     MinecraftToken,
     MinecraftEntitlements,
     MinecraftProfile,
+    GetDeviceCode,
+    PollDeviceCode,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -80,6 +84,8 @@ pub enum MinecraftAuthenticationError {
     NoSessionId,
     #[error("Error reading user hash")]
     NoUserHash,
+    #[error("Device code login failed: {0}")]
+    DeviceCode(String),
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -99,11 +105,17 @@ pub struct MinecraftLoginFlow {
     pub redirect_uri: String,
 }
 
+/// Held while the account store is written, so concurrent updates don't overwrite each other
+static STORE_LOCK: Lazy<tokio::sync::Mutex<()>> = Lazy::new(Default::default);
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct MinecraftAuthStore {
     pub users: HashMap<Uuid, Credentials>,
     pub token: Option<SaveDeviceToken>,
     pub default_user: Option<Uuid>,
+    /// Detached stores never save, their changes are merged into the current store with `update`
+    #[serde(skip)]
+    detached: bool,
 }
 
 
@@ -113,40 +125,94 @@ impl Default for MinecraftAuthStore {
             users: HashMap::new(),
             token: None,
             default_user: None,
+            detached: false,
         }
     }
 }
 
 impl MinecraftAuthStore {
-    //TODO
-    pub async fn init(create_new: Option<bool>) -> Result<Self, crate::error::Error> {
-        
-        let auth_path = LAUNCHER_DIRECTORY.config_dir().join("accounts.json");
+    /// The accounts contain all tokens, so they are only stored encrypted
+    fn secret_store() -> SecretStore {
+        SecretStore::new(LAUNCHER_DIRECTORY.config_dir().join("accounts.enc"))
+    }
 
+    pub async fn init(create_new: Option<bool>) -> Result<Self, crate::error::Error> {
         if create_new.unwrap_or(false) {
             return Ok(MinecraftAuthStore::default());
         }
 
-        if auth_path.exists() {
-            let contents = fs::read_to_string(&auth_path).await.map_err(|e| {
-                ErrorKind::FSError(format!("Failed to read accounts.json: {}", e))
-            })?;
+        let contents = tokio::task::spawn_blocking(|| Self::secret_store().read()).await?;
+        let error = match contents {
+            Ok(Some(contents)) => match serde_json::from_slice::<MinecraftAuthStore>(&contents) {
+                Ok(store) => return Ok(store),
+                Err(e) => anyhow::Error::from(e).context("JSON Error reading accounts"),
+            },
+            Ok(None) => return Self::migrate_plaintext_store().await,
+            Err(e) => e.context("Failed to decrypt accounts"),
+        };
 
-            match serde_json::from_str::<MinecraftAuthStore>(&contents) {
-                Ok(store) => Ok(store),
-                Err(e) => {
-                    error!("JSON Error reading accounts.json: {:?}", e);
-                    Ok(MinecraftAuthStore::default())
-                }
-            }
-        } else {
-            Ok(MinecraftAuthStore::default())
+        // The unreadable store is kept, it can still be restored if the keyring only was unavailable
+        let backup_path = tokio::task::spawn_blocking(|| Self::secret_store().backup()).await?
+            .map_err(|e| ErrorKind::FSError(format!("{:?}, unable to back up the account store: {:?}", error, e)))?;
+        error!("{:?}, moved the account store to {:?} and continuing without accounts", error, backup_path);
+        Ok(MinecraftAuthStore::default())
+    }
+
+    /// Moves the accounts of the plaintext accounts.json of older launcher versions into the encrypted store
+    async fn migrate_plaintext_store() -> Result<Self, crate::error::Error> {
+        let auth_path = LAUNCHER_DIRECTORY.config_dir().join("accounts.json");
+        if !auth_path.exists() {
+            return Ok(MinecraftAuthStore::default());
         }
+
+        let contents = fs::read_to_string(&auth_path).await.map_err(|e| {
+            ErrorKind::FSError(format!("Failed to read accounts.json: {}", e))
+        })?;
+
+        let store = match serde_json::from_str::<MinecraftAuthStore>(&contents) {
+            Ok(store) => store,
+            Err(e) => {
+                let backup_path = auth_path.with_extension("json.bak");
+                fs::rename(&auth_path, &backup_path).await?;
+                error!("JSON Error reading accounts.json, moved it to {:?}: {:?}", backup_path, e);
+                return Ok(MinecraftAuthStore::default());
+            }
+        };
+
+        // The plaintext file is only removed once the accounts are stored encrypted
+        store.write().await?;
+        fs::remove_file(&auth_path).await?;
+        info!("Migrated {} accounts from accounts.json to the encrypted account store", store.users.len());
+
+        Ok(store)
     }
 
-    //TODO
     pub async fn save(&self) -> Result<(), crate::error::Error> {
-        let _ = fs::write(LAUNCHER_DIRECTORY.config_dir().join("accounts.json"), serde_json::to_string_pretty(&self)?).await?;
+        if self.detached {
+            return Ok(());
+        }
+        let _lock = STORE_LOCK.lock().await;
+        self.write().await
+    }
+
+    /// Loads the current store, applies the change and saves it while no other update can save in between
+    pub async fn update<F: FnOnce(&mut Self)>(change: F) -> Result<Self, crate::error::Error> {
+        let _lock = STORE_LOCK.lock().await;
+        let mut store = Self::init(None).await?;
+        change(&mut store);
+        store.write().await?;
+        Ok(store)
+    }
+
+    /// Copy of the store whose changes aren't saved, e.g. for long running token refreshes
+    pub fn detach(mut self) -> Self {
+        self.detached = true;
+        self
+    }
+
+    async fn write(&self) -> Result<(), crate::error::Error> {
+        let contents = serde_json::to_vec(&self)?;
+        tokio::task::spawn_blocking(move || Self::secret_store().write(&contents)).await??;
         Ok(())
     }
 
@@ -283,10 +349,44 @@ impl MinecraftAuthStore {
         debug!("oauth_token");
         window.emit("microsoft-output", "signIn.step.oauthToken").unwrap_or_default();
         let oauth_token = oauth_token(code, &flow.verifier).await?;
+
+        self.finish_login(oauth_token, Some(&flow.session_id), key, token, window).await
+    }
+
+    /// Polls until the user entered the device code on the verification page, none if the code expired.
+    /// Unlike the redirect flow this needs no webview, so it also works in headless or SSH sessions.
+    pub async fn login_device_code(
+        &mut self,
+        flow: &DeviceCodeFlow,
+        cancellation: &CancellationToken,
+        window: Window,
+    ) -> Result<Option<Credentials>, crate::error::Error> {
+        let Some(oauth_token) = wait_for_device_code(&ENDPOINTS.auth.oauth_token, flow, cancellation).await? else {
+            debug!("Device code expired");
+            return Ok(None);
+        };
+
+        debug!("refresh_and_get_device_token");
+        window.emit("microsoft-output", "signIn.step.deviceToken").unwrap_or_default();
+        let (key, token, _, _) =
+            self.refresh_and_get_device_token(Utc::now(), false).await?;
+
+        self.finish_login(oauth_token, None, key, token, window).await.map(Some)
+    }
+
+    /// Exchanges the Microsoft OAuth token for Xbox and Minecraft tokens and stores the account
+    async fn finish_login(
+        &mut self,
+        oauth_token: RequestWithDate<OAuthToken>,
+        session_id: Option<&str>,
+        key: DeviceTokenKey,
+        token: DeviceToken,
+        window: Window,
+    ) -> Result<Credentials, crate::error::Error> {
         window.emit("microsoft-output", "signIn.step.sisuAuthorize").unwrap_or_default();
         debug!("sisu_authorize");
         let sisu_authorize = sisu_authorize(
-            Some(&flow.session_id),
+            session_id,
             &oauth_token.value.access_token,
             &token.token,
             &key,
@@ -326,6 +426,7 @@ impl MinecraftAuthStore {
             access_token: minecraft_token.access_token,
             refresh_token: oauth_token.value.refresh_token,
             expires: oauth_token.date + Duration::seconds(oauth_token.value.expires_in as i64),
+            account_type: AccountType::Microsoft,
             norisk_credentials: if existing_accounts.users.get(&profile_id.clone()).is_some() { existing_accounts.users.get(&profile_id.clone()).unwrap().norisk_credentials.clone() } else { NoRiskCredentials { production: None, experimental: None } },
         };
 
@@ -342,12 +443,17 @@ impl MinecraftAuthStore {
         Ok(credentials)
     }
 
+    /// A refreshed account becomes the default account if `make_default` is set
     pub(crate) async fn refresh_norisk_token_if_necessary(
         &mut self,
         creds: &Credentials,
         force_update: bool,
+        make_default: bool,
     ) -> Result<Credentials, crate::error::Error> {
         debug!("Refreshing NoRisk Token... {:?}",creds.username);
+        if creds.is_offline() {
+            return Ok(creds.clone());
+        }
         let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default();
         let cred_id = creds.id;
         let mut maybe_update = false;
@@ -364,6 +470,10 @@ impl MinecraftAuthStore {
                         error!("New Username {:?} to {:?}",data.claims.username,creds.username);
                         maybe_update = true;
                     }
+                    if NoRiskToken::expiry_of(&token).map_or(false, |expires| expires - Duration::minutes(TOKEN_REFRESH_MARGIN_MINUTES) < Utc::now()) {
+                        debug!("NoRisk Token expires soon");
+                        maybe_update = true;
+                    }
                 }
                 Err(error) => {
                     maybe_update = true;
@@ -380,25 +490,80 @@ impl MinecraftAuthStore {
                 OtherError(format!("HWID Error {:?}", e))
             })?;
             debug!("Refreshing NoRisk Token Force[{:?}] Maybe[{:?}] HWID[{:?}]",force_update,maybe_update, hwid);
-            let norisk_token = ApiEndpoints::refresh_norisk_token(creds.access_token.as_str(), &hwid, true).await?;
 
-            let mut copied_credentials = creds.clone();
-            if options.experimental_mode {
-                copied_credentials.norisk_credentials.experimental = Some(norisk_token);
-            } else {
-                copied_credentials.norisk_credentials.production = Some(norisk_token);
+            if make_default {
+                self.default_user = Some(cred_id); //das könnte backfiren
             }
-
-            self.default_user = Some(cred_id); //das könnte backfiren
-            self.users.insert(cred_id, copied_credentials.clone());
-            self.save().await?;
-            Ok(copied_credentials)
+            self.fetch_norisk_token(creds, &hwid, options.experimental_mode).await
         } else {
             debug!("NoRisk Token is still fresh 8)");
             Ok(creds.clone())
         }
     }
 
+    /// Requests a new NoRisk token for the account and stores it with its expiry
+    async fn fetch_norisk_token(
+        &mut self,
+        creds: &Credentials,
+        hwid: &str,
+        experimental_mode: bool,
+    ) -> Result<Credentials, crate::error::Error> {
+        let mut norisk_token = ApiEndpoints::refresh_norisk_token(creds.access_token.as_str(), hwid, true).await?;
+        norisk_token.expires = NoRiskToken::expiry_of(&norisk_token.value);
+
+        let mut copied_credentials = creds.clone();
+        if experimental_mode {
+            copied_credentials.norisk_credentials.experimental = Some(norisk_token);
+        } else {
+            copied_credentials.norisk_credentials.production = Some(norisk_token);
+        }
+
+        self.users.insert(creds.id, copied_credentials.clone());
+        self.save().await?;
+        Ok(copied_credentials)
+    }
+
+    /// Refreshes the Microsoft and NoRisk tokens of the account which expire within the refresh margin,
+    /// none if they are still fresh. Unlike the refresh on launch this doesn't change the default account.
+    pub(crate) async fn refresh_expiring_tokens(
+        &mut self,
+        creds: &Credentials,
+    ) -> Result<Option<Credentials>, crate::error::Error> {
+        if creds.is_offline() {
+            return Ok(None);
+        }
+
+        let margin = Duration::minutes(TOKEN_REFRESH_MARGIN_MINUTES);
+        let mut creds = creds.clone();
+        let mut refreshed = false;
+
+        if creds.expires - margin < Utc::now() {
+            debug!("Refreshing Microsoft Token of {:?} in the background", creds.username);
+            creds = self.refresh_token(&creds).await?.ok_or(ErrorKind::NoCredentialsError)?;
+            refreshed = true;
+        }
+
+        let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default();
+        let norisk_token = if options.experimental_mode {
+            creds.norisk_credentials.experimental.as_ref()
+        } else {
+            creds.norisk_credentials.production.as_ref()
+        };
+
+        // Accounts without a NoRisk token aren't whitelisted, there is nothing to refresh
+        let norisk_expires = norisk_token.and_then(|token| token.expires.or_else(|| NoRiskToken::expiry_of(&token.value)));
+        if norisk_expires.map_or(false, |expires| expires - margin < Utc::now()) {
+            debug!("Refreshing NoRisk Token of {:?} in the background", creds.username);
+            let hwid = IdBuilder::new(Encryption::SHA256).add_component(HWIDComponent::SystemID).build("NRC").map_err(|e| {
+                OtherError(format!("HWID Error {:?}", e))
+            })?;
+            creds = self.fetch_norisk_token(&creds, &hwid, options.experimental_mode).await?;
+            refreshed = true;
+        }
+
+        Ok(Some(creds).filter(|_| refreshed))
+    }
+
     async fn refresh_token(
         &mut self,
         creds: &Credentials,
@@ -437,6 +602,7 @@ impl MinecraftAuthStore {
             refresh_token: oauth_token.value.refresh_token,
             expires: oauth_token.date + Duration::seconds(oauth_token.value.expires_in as i64),
             norisk_credentials: creds.clone().norisk_credentials,
+            account_type: creds.account_type,
         };
 
         self.users.insert(val.id, val.clone());
@@ -445,8 +611,11 @@ impl MinecraftAuthStore {
         Ok(Some(val))
     }
 
-    pub async fn update_norisk_and_microsoft_token(&mut self, creds: &Credentials) -> Result<Option<Credentials>, crate::error::Error> {
+    pub async fn update_norisk_and_microsoft_token(&mut self, creds: &Credentials, make_default: bool) -> Result<Option<Credentials>, crate::error::Error> {
         debug!("Checking Microsoft and NoRisk Token...");
+        if creds.is_offline() {
+            return Ok(Some(creds.clone()));
+        }
         debug!("Mojang Creds Expire Check {:?} {:?}", creds.expires, Utc::now());
         if creds.expires < Utc::now() {
             debug!("Refreshing expired Token {:?} {:?}", creds.expires, creds.id);
@@ -457,7 +626,7 @@ impl MinecraftAuthStore {
             match res {
                 Ok(val) => {
                     return if val.is_some() {
-                        Ok(Some(self.refresh_norisk_token_if_necessary(&val.unwrap().clone(), false).await?))
+                        Ok(Some(self.refresh_norisk_token_if_necessary(&val.unwrap().clone(), false, make_default).await?))
                     } else {
                         Err(ErrorKind::NoCredentialsError.as_error())
                     };
@@ -481,7 +650,7 @@ impl MinecraftAuthStore {
         } else {
             debug!("Microsoft Token is still valid {:?} {:?}", creds.expires, creds.id);
             //Refresh NoRisk Token
-            Ok(Some(self.refresh_norisk_token_if_necessary(&creds.clone(), false).await?))
+            Ok(Some(self.refresh_norisk_token_if_necessary(&creds.clone(), false, make_default).await?))
         }
     }
 
@@ -504,12 +673,41 @@ impl MinecraftAuthStore {
                 self.save().await?;
             }
 
-            Ok(self.update_norisk_and_microsoft_token(&creds.clone()).await?)
+            Ok(self.update_norisk_and_microsoft_token(&creds.clone(), true).await?)
         } else {
             Ok(None)
         }
     }
 
+    /// Adds an offline account, an existing offline account with the same name is replaced
+    pub async fn add_offline(&mut self, username: &str) -> Result<Credentials, crate::error::Error> {
+        let credentials = Credentials::offline(username)?;
+        if self.users.get(&credentials.id).is_some_and(|existing| !existing.is_offline()) {
+            return Err(ErrorKind::InputError(format!("{} is already added as a Microsoft account", username)).as_error());
+        }
+
+        self.users.insert(credentials.id, credentials.clone());
+        if self.default_user.is_none() {
+            self.default_user = Some(credentials.id);
+        }
+        self.save().await?;
+
+        Ok(credentials)
+    }
+
+    /// Refreshes the tokens of a specific account, the default account stays unchanged
+    pub async fn get_credential(
+        &mut self,
+        id: Uuid,
+    ) -> Result<Option<Credentials>, crate::error::Error> {
+        let Some(creds) = self.users.get(&id).cloned() else {
+            return Ok(None);
+        };
+
+        // Launching another account must not make it the default one
+        self.update_norisk_and_microsoft_token(&creds, false).await
+    }
+
     pub async fn remove(
         &mut self,
         id: Uuid,
@@ -545,6 +743,52 @@ pub struct Credentials {
     pub refresh_token: String,
     pub expires: DateTime<Utc>,
     pub norisk_credentials: NoRiskCredentials,
+    /// Missing for accounts stored by older launcher versions, which were always Microsoft accounts
+    #[serde(default)]
+    pub account_type: AccountType,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AccountType {
+    #[default]
+    Microsoft,
+    /// Local account without any tokens, only usable for vanilla launches and servers with `online-mode=false`
+    Offline,
+}
+
+impl Credentials {
+    pub fn is_offline(&self) -> bool {
+        self.account_type == AccountType::Offline
+    }
+
+    /// Creates an offline account with the same uuid an offline mode server assigns to the username
+    pub fn offline(username: &str) -> Result<Self, crate::error::Error> {
+        let valid_name = (3..=16).contains(&username.len())
+            && username.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid_name {
+            return Err(ErrorKind::InputError(format!("Invalid offline username: {:?}", username)).as_error());
+        }
+
+        Ok(Credentials {
+            id: offline_uuid(username),
+            username: username.to_string(),
+            access_token: "0".to_string(),
+            refresh_token: String::new(),
+            // Offline accounts are never refreshed, the expiry is ignored
+            expires: Utc::now(),
+            norisk_credentials: NoRiskCredentials { production: None, experimental: None },
+            account_type: AccountType::Offline,
+        })
+    }
+}
+
+/// Name based uuid like `UUID.nameUUIDFromBytes("OfflinePlayer:<name>")` of the server
+fn offline_uuid(username: &str) -> Uuid {
+    let mut bytes = md5::compute(format!("OfflinePlayer:{}", username)).0;
+    bytes[6] = (bytes[6] & 0x0f) | 0x30;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    Uuid::from_bytes(bytes)
 }
 
 //TODO JWT
@@ -557,9 +801,26 @@ pub struct NoRiskCredentials {
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct NoRiskToken {
     pub value: String,
-    //TODO habs nichts hinbekommen jetzt erstmal bei jedem restart, pub expires: DateTime<Utc>,
+    /// Expiry of the JWT, missing for tokens stored by older launcher versions
+    #[serde(default)]
+    pub expires: Option<DateTime<Utc>>,
+}
+
+impl NoRiskToken {
+    /// Reads the `exp` claim of the token without validating it
+    pub fn expiry_of(token: &str) -> Option<DateTime<Utc>> {
+        let key = DecodingKey::from_secret(&[]);
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.insecure_disable_signature_validation();
+        validation.validate_exp = false;
+        let data = decode::<NoRiskTokenClaims>(token, &key, &validation).ok()?;
+        DateTime::from_timestamp(data.claims.exp as i64, 0)
+    }
 }
 
+/// Tokens are refreshed this long before they expire
+pub const TOKEN_REFRESH_MARGIN_MINUTES: i64 = 15;
+
 impl NoRiskCredentials {
     pub async fn get_token(&self, experimental_mode: bool) -> Result<String, crate::error::Error> {
         if experimental_mode && self.experimental.is_some() {
@@ -600,8 +861,7 @@ pub async fn device_token(
 ) -> Result<RequestWithDate<DeviceToken>, MinecraftAuthenticationError> {
     let res = send_signed_request(
         None,
-        "https://device.auth.xboxlive.com/device/authenticate",
-        "/device/authenticate",
+        &ENDPOINTS.auth.device_authenticate,
         json!({
             "Properties": {
                 "AuthMethod": "ProofOfPossession",
@@ -648,8 +908,7 @@ async fn sisu_authenticate(
 {
     let res = send_signed_request::<RedirectUri>(
         None,
-        "https://sisu.xboxlive.com/authenticate",
-        "/authenticate",
+        &ENDPOINTS.auth.sisu_authenticate,
         json!({
           "AppId": MICROSOFT_CLIENT_ID,
           "DeviceToken": token,
@@ -714,7 +973,7 @@ async fn oauth_token(
 
     let res = auth_retry(|| {
         HTTP_CLIENT
-            .post("https://login.live.com/oauth20_token.srf")
+            .post(&ENDPOINTS.auth.oauth_token)
             .header("Accept", "application/json")
             .form(&query)
             .send()
@@ -761,7 +1020,7 @@ async fn oauth_refresh(
 
     let res = auth_retry(|| {
         HTTP_CLIENT
-            .post("https://login.live.com/oauth20_token.srf")
+            .post(&ENDPOINTS.auth.oauth_token)
             .header("Accept", "application/json")
             .form(&query)
             .send()
@@ -796,6 +1055,145 @@ async fn oauth_refresh(
     })
 }
 
+/// Code the user enters on the verification page to sign in on another device
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DeviceCodeFlow {
+    pub user_code: String,
+    #[serde(skip_serializing)]
+    pub device_code: String,
+    pub verification_uri: String,
+    pub expires_in: u64,
+    pub interval: u64,
+}
+
+pub async fn device_code() -> Result<DeviceCodeFlow, MinecraftAuthenticationError> {
+    let mut query = HashMap::new();
+    query.insert("client_id", MICROSOFT_CLIENT_ID);
+    query.insert("scope", REQUESTED_SCOPES);
+    query.insert("response_type", "device_code");
+
+    let res = auth_retry(|| {
+        HTTP_CLIENT
+            .post(&ENDPOINTS.auth.oauth_device_code)
+            .header("Accept", "application/json")
+            .form(&query)
+            .send()
+    })
+        .await
+        .map_err(|source| MinecraftAuthenticationError::Request {
+            source,
+            step: MinecraftAuthStep::GetDeviceCode,
+        })?;
+
+    let status = res.status();
+    let text = res.text().await.map_err(|source| {
+        MinecraftAuthenticationError::Request {
+            source,
+            step: MinecraftAuthStep::GetDeviceCode,
+        }
+    })?;
+
+    serde_json::from_str(&text).map_err(|source| {
+        MinecraftAuthenticationError::DeserializeResponse {
+            source,
+            raw: text,
+            step: MinecraftAuthStep::GetDeviceCode,
+            status_code: status,
+        }
+    })
+}
+
+/// Polls the token endpoint in the interval Microsoft asks for, none if the code expired first
+async fn wait_for_device_code(
+    token_url: &str,
+    flow: &DeviceCodeFlow,
+    cancellation: &CancellationToken,
+) -> Result<Option<RequestWithDate<OAuthToken>>, crate::error::Error> {
+    let expires = tokio::time::Instant::now() + std::time::Duration::from_secs(flow.expires_in);
+    let mut interval = flow.interval.max(1);
+
+    loop {
+        if tokio::time::Instant::now() > expires {
+            return Ok(None);
+        }
+
+        cancellation.run(tokio::time::sleep(std::time::Duration::from_secs(interval))).await?;
+
+        match poll_device_code(token_url, &flow.device_code).await? {
+            DeviceCodePoll::Pending => {}
+            DeviceCodePoll::SlowDown => interval += 5,
+            DeviceCodePoll::Token(oauth_token) => return Ok(Some(oauth_token)),
+        }
+    }
+}
+
+enum DeviceCodePoll {
+    Pending,
+    SlowDown,
+    Token(RequestWithDate<OAuthToken>),
+}
+
+#[derive(Deserialize)]
+struct OAuthError {
+    error: String,
+}
+
+async fn poll_device_code(
+    token_url: &str,
+    device_code: &str,
+) -> Result<DeviceCodePoll, MinecraftAuthenticationError> {
+    let mut query = HashMap::new();
+    query.insert("client_id", MICROSOFT_CLIENT_ID);
+    query.insert("device_code", device_code);
+    query.insert("grant_type", "urn:ietf:params:oauth:grant-type:device_code");
+
+    let res = auth_retry(|| {
+        HTTP_CLIENT
+            .post(token_url)
+            .header("Accept", "application/json")
+            .form(&query)
+            .send()
+    })
+        .await
+        .map_err(|source| MinecraftAuthenticationError::Request {
+            source,
+            step: MinecraftAuthStep::PollDeviceCode,
+        })?;
+
+    let status = res.status();
+    let current_date = get_date_header(res.headers());
+    let text = res.text().await.map_err(|source| {
+        MinecraftAuthenticationError::Request {
+            source,
+            step: MinecraftAuthStep::PollDeviceCode,
+        }
+    })?;
+
+    if !status.is_success() {
+        if let Ok(error) = serde_json::from_str::<OAuthError>(&text) {
+            return match error.error.as_str() {
+                "authorization_pending" => Ok(DeviceCodePoll::Pending),
+                "slow_down" => Ok(DeviceCodePoll::SlowDown),
+                _ => Err(MinecraftAuthenticationError::DeviceCode(error.error)),
+            };
+        }
+    }
+
+    let body = serde_json::from_str(&text).map_err(|source| {
+        MinecraftAuthenticationError::DeserializeResponse {
+            source,
+            raw: text,
+            step: MinecraftAuthStep::PollDeviceCode,
+            status_code: status,
+        }
+    })?;
+
+    Ok(DeviceCodePoll::Token(RequestWithDate {
+        date: current_date,
+        value: body,
+    }))
+}
+
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "PascalCase")]
 struct SisuAuthorize {
@@ -816,8 +1214,7 @@ async fn sisu_authorize(
 ) -> Result<RequestWithDate<SisuAuthorize>, MinecraftAuthenticationError> {
     let res = send_signed_request(
         None,
-        "https://sisu.xboxlive.com/authorize",
-        "/authorize",
+        &ENDPOINTS.auth.sisu_authorize,
         json!({
             "AccessToken": format!("t={access_token}"),
             "AppId": "00000000402b5328",
@@ -856,8 +1253,7 @@ async fn xsts_authorize(
 ) -> Result<RequestWithDate<DeviceToken>, MinecraftAuthenticationError> {
     let res = send_signed_request(
         None,
-        "https://xsts.auth.xboxlive.com/xsts/authorize",
-        "/xsts/authorize",
+        &ENDPOINTS.auth.xsts_authorize,
         json!({
             "RelyingParty": "rp://api.minecraftservices.com/",
             "TokenType": "JWT",
@@ -903,7 +1299,7 @@ async fn minecraft_token(
 
     let res = auth_retry(|| {
         HTTP_CLIENT
-            .post("https://api.minecraftservices.com/launcher/login")
+            .post(format!("{}/launcher/login", ENDPOINTS.minecraft_services))
             .header("Accept", "application/json")
             .json(&json!({
                 "platform": "PC_LAUNCHER",
@@ -946,7 +1342,7 @@ async fn minecraft_profile(
 ) -> Result<MinecraftProfile, MinecraftAuthenticationError> {
     let res = auth_retry(|| {
         HTTP_CLIENT
-            .get("https://api.minecraftservices.com/minecraft/profile")
+            .get(format!("{}/minecraft/profile", ENDPOINTS.minecraft_services))
             .header("Accept", "application/json")
             .bearer_auth(token)
             .send()
@@ -984,7 +1380,7 @@ async fn minecraft_entitlements(
 ) -> Result<MinecraftEntitlements, MinecraftAuthenticationError> {
     let res = auth_retry(|| {
         HTTP_CLIENT
-            .get(format!("https://api.minecraftservices.com/entitlements/license?requestId={}", Uuid::new_v4()))
+            .get(format!("{}/entitlements/license?requestId={}", ENDPOINTS.minecraft_services, Uuid::new_v4()))
             .header("Accept", "application/json")
             .bearer_auth(token)
             .send()
@@ -1085,12 +1481,13 @@ struct SignedRequestResponse<T> {
 async fn send_signed_request<T: DeserializeOwned>(
     authorization: Option<&str>,
     url: &str,
-    url_path: &str,
     raw_body: serde_json::Value,
     key: &DeviceTokenKey,
     step: MinecraftAuthStep,
     current_date: DateTime<Utc>,
 ) -> Result<SignedRequestResponse<T>, MinecraftAuthenticationError> {
+    // The path is part of the signature, it is taken from the url so configured endpoints are signed correctly
+    let url_path = reqwest::Url::parse(url).map(|url| url.path().to_string()).unwrap_or_default();
     let auth = authorization.map_or(Vec::new(), |v| v.as_bytes().to_vec());
 
     let body = serde_json::to_vec(&raw_body).map_err(|source| {
@@ -1161,7 +1558,7 @@ async fn send_signed_request<T: DeserializeOwned>(
             .header("Accept", "application/json")
             .header("Signature", &signature);
 
-        if url != "https://sisu.xboxlive.com/authorize" {
+        if !matches!(step, MinecraftAuthStep::SisuAuthorize) {
             request = request.header("x-xbl-contract-version", "1");
         }
 
@@ -1213,3 +1610,114 @@ fn generate_oauth_challenge() -> String {
     let bytes: Vec<u8> = (0..64).map(|_| rng.gen::<u8>()).collect();
     bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::time::Instant;
+
+    use super::*;
+
+    /// Answers the requests in order, the last answer is repeated. Also returns when each request arrived.
+    async fn serve(responses: Vec<(u16, &'static str)>) -> (String, Arc<Mutex<Vec<Instant>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let requests = Arc::new(Mutex::new(vec![]));
+        let arrivals = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = [0; 1024];
+                let _ = stream.read(&mut request).await;
+                let count = {
+                    let mut arrivals = arrivals.lock().unwrap();
+                    arrivals.push(Instant::now());
+                    arrivals.len()
+                };
+                let (status, body) = responses[(count - 1).min(responses.len() - 1)];
+                let response = format!(
+                    "HTTP/1.1 {} Status\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status, body.len(), body
+                );
+                let _ = stream.write_all(response.as_bytes()).await;
+            }
+        });
+        (format!("http://{}/oauth20_token.srf", address), requests)
+    }
+
+    fn device_code_flow(expires_in: u64) -> DeviceCodeFlow {
+        DeviceCodeFlow {
+            user_code: "ABCD1234".to_string(),
+            device_code: "device".to_string(),
+            verification_uri: "https://www.microsoft.com/link".to_string(),
+            expires_in,
+            interval: 1,
+        }
+    }
+
+    const PENDING: (u16, &str) = (400, r#"{"error": "authorization_pending"}"#);
+
+    #[tokio::test(start_paused = true)]
+    async fn device_code_polling_slows_down_when_asked() {
+        let (url, requests) = serve(vec![
+            PENDING,
+            (400, r#"{"error": "slow_down"}"#),
+            (200, r#"{"expires_in": 3600, "access_token": "access", "refresh_token": "refresh"}"#),
+        ]).await;
+
+        let start = Instant::now();
+        let token = wait_for_device_code(&url, &device_code_flow(900), &CancellationToken::new()).await.unwrap().unwrap();
+        assert_eq!(token.value.access_token, "access");
+        assert_eq!(token.value.refresh_token, "refresh");
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests.len(), 3);
+        assert_eq!((requests[0] - start).as_secs(), 1);
+        assert_eq!((requests[1] - requests[0]).as_secs(), 1);
+        assert_eq!((requests[2] - requests[1]).as_secs(), 6);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn expired_device_codes_stop_polling() {
+        let (url, requests) = serve(vec![PENDING]).await;
+
+        let start = Instant::now();
+        assert!(wait_for_device_code(&url, &device_code_flow(3), &CancellationToken::new()).await.unwrap().is_none());
+        assert!(start.elapsed() < std::time::Duration::from_secs(5));
+        assert_eq!(requests.lock().unwrap().len(), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn cancelled_device_codes_stop_polling() {
+        let (url, requests) = serve(vec![PENDING]).await;
+        let cancellation = CancellationToken::new();
+        cancellation.cancel();
+
+        assert!(wait_for_device_code(&url, &device_code_flow(900), &cancellation).await.is_err());
+        assert!(requests.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn declined_device_codes_fail() {
+        let (url, _) = serve(vec![(400, r#"{"error": "authorization_declined"}"#)]).await;
+        assert!(wait_for_device_code(&url, &device_code_flow(900), &CancellationToken::new()).await.is_err());
+    }
+
+    #[test]
+    fn offline_uuids_match_the_server() {
+        assert_eq!(offline_uuid("Notch").to_string(), "b50ad385-829d-3141-a216-7e7d7539ba7f");
+    }
+
+    #[test]
+    fn offline_usernames_are_validated() {
+        let credentials = Credentials::offline("Notch").unwrap();
+        assert!(credentials.is_offline());
+        assert_eq!(credentials.id, offline_uuid("Notch"));
+
+        assert!(Credentials::offline("ab").is_err());
+        assert!(Credentials::offline("name with spaces").is_err());
+        assert!(Credentials::offline("seventeen_chars_x").is_err());
+    }
+}
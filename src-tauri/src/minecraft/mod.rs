@@ -3,6 +3,9 @@ pub mod version;
 mod rule_interpreter;
 pub mod auth;
 pub mod prelauncher;
+pub mod forge_installer;
+pub mod norisk_assets;
 pub mod progress;
 pub mod java;
-pub mod minecraft_auth;
\ No newline at end of file
+pub mod minecraft_auth;
+pub mod token_refresher;
\ No newline at end of file
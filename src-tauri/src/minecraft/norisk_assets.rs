@@ -0,0 +1,460 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::Result;
+use log::{debug, info, warn};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use walkdir::WalkDir;
+
+use crate::minecraft::progress::{ProgressReceiver, ProgressTracker, ProgressUpdate, ProgressUpdateSteps};
+use crate::minecraft::version::AssetObject;
+use crate::utils::{md5sum, md5sum_blocking};
+use crate::{LAUNCHER_DIRECTORY, LAUNCHER_VERSION};
+
+/// Files in the asset directory which are never touched by the verification
+const IGNORED_FILES: [&str; 1] = [".DS_Store"];
+
+pub fn is_cosmetic_asset(location: &str) -> bool {
+    location.starts_with("nrc-cosmetics/")
+}
+
+/// Where an asset is stored. Cosmetics live in the NoRisk asset directory, all other assets in the game directory.
+pub fn norisk_asset_path(location: &str, asset_dir: &Path, game_dir: &Path) -> PathBuf {
+    let base = if is_cosmetic_asset(location) { asset_dir } else { game_dir };
+    location.split('/').fold(base.to_path_buf(), |path, part| path.join(part))
+}
+
+/// Result of checking the NoRisk asset directory against the assets of a branch
+#[derive(Debug, Default)]
+pub struct NoRiskAssetReport {
+    pub verified: usize,
+    /// Files which don't belong to any asset of the branch
+    pub stale: Vec<PathBuf>,
+    /// Cosmetics whose MD5 doesn't match the asset
+    pub invalid: Vec<PathBuf>,
+    /// Non-cosmetic assets whose MD5 doesn't match, these might be edited by the user and are kept
+    pub modified: Vec<PathBuf>,
+    /// Cosmetics that should exist but don't, e.g. because their download failed
+    pub missing: Vec<String>,
+}
+
+impl NoRiskAssetReport {
+    /// Files which are removed when the report is applied
+    pub fn to_remove(&self) -> impl Iterator<Item = &PathBuf> {
+        self.stale.iter().chain(self.invalid.iter())
+    }
+}
+
+/// Checks every file of the asset directory by its path relative to the directory and its MD5,
+/// without changing anything.
+/// Files replaced by an override are expected but not compared with the asset.
+pub async fn check_norisk_assets(asset_dir: &Path, game_dir: &Path, asset_objects: &HashMap<String, AssetObject>, overrides: Option<&AssetOverrides>, progress: Arc<impl ProgressReceiver>) -> Result<NoRiskAssetReport> {
+    let expected: HashMap<PathBuf, (&String, &AssetObject)> = asset_objects.iter()
+        .map(|(location, asset_object)| (norisk_asset_path(location, asset_dir, game_dir), (location, asset_object)))
+        .collect();
+    let overridden: HashSet<PathBuf> = overrides.map_or_else(HashSet::new, |overrides| {
+        overrides.files.keys().map(|location| norisk_asset_path(location, asset_dir, game_dir)).collect()
+    });
+
+    let walk_dir = asset_dir.to_path_buf();
+    let files = tokio::task::spawn_blocking(move || {
+        WalkDir::new(walk_dir).into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .filter(|entry| !IGNORED_FILES.contains(&entry.file_name().to_string_lossy().as_ref()))
+            .map(|entry| entry.into_path())
+            .collect::<Vec<_>>()
+    }).await?;
+    let total = files.len() as u64;
+    let tracker = ProgressTracker::new(ProgressUpdateSteps::VerifyNoRiskAssets, total, 0);
+
+    progress.progress_update(ProgressUpdate::set_label("translation.verifyingNoriskAssets"));
+    progress.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::VerifyNoRiskAssets, 0, total));
+
+    let mut report = NoRiskAssetReport::default();
+    for path in files {
+        let relative_path = path.strip_prefix(asset_dir).unwrap_or(&path).to_string_lossy().replace('\\', "/");
+
+        if overridden.contains(&path) {
+            report.verified += 1;
+        } else if let Some((location, asset_object)) = expected.get(&path) {
+            let matches = md5sum_blocking(path.clone()).await.is_ok_and(|md5| md5 == asset_object.hash);
+            if matches {
+                report.verified += 1;
+            } else if is_cosmetic_asset(location) {
+                report.invalid.push(path);
+            } else {
+                report.modified.push(path);
+            }
+        } else {
+            report.stale.push(path);
+        }
+
+        if let Some(state) = tracker.complete_item(0, &relative_path) {
+            progress.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::VerifyNoRiskAssets, state.completed_items, total));
+            progress.progress_state(Some(state));
+        }
+    }
+
+    report.missing = expected.iter()
+        .filter(|(path, (location, _))| is_cosmetic_asset(location) && !path.exists() && !overridden.contains(*path))
+        .map(|(_, (location, _))| location.to_string())
+        .collect();
+
+    progress.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::VerifyNoRiskAssets, total, total));
+    Ok(report)
+}
+
+/// Checks the asset directory and removes stale and invalid files afterwards
+pub async fn verify_norisk_assets(asset_dir: &Path, game_dir: &Path, asset_objects: &HashMap<String, AssetObject>, overrides: Option<&AssetOverrides>, progress: Arc<impl ProgressReceiver>) -> Result<NoRiskAssetReport> {
+    let report = check_norisk_assets(asset_dir, game_dir, asset_objects, overrides, progress).await?;
+    info!(
+        "Norisk asset verification: {} verified, {} stale, {} invalid, {} modified, {} missing",
+        report.verified, report.stale.len(), report.invalid.len(), report.modified.len(), report.missing.len()
+    );
+    for path in &report.modified {
+        info!("Keeping modified Norisk asset {}", path.display());
+    }
+    for location in &report.missing {
+        warn!("Norisk asset {} is missing", location);
+    }
+
+    for path in report.to_remove() {
+        if let Err(err) = fs::remove_file(path).await {
+            info!("Failed to remove {}: {}", path.display(), err);
+        } else {
+            info!("Removed file {} since it doesn't match any asset object of this branch.", path.display());
+        }
+    }
+
+    Ok(report)
+}
+
+/// Record of the non-cosmetic assets the launcher installed into the game directory of a branch.
+/// It is used to tell apart outdated assets, which are updated, from assets the user edited, which are kept.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct InstalledNoRiskAssets {
+    #[serde(default)]
+    pub assets: HashMap<String, InstalledNoRiskAsset>,
+    /// Set if the game directory had no record yet, its assets were installed by a launcher that didn't keep one
+    #[serde(skip)]
+    pub unrecorded: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstalledNoRiskAsset {
+    pub hash: String,
+    #[serde(rename = "launcherVersion")]
+    pub launcher_version: String,
+}
+
+/// State of a non-cosmetic asset in the game directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NonCosmeticAssetState {
+    Missing,
+    UpToDate,
+    /// Still the file the launcher installed, but the asset got updated since
+    Outdated,
+    /// Changed locally or not installed by the launcher, it is kept and reported as conflict
+    Modified,
+    /// Differs from the asset, but was installed before the launcher kept a record.
+    /// It is updated like an outdated asset, the previous file is kept as backup in case the user edited it.
+    Unrecorded,
+}
+
+impl InstalledNoRiskAssets {
+    fn path(game_dir: &Path) -> PathBuf {
+        game_dir.join("NoRiskClient").join("installed_assets.json")
+    }
+
+    pub async fn load(game_dir: &Path) -> Self {
+        let path = Self::path(game_dir);
+        match fs::read(&path).await {
+            Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|err| {
+                warn!("Failed to read {:?}, starting with an empty record: {}", path, err);
+                Self::default()
+            }),
+            Err(_) => Self { unrecorded: true, ..Self::default() },
+        }
+    }
+
+    pub async fn store(&self, game_dir: &Path) -> Result<()> {
+        let path = Self::path(game_dir);
+        fs::create_dir_all(path.parent().unwrap()).await?;
+        fs::write(path, serde_json::to_string_pretty(self)?).await?;
+        Ok(())
+    }
+
+    pub fn record(&mut self, location: &str, hash: &str) {
+        self.assets.insert(location.to_string(), InstalledNoRiskAsset {
+            hash: hash.to_string(),
+            launcher_version: LAUNCHER_VERSION.to_string(),
+        });
+    }
+}
+
+/// Compares the file of a non-cosmetic asset with the asset and the hash the launcher installed last.
+/// `unrecorded` is the flag of [`InstalledNoRiskAssets`], without a record every differing file would count as modified.
+pub async fn non_cosmetic_asset_state(path: &Path, asset_object: &AssetObject, installed: Option<&InstalledNoRiskAsset>, unrecorded: bool) -> NonCosmeticAssetState {
+    if !path.exists() {
+        return NonCosmeticAssetState::Missing;
+    }
+
+    let md5 = match md5sum_blocking(path.to_path_buf()).await {
+        Ok(md5) => md5,
+        Err(_) => return NonCosmeticAssetState::Modified,
+    };
+
+    if md5 == asset_object.hash {
+        NonCosmeticAssetState::UpToDate
+    } else if installed.is_some_and(|installed| installed.hash == md5) {
+        NonCosmeticAssetState::Outdated
+    } else if installed.is_none() && unrecorded {
+        NonCosmeticAssetState::Unrecorded
+    } else {
+        NonCosmeticAssetState::Modified
+    }
+}
+
+/// Moves an unrecorded asset aside before it gets replaced
+pub async fn backup_unrecorded_asset(path: &Path) -> Result<PathBuf> {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".bak");
+    let backup = path.with_file_name(file_name);
+    fs::rename(path, &backup).await?;
+    Ok(backup)
+}
+
+/// Settings of the local asset override directory, only available to developers and designers
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct AssetOverrideSettings {
+    pub directory: Option<String>,
+}
+
+impl AssetOverrideSettings {
+    fn path() -> PathBuf {
+        LAUNCHER_DIRECTORY.config_dir().join("asset_overrides.json")
+    }
+
+    pub async fn load() -> Self {
+        match fs::read(Self::path()).await {
+            Ok(content) => serde_json::from_slice(&content).unwrap_or_default(),
+            Err(_) => Self::default(),
+        }
+    }
+
+    pub async fn store(&self) -> Result<()> {
+        fs::write(Self::path(), serde_json::to_string_pretty(self)?).await?;
+        Ok(())
+    }
+
+    /// Overrides of the configured directory, none if no directory is configured
+    pub fn overrides(&self) -> Option<AssetOverrides> {
+        let directory = PathBuf::from(self.directory.as_ref().filter(|directory| !directory.is_empty())?);
+        if !directory.is_dir() {
+            warn!("Asset override directory {:?} doesn't exist", directory);
+            return None;
+        }
+
+        Some(AssetOverrides::load(&directory))
+    }
+}
+
+/// Local files which take priority over the CDN assets, keyed by the same location as the assets.
+/// Files without a matching asset are installed as well.
+#[derive(Debug)]
+pub struct AssetOverrides {
+    pub files: HashMap<String, PathBuf>,
+}
+
+impl AssetOverrides {
+    pub fn load(directory: &Path) -> Self {
+        let files = WalkDir::new(directory).into_iter()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_type().is_file())
+            .filter(|entry| !IGNORED_FILES.contains(&entry.file_name().to_string_lossy().as_ref()))
+            .filter_map(|entry| {
+                let location = entry.path().strip_prefix(directory).ok()?.to_string_lossy().replace('\\', "/");
+                Some((location, entry.into_path()))
+            })
+            .collect();
+
+        AssetOverrides { files }
+    }
+
+    pub fn contains(&self, location: &str) -> bool {
+        self.files.contains_key(location)
+    }
+
+    /// Copies the override files into place. Non-cosmetic overrides are recorded as installed,
+    /// so they are updated from the CDN again once the override is removed.
+    pub async fn apply(&self, asset_dir: &Path, game_dir: &Path, installed: &mut InstalledNoRiskAssets) -> Result<()> {
+        for (location, source) in &self.files {
+            let destination = norisk_asset_path(location, asset_dir, game_dir);
+            let source_md5 = md5sum(source)?;
+
+            if destination.exists() && md5sum(&destination).map_or(false, |md5| md5 == source_md5) {
+                debug!("Norisk asset override {} is already in place", location);
+            } else {
+                fs::create_dir_all(destination.parent().unwrap()).await?;
+                fs::copy(source, &destination).await?;
+                info!("Installed Norisk asset override {}", location);
+            }
+
+            if !is_cosmetic_asset(location) {
+                installed.record(location, &source_md5);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    struct NoProgress;
+
+    impl ProgressReceiver for NoProgress {
+        fn progress_update(&self, _update: ProgressUpdate) {}
+    }
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("nrc-norisk-assets-{}", Uuid::new_v4()))
+    }
+
+    async fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).await.unwrap();
+        fs::write(path, content).await.unwrap();
+    }
+
+    fn asset(content: &str) -> AssetObject {
+        AssetObject { hash: format!("{:x}", md5::compute(content)), size: content.len() as i64 }
+    }
+
+    async fn check(asset_dir: &Path, assets: &HashMap<String, AssetObject>, overrides: Option<&AssetOverrides>) -> NoRiskAssetReport {
+        check_norisk_assets(asset_dir, &asset_dir.join("game"), assets, overrides, Arc::new(NoProgress)).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn assets_with_the_same_name_are_told_apart_by_their_folder() {
+        let asset_dir = temp_dir();
+        let assets = HashMap::from([
+            ("nrc-cosmetics/hats/texture.png".to_string(), asset("hat")),
+            ("nrc-cosmetics/wings/texture.png".to_string(), asset("wings")),
+        ]);
+        write(&asset_dir.join("nrc-cosmetics/hats/texture.png"), "hat").await;
+        write(&asset_dir.join("nrc-cosmetics/wings/texture.png"), "wings").await;
+
+        let report = check(&asset_dir, &assets, None).await;
+        assert_eq!(report.verified, 2);
+        assert!(report.invalid.is_empty() && report.stale.is_empty());
+
+        // Swapped files have the right names but not the right content
+        write(&asset_dir.join("nrc-cosmetics/hats/texture.png"), "wings").await;
+        write(&asset_dir.join("nrc-cosmetics/wings/texture.png"), "hat").await;
+
+        let report = check(&asset_dir, &assets, None).await;
+        assert_eq!(report.verified, 0);
+        assert_eq!(report.invalid.len(), 2);
+
+        fs::remove_dir_all(&asset_dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn stale_files_are_reported_even_if_an_asset_has_their_name() {
+        let asset_dir = temp_dir();
+        let assets = HashMap::from([
+            ("nrc-cosmetics/capes/cape.png".to_string(), asset("cape")),
+            ("nrc-cosmetics/capes/missing.png".to_string(), asset("missing")),
+        ]);
+        write(&asset_dir.join("nrc-cosmetics/capes/cape.png"), "cape").await;
+        write(&asset_dir.join("nrc-cosmetics/old/cape.png"), "cape").await;
+        write(&asset_dir.join("nrc-cosmetics/old/.DS_Store"), "").await;
+
+        let report = check(&asset_dir, &assets, None).await;
+        assert_eq!(report.verified, 1);
+        assert_eq!(report.stale, vec![asset_dir.join("nrc-cosmetics/old/cape.png")]);
+        assert_eq!(report.missing, vec!["nrc-cosmetics/capes/missing.png".to_string()]);
+        assert_eq!(report.to_remove().count(), 1);
+
+        fs::remove_dir_all(&asset_dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn overridden_assets_are_not_compared() {
+        let asset_dir = temp_dir();
+        let assets = HashMap::from([
+            ("nrc-cosmetics/capes/cape.png".to_string(), asset("cape")),
+            ("nrc-cosmetics/capes/other.png".to_string(), asset("other")),
+        ]);
+        write(&asset_dir.join("nrc-cosmetics/capes/cape.png"), "local cape").await;
+        write(&asset_dir.join("nrc-cosmetics/local/new.png"), "new").await;
+        let overrides = AssetOverrides {
+            files: HashMap::from([
+                ("nrc-cosmetics/capes/cape.png".to_string(), PathBuf::from("cape.png")),
+                ("nrc-cosmetics/capes/other.png".to_string(), PathBuf::from("other.png")),
+                ("nrc-cosmetics/local/new.png".to_string(), PathBuf::from("new.png")),
+            ]),
+        };
+
+        let report = check(&asset_dir, &assets, Some(&overrides)).await;
+        assert_eq!(report.verified, 2);
+        assert!(report.invalid.is_empty() && report.stale.is_empty() && report.missing.is_empty());
+
+        fs::remove_dir_all(&asset_dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn non_cosmetic_assets_are_compared_with_the_installed_hash() {
+        let game_dir = temp_dir();
+        let path = game_dir.join("config").join("nrc.json");
+        let asset_object = asset("new");
+        let installed = InstalledNoRiskAsset { hash: asset("old").hash, launcher_version: LAUNCHER_VERSION.to_string() };
+
+        assert_eq!(non_cosmetic_asset_state(&path, &asset_object, Some(&installed), false).await, NonCosmeticAssetState::Missing);
+
+        write(&path, "new").await;
+        assert_eq!(non_cosmetic_asset_state(&path, &asset_object, Some(&installed), false).await, NonCosmeticAssetState::UpToDate);
+
+        write(&path, "old").await;
+        assert_eq!(non_cosmetic_asset_state(&path, &asset_object, Some(&installed), false).await, NonCosmeticAssetState::Outdated);
+
+        write(&path, "edited").await;
+        assert_eq!(non_cosmetic_asset_state(&path, &asset_object, Some(&installed), false).await, NonCosmeticAssetState::Modified);
+        assert_eq!(non_cosmetic_asset_state(&path, &asset_object, None, false).await, NonCosmeticAssetState::Modified);
+
+        fs::remove_dir_all(&game_dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn assets_without_a_record_are_updated_with_a_backup() {
+        let game_dir = temp_dir();
+        let path = game_dir.join("config").join("nrc.json");
+        write(&path, "old").await;
+
+        let installed_assets = InstalledNoRiskAssets::load(&game_dir).await;
+        assert!(installed_assets.unrecorded);
+        assert_eq!(non_cosmetic_asset_state(&path, &asset("new"), None, installed_assets.unrecorded).await, NonCosmeticAssetState::Unrecorded);
+
+        let backup = backup_unrecorded_asset(&path).await.unwrap();
+        assert_eq!(backup, game_dir.join("config").join("nrc.json.bak"));
+        assert_eq!(fs::read_to_string(&backup).await.unwrap(), "old");
+        assert!(!path.exists());
+
+        // Once stored, assets added later are new files of the launcher or the user's own
+        let mut installed_assets = installed_assets;
+        installed_assets.record("config/nrc.json", &asset("new").hash);
+        installed_assets.store(&game_dir).await.unwrap();
+        let installed_assets = InstalledNoRiskAssets::load(&game_dir).await;
+        assert!(!installed_assets.unrecorded);
+        assert_eq!(installed_assets.assets["config/nrc.json"].hash, asset("new").hash);
+
+        fs::remove_dir_all(&game_dir).await.unwrap();
+    }
+}
@@ -1,3 +1,5 @@
+use std::cell::Cell;
+use std::collections::HashMap;
 use std::path::Path;
 use std::sync::{Mutex, Arc};
 
@@ -7,21 +9,24 @@ use tokio::fs;
 use uuid::Uuid;
 
 use crate::app::api::{LoaderSubsystem, ModSource, LoaderMod, NoRiskLaunchManifest};
+use crate::app::app_data::MavenRepository;
 use crate::error::LauncherError;
 use crate::LAUNCHER_DIRECTORY;
+use crate::minecraft::forge_installer::ForgeInstaller;
 use crate::minecraft::launcher;
-use crate::minecraft::launcher::{LauncherData, LaunchingParameter};
-use crate::minecraft::progress::{get_max, get_progress, ProgressReceiver, ProgressUpdate, ProgressUpdateSteps};
+use crate::minecraft::launcher::{LaunchTarget, LauncherData, LaunchingParameter};
+use crate::minecraft::progress::{get_max, get_progress, ProgressReceiver, ProgressTracker, ProgressUpdate, ProgressUpdateSteps};
 use crate::minecraft::version::{VersionManifest, VersionProfile};
-use crate::utils::{download_file, get_maven_artifact_path};
+use crate::utils::{download_file_to_with_auth, DirectoryLock, MavenCoordinate};
 
 ///
 /// Prelaunching client
 ///
 pub(crate) async fn launch<D: Send + Sync>(multiple_instances: bool, norisk_token: &str, uuid: &str, launch_manifest: NoRiskLaunchManifest, launching_parameter: LaunchingParameter, additional_mods: Vec<LoaderMod>, progress: LauncherData<D>, window: Arc<Mutex<tauri::Window>>, instance_id: Uuid) -> Result<()> {
     info!("Loading minecraft version manifest...");
+    let cancellation = progress.cancellation.clone();
     let data_path = LAUNCHER_DIRECTORY.data_dir().join("gameDir").join(&launch_manifest.build.branch).join("nrc_cache");
-    let mc_version_manifest = VersionManifest::download(&data_path).await?;
+    let mc_version_manifest = cancellation.run(VersionManifest::download(&data_path)).await??;
 
     let build = &launch_manifest.build;
     let subsystem = &launch_manifest.subsystem;
@@ -31,19 +36,50 @@ pub(crate) async fn launch<D: Send + Sync>(multiple_instances: bool, norisk_toke
 
     let data_directory = launching_parameter.data_path.clone();
 
-    // Copy retrieve and copy mods from manifest
-    clear_mods(&data_directory, &launch_manifest).await.or_else(|e| if multiple_instances { Ok(()) } else { Err(e) })?;
-    retrieve_and_copy_mods(&data_directory, &launch_manifest, &launch_manifest.mods, &additional_mods, &progress).await?;
-    retrieve_and_copy_mods(&data_directory, &launch_manifest, &additional_mods, &additional_mods, &progress).await?;
+    // Only one launch at a time may prepare the game directory of a branch
+    let game_dir = data_directory.join("gameDir").join(&launch_manifest.build.branch);
+    let prepare_lock = DirectoryLock::acquire(&game_dir, &cancellation, || {
+        progress.progress_update(ProgressUpdate::set_label("translation.waitingForOtherLaunch"));
+    }).await?;
+
+    // A running instance of the branch still uses its mods, if they are the same they are reused instead of replaced
+    let prepared_mods = serde_json::to_string(&(&launch_manifest.mods, &additional_mods))?;
+    let prepared_mods_path = game_dir.join(".prepared_mods.json");
+    let is_prepared = fs::read_to_string(&prepared_mods_path).await.map_or(false, |content| content == prepared_mods);
+    if is_prepared && progress.has_other_instance(&launch_manifest.build.branch) {
+        info!("Reusing mods prepared for the running instance of {}", launch_manifest.build.branch);
+    } else {
+        let _ = fs::remove_file(&prepared_mods_path).await;
+
+        // Copy retrieve and copy mods from manifest
+        clear_mods(&data_directory, &launch_manifest).await.or_else(|e| if multiple_instances { Ok(()) } else { Err(e) })?;
+        let repositories = collect_repositories(&launch_manifest, &launching_parameter.custom_repositories);
+        cancellation.run(retrieve_and_copy_mods(&data_directory, &launch_manifest, &repositories, &launch_manifest.mods, &additional_mods, &progress)).await??;
+        cancellation.run(retrieve_and_copy_mods(&data_directory, &launch_manifest, &repositories, &additional_mods, &additional_mods, &progress)).await??;
+
+        fs::write(&prepared_mods_path, prepared_mods).await?;
+    }
 
     info!("Loading version profile...");
-    let manifest_url = match subsystem {
-        LoaderSubsystem::Fabric { manifest, .. } => manifest
-            .replace("{MINECRAFT_VERSION}", &build.mc_version)
-            .replace("{FABRIC_LOADER_VERSION}", &build.fabric_loader_version),
-        LoaderSubsystem::Forge { manifest, .. } => manifest.clone()
+    let mut forge_installer = None;
+    let mut version = match subsystem {
+        LoaderSubsystem::Fabric { manifest, .. } | LoaderSubsystem::Quilt { manifest, .. } => {
+            let manifest_url = build.fill_loader_template(manifest)?;
+            cancellation.run(VersionProfile::download(&data_path.join("child_sub_system.json"), &manifest_url)).await??
+        }
+        LoaderSubsystem::Forge { installer: Some(installer), .. } | LoaderSubsystem::NeoForge { installer: Some(installer), .. } => {
+            // Modern Forge and NeoForge need the installer processors to produce the patched client
+            let installer_url = build.fill_loader_template(installer)?;
+            let installer = cancellation.run(ForgeInstaller::prepare(&installer_url, &data_directory)).await??;
+            let version = installer.version_profile().await?;
+            forge_installer = Some(installer);
+            version
+        }
+        LoaderSubsystem::Forge { manifest, .. } | LoaderSubsystem::NeoForge { manifest, .. } => {
+            let manifest_url = build.fill_loader_template(manifest)?;
+            cancellation.run(VersionProfile::download(&data_path.join("child_sub_system.json"), &manifest_url)).await??
+        }
     };
-    let mut version = VersionProfile::download(&data_path.join("child_sub_system.json"), &manifest_url).await?;
 
     if let Some(inherited_version) = &version.inherits_from {
         let url = mc_version_manifest.versions
@@ -55,14 +91,47 @@ pub(crate) async fn launch<D: Send + Sync>(multiple_instances: bool, norisk_toke
         debug!("Determined {}'s download url to be {}", inherited_version, url);
         info!("Downloading inherited version {}...", inherited_version);
 
-        let parent_version = VersionProfile::download(&data_path.join("parent_sub_system.json"), url).await?;
+        let parent_version = cancellation.run(VersionProfile::download(&data_path.join("parent_sub_system.json"), url)).await??;
 
         version.merge(parent_version)?;
     }
 
     info!("Launching {}...", launch_manifest.build.branch);
 
-    launcher::launch(multiple_instances, norisk_token, uuid, &data_directory, launch_manifest, version, launching_parameter, progress, window, instance_id).await?;
+    let target = LaunchTarget::NoRisk { norisk_token: norisk_token.to_string(), uuid: uuid.to_string(), manifest: launch_manifest };
+    launcher::launch(multiple_instances, target, &data_directory, version, forge_installer, launching_parameter, progress, prepare_lock, window, instance_id).await?;
+    Ok(())
+}
+
+///
+/// Prelaunching an unmodified minecraft version
+///
+pub(crate) async fn launch_vanilla<D: Send + Sync>(multiple_instances: bool, version_id: &str, launching_parameter: LaunchingParameter, progress: LauncherData<D>, window: Arc<Mutex<tauri::Window>>, instance_id: Uuid) -> Result<()> {
+    info!("Loading minecraft version manifest...");
+    let data_path = LAUNCHER_DIRECTORY.data_dir().join("gameDir").join("vanilla").join(version_id).join("nrc_cache");
+    let cancellation = progress.cancellation.clone();
+    fs::create_dir_all(&data_path).await?;
+    let mc_version_manifest = cancellation.run(VersionManifest::download(&data_path)).await??;
+
+    progress.progress_update(ProgressUpdate::set_max());
+    progress.progress_update(ProgressUpdate::SetProgress(0));
+
+    info!("Loading version profile...");
+    let url = mc_version_manifest.versions
+        .iter()
+        .find(|x| x.id == version_id)
+        .map(|x| &x.url)
+        .ok_or_else(|| LauncherError::InvalidVersionProfile(format!("unable to find version manifest {}", version_id)))?;
+    let version = cancellation.run(VersionProfile::download(&data_path.join("version.json"), url)).await??;
+
+    info!("Launching vanilla {}...", version_id);
+
+    let data_directory = launching_parameter.data_path.clone();
+    let target = LaunchTarget::Vanilla { version_id: version_id.to_string() };
+    let prepare_lock = DirectoryLock::acquire(&target.game_dir(&data_directory), &cancellation, || {
+        progress.progress_update(ProgressUpdate::set_label("translation.waitingForOtherLaunch"));
+    }).await?;
+    launcher::launch(multiple_instances, target, &data_directory, version, None, launching_parameter, progress, prepare_lock, window, instance_id).await?;
     Ok(())
 }
 
@@ -83,7 +152,24 @@ pub(crate) async fn clear_mods(data: &Path, manifest: &NoRiskLaunchManifest) ->
     Ok(())
 }
 
-pub async fn retrieve_and_copy_mods(data: &Path, manifest: &NoRiskLaunchManifest, mods: &Vec<LoaderMod>, additional_mods: &Vec<LoaderMod>, progress: &impl ProgressReceiver) -> Result<()> {
+/// Merges the user configured repositories with the repositories of the launch manifest.
+/// If both define a repository with the same name, the manifest entry takes priority.
+pub fn collect_repositories(manifest: &NoRiskLaunchManifest, custom_repositories: &Vec<MavenRepository>) -> HashMap<String, MavenRepository> {
+    let mut repositories: HashMap<String, MavenRepository> = custom_repositories
+        .iter()
+        .map(|repository| (repository.name.clone(), repository.clone()))
+        .collect();
+
+    for (name, url) in &manifest.repositories {
+        if let Some(overridden) = repositories.insert(name.clone(), MavenRepository { name: name.clone(), url: url.clone(), auth: None }) {
+            warn!("Custom repository {} ({}) is overridden by the launch manifest", overridden.name, overridden.url);
+        }
+    }
+
+    repositories
+}
+
+pub async fn retrieve_and_copy_mods(data: &Path, manifest: &NoRiskLaunchManifest, repositories: &HashMap<String, MavenRepository>, mods: &Vec<LoaderMod>, additional_mods: &Vec<LoaderMod>, progress: &impl ProgressReceiver) -> Result<()> {
     let mod_cache_path = data.join("mod_cache");
     let mods_path = data.join("gameDir").join(&manifest.build.branch).join("mods");
 
@@ -94,7 +180,9 @@ pub async fn retrieve_and_copy_mods(data: &Path, manifest: &NoRiskLaunchManifest
 
     // Download and copy mods
     let max = get_max(mods.len());
+    let tracker = ProgressTracker::new(ProgressUpdateSteps::DownloadNoRiskClientMods, mods.len() as u64, 0);
 
+    progress.progress_update(ProgressUpdate::set_label("translation.downloadingNoRiskClientMods"));
     for (mod_idx, current_mod) in mods.iter().enumerate() {
         // Skip mods that are not needed
         if (!current_mod.required && !current_mod.enabled) || additional_mods.iter().any(|m| m.source.get_slug() == current_mod.source.get_slug() && m.source.get_repository() == "PLACEHOLDER") {
@@ -111,35 +199,53 @@ pub async fn retrieve_and_copy_mods(data: &Path, manifest: &NoRiskLaunchManifest
             continue;
         }
 
-        progress.progress_update(ProgressUpdate::set_label(format!("translation.downloadingRecommendedMod&mod%{}", current_mod.name)));
+        progress.progress_state(tracker.set_current_file(&current_mod.name));
 
-        let current_mod_path = mod_cache_path.join(current_mod.source.get_path()?);
+        let mut current_mod_path = mod_cache_path.join(current_mod.source.get_path()?);
 
         // Do we need to download the mod?
-        if !current_mod_path.exists() && current_mod.source.get_repository() != "CUSTOM".to_string() {
-            // Make sure that the parent directory exists
-            fs::create_dir_all(&current_mod_path.parent().unwrap()).await?;
-
-            match &current_mod.source {
+        if current_mod.source.get_repository() != "CUSTOM".to_string() {
+            let download = match &current_mod.source {
                 ModSource::Repository { repository, artifact, url } => {
-                    let download_url = if let Some(url) = url.clone() {
-                        url
+                    if let Some(url) = url.clone() {
+                        // Snapshots with a fixed url can't be resolved, so they are always downloaded again
+                        if !current_mod_path.exists() || current_mod.source.is_snapshot() {
+                            Some((url, None))
+                        } else {
+                            None
+                        }
+                    } else if current_mod_path.exists() && !current_mod.source.is_snapshot() {
+                        None
                     } else {
-                        let repository_url = manifest.repositories.get(repository).ok_or_else(|| LauncherError::InvalidVersionProfile(format!("There is no repository specified with the name {}", repository)))?;
-                        let maven_artifact_path = get_maven_artifact_path(artifact)?;
-                        format!("{}{}", repository_url, maven_artifact_path)
-                    };
+                        let repository = repositories.get(repository).ok_or_else(|| LauncherError::InvalidVersionProfile(format!("There is no repository specified with the name {}", repository)))?;
+                        let coordinate = MavenCoordinate::parse(artifact)?;
+                        // Snapshots are cached by their timestamped version, so each build is only downloaded once
+                        let file_version = coordinate.resolve_version(&repository.base_url(), repository.auth.as_ref()).await?;
+                        current_mod_path = mod_cache_path.join(coordinate.path_for_version(&file_version));
+                        if !current_mod_path.exists() {
+                            Some((coordinate.url_for_version(&repository.base_url(), &file_version), repository.auth.as_ref()))
+                        } else {
+                            None
+                        }
+                    }
+                }
+            };
 
-                    info!("downloading mod {} from {}", artifact, download_url);
+            if let Some((download_url, auth)) = download {
+                // Make sure that the parent directory exists
+                fs::create_dir_all(&current_mod_path.parent().unwrap()).await?;
 
-                    let retrieved_bytes = download_file(&download_url, |a, b| {
-                        progress.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadNoRiskClientMods, get_progress(mod_idx, a, b), max));
-                    }).await?;
+                info!("downloading mod {} from {}", current_mod.name, download_url);
 
-                    fs::write(&current_mod_path, retrieved_bytes).await?;
-                }
+                let downloaded = Cell::new(0);
+                download_file_to_with_auth(&download_url, auth, &current_mod_path, |a, b| {
+                    if let Some(state) = tracker.add_bytes(a.saturating_sub(downloaded.replace(a))) {
+                        progress.progress_update(ProgressUpdate::set_for_step(ProgressUpdateSteps::DownloadNoRiskClientMods, get_progress(mod_idx, a, b), max));
+                        progress.progress_state(Some(state));
+                    }
+                }).await?;
             }
-        } else if !current_mod_path.exists() && current_mod.source.get_repository() == "CUSTOM".to_string() {
+        } else if !current_mod_path.exists() {
             // Broken custom mod path -> ignore
             installed_mods.push(current_mod.clone());
             warn!("Skipping Mod {:?} cuz it's a custom mod with a broken / non existing path!",current_mod);
@@ -150,6 +256,7 @@ pub async fn retrieve_and_copy_mods(data: &Path, manifest: &NoRiskLaunchManifest
         fs::copy(&current_mod_path, mods_path.join(format!("{}.jar", current_mod.name.replace(".jar","")))).await?;
 
         info!("Installed Mod {:?}",current_mod);
+        progress.progress_state(tracker.complete_item(0, &current_mod.name));
         installed_mods.push(current_mod.clone())
     }
 
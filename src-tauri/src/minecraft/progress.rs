@@ -1,17 +1,23 @@
 use core::convert::AsRef;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
 pub enum ProgressUpdateSteps {
     DownloadNoRiskClientMods,
     DownloadJRE,
     DownloadClientJar,
     DownloadLibraries,
+    InstallLoader,
     DownloadAssets,
     DownloadNoRiskAssets,
     VerifyNoRiskAssets,
+    Launching,
+    DownloadAddon,
     DownloadCustomServerJar,
     DownloadCustomServerInstallerJar,
 }
@@ -25,8 +31,9 @@ pub fn get_max(len: usize) -> u64 {
 }
 
 impl ProgressUpdateSteps {
+    /// Amount of steps of a client launch
     fn len() -> usize {
-        12
+        8
     }
 
     fn step_idx(&self) -> usize {
@@ -35,9 +42,12 @@ impl ProgressUpdateSteps {
             ProgressUpdateSteps::DownloadJRE => 1,
             ProgressUpdateSteps::DownloadClientJar => 2,
             ProgressUpdateSteps::DownloadLibraries => 3,
-            ProgressUpdateSteps::DownloadAssets => 4,
-            ProgressUpdateSteps::DownloadNoRiskAssets => 5,
-            ProgressUpdateSteps::VerifyNoRiskAssets => 6,
+            ProgressUpdateSteps::InstallLoader => 4,
+            ProgressUpdateSteps::DownloadAssets => 5,
+            ProgressUpdateSteps::DownloadNoRiskAssets => 6,
+            ProgressUpdateSteps::VerifyNoRiskAssets => 7,
+            ProgressUpdateSteps::Launching => Self::len(),
+            ProgressUpdateSteps::DownloadAddon => 0,
             ProgressUpdateSteps::DownloadCustomServerJar => 1,
             ProgressUpdateSteps::DownloadCustomServerInstallerJar => 2,
         }
@@ -60,13 +70,15 @@ pub enum ProgressUpdate {
     SetProgress(u64),
     #[serde(rename = "label")]
     SetLabel(String),
+    #[serde(rename = "state")]
+    SetState(ProgressState),
 }
 
 const PER_STEP: u64 = 1024;
 
 impl ProgressUpdate {
     pub fn set_for_step(step: ProgressUpdateSteps, progress: u64, max: u64) -> Self {
-        Self::SetProgress(step.step_idx() as u64 * PER_STEP + (progress * PER_STEP / max))
+        Self::SetProgress(step.step_idx() as u64 * PER_STEP + (progress * PER_STEP / max.max(1)))
     }
     pub fn set_to_max() -> Self {
         Self::SetProgress(ProgressUpdateSteps::len() as u64 * PER_STEP)
@@ -81,7 +93,247 @@ impl ProgressUpdate {
     }
 }
 
+///
+/// Structured progress of a single phase
+///
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ProgressState {
+    pub phase: ProgressUpdateSteps,
+    #[serde(rename = "completedItems")]
+    pub completed_items: u64,
+    #[serde(rename = "totalItems")]
+    pub total_items: u64,
+    #[serde(rename = "completedBytes")]
+    pub completed_bytes: u64,
+    #[serde(rename = "totalBytes")]
+    pub total_bytes: u64,
+    #[serde(rename = "bytesPerSecond")]
+    pub bytes_per_second: u64,
+    #[serde(rename = "etaSeconds")]
+    pub eta_seconds: Option<u64>,
+    #[serde(rename = "currentFile")]
+    pub current_file: Option<String>,
+}
+
+/// Lets an update through at most once per interval
+pub struct RateLimiter {
+    interval: Duration,
+    last: Option<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(interval: Duration) -> Self {
+        RateLimiter { interval, last: None }
+    }
+
+    pub fn check(&mut self) -> bool {
+        let now = Instant::now();
+        match self.last {
+            Some(last) if now.duration_since(last) < self.interval => false,
+            _ => {
+                self.last = Some(now);
+                true
+            }
+        }
+    }
+}
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Keeps track of the items and bytes of a phase, calculates speed and ETA and
+/// rate limits the resulting states so the UI doesn't get flooded.
+/// Every method returns the new state only if it should be sent.
+pub struct ProgressTracker {
+    inner: Mutex<ProgressTrackerInner>,
+}
+
+struct ProgressTrackerInner {
+    state: ProgressState,
+    started_at: Instant,
+    last_sample: (Instant, u64),
+    limiter: RateLimiter,
+}
+
+impl ProgressTracker {
+    pub fn new(phase: ProgressUpdateSteps, total_items: u64, total_bytes: u64) -> Self {
+        let now = Instant::now();
+        ProgressTracker {
+            inner: Mutex::new(ProgressTrackerInner {
+                state: ProgressState {
+                    phase,
+                    completed_items: 0,
+                    total_items,
+                    completed_bytes: 0,
+                    total_bytes,
+                    bytes_per_second: 0,
+                    eta_seconds: None,
+                    current_file: None,
+                },
+                started_at: now,
+                last_sample: (now, 0),
+                limiter: RateLimiter::new(PROGRESS_INTERVAL),
+            }),
+        }
+    }
+
+    /// Sets the byte progress of a phase consisting of a single download
+    pub fn set_bytes(&self, completed: u64, total: u64) -> Option<ProgressState> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.state.completed_bytes = completed;
+        inner.state.total_bytes = total;
+        let force = total > 0 && completed >= total;
+        inner.snapshot(force)
+    }
+
+    pub fn add_bytes(&self, bytes: u64) -> Option<ProgressState> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.state.completed_bytes += bytes;
+        inner.snapshot(false)
+    }
+
+    /// Marks an item as done whose bytes were transferred, the last item of a phase is always sent
+    pub fn complete_item<S: AsRef<str>>(&self, bytes: u64, file: S) -> Option<ProgressState> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.state.completed_bytes += bytes;
+        inner.finish_item(file)
+    }
+
+    /// Marks an item as done that was already present. Its bytes are taken out of the total,
+    /// so speed and ETA only cover what actually has to be transferred.
+    pub fn skip_item<S: AsRef<str>>(&self, bytes: u64, file: S) -> Option<ProgressState> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.state.total_bytes = inner.state.total_bytes.saturating_sub(bytes);
+        inner.finish_item(file)
+    }
+
+    pub fn set_current_file<S: AsRef<str>>(&self, file: S) -> Option<ProgressState> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.state.current_file = Some(file.as_ref().to_owned());
+        inner.snapshot(false)
+    }
+
+    /// Current state, ignoring the rate limit
+    pub fn state(&self) -> ProgressState {
+        self.inner.lock().unwrap().snapshot(true).unwrap()
+    }
+}
+
+impl ProgressTrackerInner {
+    fn finish_item<S: AsRef<str>>(&mut self, file: S) -> Option<ProgressState> {
+        self.state.completed_items += 1;
+        self.state.current_file = Some(file.as_ref().to_owned());
+        let force = self.state.completed_items >= self.state.total_items;
+        self.snapshot(force)
+    }
+
+    fn snapshot(&mut self, force: bool) -> Option<ProgressState> {
+        if !self.limiter.check() && !force {
+            return None;
+        }
+
+        // Speed is smoothed over the samples to avoid jumping numbers
+        let now = Instant::now();
+        let (last_time, last_bytes) = self.last_sample;
+        let elapsed = now.duration_since(last_time).as_secs_f64();
+        if elapsed > 0.0 && self.state.completed_bytes >= last_bytes {
+            let current_speed = (self.state.completed_bytes - last_bytes) as f64 / elapsed;
+            self.state.bytes_per_second = if self.state.bytes_per_second == 0 {
+                current_speed as u64
+            } else {
+                (self.state.bytes_per_second as f64 * 0.7 + current_speed * 0.3) as u64
+            };
+        }
+        self.last_sample = (now, self.state.completed_bytes);
+
+        self.state.eta_seconds = if self.state.total_bytes > 0 && self.state.bytes_per_second > 0 {
+            Some(self.state.total_bytes.saturating_sub(self.state.completed_bytes) / self.state.bytes_per_second)
+        } else if self.state.total_items > 0 && self.state.completed_items > 0 {
+            let per_item = now.duration_since(self.started_at).as_secs_f64() / self.state.completed_items as f64;
+            Some((per_item * self.state.total_items.saturating_sub(self.state.completed_items) as f64) as u64)
+        } else {
+            None
+        };
+
+        Some(self.state.clone())
+    }
+}
+
 pub trait ProgressReceiver {
     fn progress_update(&self, update: ProgressUpdate);
+
+    /// Sends the state of a tracker, if the tracker let it through
+    fn progress_state(&self, state: Option<ProgressState>) {
+        if let Some(state) = state {
+            self.progress_update(ProgressUpdate::SetState(state));
+        }
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limiter_lets_one_update_through_per_interval() {
+        let mut limiter = RateLimiter::new(Duration::from_millis(50));
+        assert!(limiter.check());
+        assert!(!limiter.check());
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(limiter.check());
+        assert!(!limiter.check());
+    }
+
+    #[test]
+    fn limiter_without_interval_lets_everything_through() {
+        let mut limiter = RateLimiter::new(Duration::ZERO);
+        assert!(limiter.check());
+        assert!(limiter.check());
+    }
+
+    #[test]
+    fn updates_are_rate_limited_except_the_last_item() {
+        let tracker = ProgressTracker::new(ProgressUpdateSteps::DownloadLibraries, 3, 300);
+        assert!(tracker.complete_item(100, "first.jar").is_some());
+        assert!(tracker.complete_item(100, "second.jar").is_none());
+
+        let state = tracker.complete_item(100, "third.jar").unwrap();
+        assert_eq!(state.completed_items, 3);
+        assert_eq!(state.completed_bytes, 300);
+        assert_eq!(state.current_file.as_deref(), Some("third.jar"));
+    }
+
+    #[test]
+    fn skipped_items_are_taken_out_of_the_total() {
+        let tracker = ProgressTracker::new(ProgressUpdateSteps::DownloadAssets, 2, 300);
+        tracker.skip_item(100, "present.png");
+        let state = tracker.complete_item(200, "missing.png").unwrap();
+        assert_eq!(state.completed_items, 2);
+        assert_eq!(state.completed_bytes, 200);
+        assert_eq!(state.total_bytes, 200);
+        assert_eq!(state.eta_seconds.unwrap_or(0), 0);
+    }
+
+    #[test]
+    fn finished_downloads_are_always_sent() {
+        let tracker = ProgressTracker::new(ProgressUpdateSteps::DownloadClientJar, 1, 0);
+        assert!(tracker.set_bytes(10, 100).is_some());
+        assert!(tracker.set_bytes(50, 100).is_none());
+        assert!(tracker.add_bytes(10).is_none());
+
+        let state = tracker.set_bytes(100, 100).unwrap();
+        assert_eq!(state.completed_bytes, 100);
+        assert_eq!(state.total_bytes, 100);
+        assert_eq!(tracker.state().completed_bytes, 100);
+    }
+
+    #[test]
+    fn states_are_sent_in_the_frontend_format() {
+        let tracker = ProgressTracker::new(ProgressUpdateSteps::DownloadNoRiskAssets, 4, 0);
+        let json = serde_json::to_value(ProgressUpdate::SetState(tracker.state())).unwrap();
+        assert_eq!(json["type"], "state");
+        assert_eq!(json["value"]["phase"], "downloadNoRiskAssets");
+        assert_eq!(json["value"]["totalItems"], 4);
+        assert_eq!(json["value"]["etaSeconds"], serde_json::Value::Null);
+    }
+}
@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+use log::{debug, error, info};
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+use uuid::Uuid;
+
+use crate::error::ErrorKind;
+use crate::minecraft::minecraft_auth::{MinecraftAuthenticationError, MinecraftAuthStore};
+
+/// How often the stored accounts are checked for expiring tokens
+const CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Sent with the `token-refresh-failed` event
+#[derive(Serialize, Clone, Debug)]
+struct TokenRefreshFailed {
+    id: Uuid,
+    username: String,
+    error: String,
+    /// Connection problems are retried with the next check, other errors usually require a new login
+    offline: bool,
+}
+
+/// Refreshes the Microsoft and NoRisk tokens of all stored accounts in the background before they expire,
+/// so launches don't have to wait for a refresh
+pub fn start_token_refresher(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        loop {
+            refresh_all_accounts(&app).await;
+            tokio::time::sleep(CHECK_INTERVAL).await;
+        }
+    });
+}
+
+async fn refresh_all_accounts(app: &AppHandle) {
+    let accounts = match MinecraftAuthStore::init(None).await {
+        Ok(accounts) => accounts,
+        Err(err) => {
+            error!("Failed to load accounts for the token refresh: {:?}", err);
+            return;
+        }
+    };
+
+    for id in accounts.users.keys() {
+        // The refresh can take a while, it works on a detached copy and only merges the tokens of this account afterwards,
+        // so accounts added, removed or switched by the launcher in the meantime aren't overwritten
+        let mut store = match MinecraftAuthStore::init(None).await {
+            Ok(store) => store.detach(),
+            Err(_) => return,
+        };
+        let Some(credentials) = store.users.get(id).cloned() else {
+            continue;
+        };
+        let device_token_id = store.token.as_ref().map(|token| token.id.clone());
+
+        let refreshed = match store.refresh_expiring_tokens(&credentials).await {
+            Ok(Some(refreshed)) => refreshed,
+            Ok(None) => {
+                debug!("Tokens of {:?} are fresh", credentials.username);
+                continue;
+            }
+            Err(err) => {
+                let offline = matches!(&*err.raw, ErrorKind::MinecraftAuthenticationError(
+                    MinecraftAuthenticationError::Request { source, .. }
+                ) if source.is_connect() || source.is_timeout());
+
+                info!("Failed to refresh tokens of {:?}: {:?}", credentials.username, err);
+                app.emit_all("token-refresh-failed", TokenRefreshFailed {
+                    id: credentials.id,
+                    username: credentials.username.clone(),
+                    error: err.to_string(),
+                    offline,
+                }).unwrap_or_default();
+                continue;
+            }
+        };
+
+        // A new device key is generated if the stored one was unusable
+        let device_token = store.token.take().filter(|token| Some(&token.id) != device_token_id.as_ref());
+        let merged = MinecraftAuthStore::update(|current| {
+            // Removed accounts stay removed
+            if let Some(user) = current.users.get_mut(id) {
+                *user = refreshed;
+            }
+            if device_token.is_some() {
+                current.token = device_token;
+            }
+        }).await;
+
+        match merged {
+            Ok(_) => debug!("Refreshed tokens of {:?}", credentials.username),
+            Err(err) => error!("Failed to save refreshed tokens of {:?}: {:?}", credentials.username, err),
+        }
+    }
+}
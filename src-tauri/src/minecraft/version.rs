@@ -7,13 +7,12 @@ use std::{
 };
 use crate::app::app_data::LauncherOptions;
 use crate::minecraft::launcher::LaunchingParameter;
-use crate::minecraft::progress::{ProgressReceiver, ProgressUpdate};
-use crate::utils::{get_maven_artifact_path, md5sum, sha1sum};
+use crate::utils::{md5sum, MavenCoordinate};
 use crate::{
     error,
     error::LauncherError,
     utils::{download_file_untracked, download_private_file_untracked, Architecture},
-    HTTP_CLIENT, LAUNCHER_DIRECTORY,
+    ENDPOINTS, HTTP_CLIENT, LAUNCHER_DIRECTORY, VERIFIED_FILES,
 };
 use anyhow::Result;
 use error::Error;
@@ -23,7 +22,6 @@ use serde::{
     Deserialize, Deserializer, Serialize,
 };
 use std::collections::HashSet;
-use std::sync::Arc;
 use tokio::fs;
 use void::Void;
 
@@ -37,7 +35,7 @@ pub struct VersionManifest {
 impl VersionManifest {
     pub async fn download(app_data: &Path) -> Result<Self, Error> {
         match HTTP_CLIENT
-            .get("https://launchermeta.mojang.com/mc/game/version_manifest.json")
+            .get(format!("{}/mc/game/version_manifest.json", ENDPOINTS.mojang_launcher_meta))
             .send()
             .await
         {
@@ -103,12 +101,21 @@ pub struct VersionProfile {
     #[serde(rename = "mainClass")]
     pub main_class: Option<String>,
     pub logging: Option<Logging>,
+    #[serde(rename = "javaVersion")]
+    pub java_version: Option<JavaVersion>,
     #[serde(rename = "type")]
     pub version_type: String,
     #[serde(flatten)]
     pub arguments: ArgumentDeclaration,
 }
 
+#[derive(Deserialize, Serialize)]
+pub struct JavaVersion {
+    pub component: Option<String>,
+    #[serde(rename = "majorVersion")]
+    pub major_version: u32,
+}
+
 impl VersionProfile {
     pub(crate) fn merge(&mut self, mut parent: VersionProfile) -> Result<()> {
         Self::merge_options(&mut self.asset_index_location, parent.asset_index_location);
@@ -124,6 +131,7 @@ impl VersionProfile {
 
         Self::merge_options(&mut self.main_class, parent.main_class);
         Self::merge_options(&mut self.logging, parent.logging);
+        Self::merge_options(&mut self.java_version, parent.java_version);
 
         match &mut self.arguments {
             ArgumentDeclaration::V14(v14_a) => {
@@ -228,7 +236,7 @@ pub enum ArgumentDeclaration {
 impl ArgumentDeclaration {
     pub(crate) fn add_jvm_args_to_vec(
         &self,
-        norisk_token: &str,
+        norisk_token: Option<&str>,
         command_arguments: &mut Vec<String>,
         parameter: &LaunchingParameter,
         features: &HashSet<String>,
@@ -240,18 +248,23 @@ impl ArgumentDeclaration {
         command_arguments.push("-XX:G1ReservePercent=20".to_string());
         command_arguments.push("-XX:MaxGCPauseMillis=50".to_string());
         command_arguments.push("-XX:G1HeapRegionSize=32M".to_string());
-        command_arguments.push(format!("-Dnorisk.token={}", norisk_token));
-        command_arguments.push(format!("-Dnorisk.experimental={}", parameter.dev_mode));
-        if parameter.force_server.is_some() {
-            info!(
-                "\n\n\nAdded force server arg: {:?}\n\n\n.",
-                parameter.force_server.clone().unwrap()
-            );
-            command_arguments.push(format!(
-                "-Dnorisk.forceServer={}",
-                parameter.force_server.clone().unwrap()
-            ));
+        // Vanilla launches don't get any norisk flags
+        if let Some(norisk_token) = norisk_token {
+            command_arguments.push(format!("-Dnorisk.token={}", norisk_token));
+            command_arguments.push(format!("-Dnorisk.experimental={}", parameter.dev_mode));
+            if parameter.force_server.is_some() {
+                info!(
+                    "\n\n\nAdded force server arg: {:?}\n\n\n.",
+                    parameter.force_server.clone().unwrap()
+                );
+                command_arguments.push(format!(
+                    "-Dnorisk.forceServer={}",
+                    parameter.force_server.clone().unwrap()
+                ));
+            }
         }
+        // Added before the custom args so users can still override them
+        command_arguments.extend(parameter.proxy.java_args());
         for arg in parameter.custom_java_args.split(" ") {
             if arg != " " && arg != "" {
                 info!("Added custom java arg: {:?}", arg);
@@ -494,7 +507,6 @@ impl AssetObject {
     pub async fn download(
         &self,
         assets_objects_folder: impl AsRef<Path>,
-        progress: Arc<impl ProgressReceiver>,
     ) -> Result<bool> {
         let assets_objects_folder = assets_objects_folder.as_ref().to_owned();
         let asset_folder = assets_objects_folder.join(&self.hash[0..2]);
@@ -506,15 +518,11 @@ impl AssetObject {
         let asset_path = asset_folder.join(&self.hash);
 
         return if !asset_path.exists() {
-            progress.progress_update(ProgressUpdate::set_label(format!(
-                "translation.downloadingAssetObject&hash%{}",
-                self.hash
-            )));
-
             info!("Downloading {}", self.hash);
             download_file_untracked(
                 &*format!(
-                    "https://resources.download.minecraft.net/{}/{}",
+                    "{}/{}/{}",
+                    ENDPOINTS.minecraft_resources,
                     &self.hash[0..2],
                     &self.hash
                 ),
@@ -535,7 +543,6 @@ impl AssetObject {
         file_path: String,
         norisk_token: String,
         assets_objects_folder: impl AsRef<Path>,
-        progress: Arc<impl ProgressReceiver>,
     ) -> Result<bool> {
         let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir())
             .await
@@ -583,11 +590,6 @@ impl AssetObject {
         }
 
         return if download {
-            progress.progress_update(ProgressUpdate::set_label(format!(
-                "translation.downloadingNoriskAssetObject&hash%{}",
-                self.hash
-            )));
-
             info!("Downloading {}", self.hash);
             let prod_or_exp = if options.experimental_mode {
                 "exp"
@@ -595,8 +597,8 @@ impl AssetObject {
                 "prod"
             };
             let path = &*format!(
-                "{}/{}/{}/assets/{}",
-                "https://cdn.norisk.gg/branches", prod_or_exp, branch, file_path,
+                "{}/branches/{}/{}/assets/{}",
+                ENDPOINTS.norisk_cdn, prod_or_exp, branch, file_path,
             );
             download_private_file_untracked(path, norisk_token, asset_file_path).await?;
             info!("Downloaded {}", self.hash);
@@ -610,9 +612,8 @@ impl AssetObject {
     pub async fn download_destructing(
         self,
         assets_objects_folder: impl AsRef<Path>,
-        progress: Arc<impl ProgressReceiver>,
     ) -> Result<bool> {
-        return self.download(assets_objects_folder, progress).await;
+        return self.download(assets_objects_folder).await;
     }
 
     pub async fn download_norisk_cosmetic_destructing(
@@ -621,7 +622,6 @@ impl AssetObject {
         file_path: String,
         norisk_token: String,
         assets_objects_folder: impl AsRef<Path>,
-        progress: Arc<impl ProgressReceiver>,
     ) -> Result<bool> {
         return self
             .download_norisk_cosmetic(
@@ -629,7 +629,6 @@ impl AssetObject {
                 file_path,
                 norisk_token,
                 assets_objects_folder,
-                progress,
             )
             .await;
     }
@@ -671,38 +670,30 @@ pub struct Library {
 
 impl Library {
     fn get_identifier(&self) -> String {
-        let parts: Vec<&str> = self.name.split(':').collect();
-        match parts.len() {
-            3 => {
-                // Standard format: group:name:version
-                format!("{}:{}", parts[0], parts[1])
-            }
-            4 => {
-                // Format with classifier: group:name:version:classifier
-                format!("{}:{}:{}", parts[0], parts[1], parts[3])
-            }
-            _ => {
-                // Fallback for unexpected formats - use the whole name
-                self.name.clone()
-            }
+        match MavenCoordinate::parse(&self.name) {
+            Ok(coordinate) => coordinate.identifier(),
+            // Fallback for unexpected formats - use the whole name
+            Err(_) => self.name.clone(),
         }
     }
-    pub fn get_library_download(&self) -> Result<LibraryDownloadInfo> {
+
+    pub async fn get_library_download(&self) -> Result<LibraryDownloadInfo> {
         if let Some(artifact) = self.downloads.as_ref().and_then(|x| x.artifact.as_ref()) {
             return Ok(artifact.into());
         }
 
-        let path = get_maven_artifact_path(&self.name)?;
-        let url = self
+        let coordinate = MavenCoordinate::parse(&self.name)?;
+        let repository = self
             .url
             .as_deref()
-            .unwrap_or("https://libraries.minecraft.net/");
+            .unwrap_or(ENDPOINTS.minecraft_libraries.as_str());
 
+        // Snapshots are stored under their -SNAPSHOT name, but downloaded from the latest timestamped build
         return Ok(LibraryDownloadInfo {
-            url: format!("{}{}", url, path),
+            url: coordinate.resolve_url(repository, None).await?,
             sha1: None,
             size: None,
-            path,
+            path: coordinate.path(),
         });
     }
 }
@@ -774,12 +765,13 @@ impl LibraryDownloadInfo {
             .map_err(|e| anyhow::anyhow!(e))
     }
 
+    /// Returns the path of the library and whether it had to be downloaded
     pub async fn download(
         &self,
         name: String,
         libraries_folder: &Path,
-        progress: Arc<impl ProgressReceiver>,
-    ) -> Result<PathBuf> {
+        full_verify: bool,
+    ) -> Result<(PathBuf, bool)> {
         info!(
             "Downloading library {}, sha1: {:?}, size: {:?}",
             name, &self.sha1, &self.size
@@ -789,6 +781,14 @@ impl LibraryDownloadInfo {
         let path = libraries_folder.to_path_buf();
         let library_path = path.join(&self.path);
 
+        // Libraries without url are generated by the loader installer or bundled with it
+        if self.url.is_empty() {
+            if !library_path.exists() {
+                info!("Library {} has no download url, expecting it to be provided by the loader installer", name);
+            }
+            return Ok((library_path, false));
+        }
+
         // Create parent directories
         fs::create_dir_all(&library_path.parent().unwrap()).await?;
 
@@ -818,19 +818,17 @@ impl LibraryDownloadInfo {
 
         // Check if library already exists
         if library_path.exists() {
-            // Check if sha1 matches
-            let hash = sha1sum(&library_path)?;
-
+            // Check if sha1 matches, unchanged files verified before aren't hashed again
             if let Some(sha1) = &sha1 {
-                if hash == *sha1 {
+                if VERIFIED_FILES.verify_sha1(&library_path, sha1, full_verify).await? {
                     // If sha1 matches, return
                     info!("Library {} already exists and matches sha1.", name);
-                    return Ok(library_path);
+                    return Ok((library_path, false));
                 }
             } else {
                 // If sha1 is not available, assume it matches
                 info!("Library {} already exists.", name);
-                return Ok(library_path);
+                return Ok((library_path, false));
             }
 
             // If sha1 doesn't match, remove the file
@@ -842,23 +840,17 @@ impl LibraryDownloadInfo {
         }
 
         // Download library
-        progress.progress_update(ProgressUpdate::set_label(format!(
-            "translation.downloadingLibrary&library%{}",
-            name
-        )));
-
         download_file_untracked(&self.url, &library_path).await?;
         info!("Downloaded {}", self.url);
 
         // After downloading, check sha1
         if let Some(sha1) = &sha1 {
-            let hash = sha1sum(&library_path)?;
-            if hash != *sha1 {
+            if !VERIFIED_FILES.verify_sha1(&library_path, sha1, true).await? {
                 anyhow::bail!("sha1 of downloaded library {} doesn't match", name);
             }
         }
 
-        Ok(library_path)
+        Ok((library_path, true))
     }
 }
 
@@ -0,0 +1,185 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::info;
+use tokio::time::Instant;
+
+/// How urgently a download is needed. Background downloads (e.g. cape and skin previews) yield
+/// to foreground downloads if the user enabled deprioritizing them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DownloadPriority {
+    Foreground,
+    Background,
+}
+
+/// How long background downloads wait before checking again for running foreground downloads
+const BACKGROUND_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Global bandwidth limit shared by all downloads of the launcher.
+/// Every received chunk takes its size from a token bucket, downloads which run into debt sleep until it is paid off.
+pub struct BandwidthLimiter {
+    bucket: Mutex<TokenBucket>,
+    deprioritize_background: AtomicBool,
+    foreground_downloads: AtomicUsize,
+}
+
+struct TokenBucket {
+    /// Bytes per second, 0 means unlimited
+    limit: u64,
+    available: f64,
+    last_refill: Instant,
+}
+
+impl BandwidthLimiter {
+    pub fn new() -> Self {
+        BandwidthLimiter {
+            bucket: Mutex::new(TokenBucket { limit: 0, available: 0.0, last_refill: Instant::now() }),
+            deprioritize_background: AtomicBool::new(false),
+            foreground_downloads: AtomicUsize::new(0),
+        }
+    }
+
+    /// Applies the limit in bytes per second (0 to disable) and whether background downloads should yield
+    pub fn configure(&self, limit: u64, deprioritize_background: bool) {
+        let mut bucket = self.bucket.lock().unwrap();
+        if bucket.limit != limit {
+            info!("Setting download bandwidth limit to {} bytes/s", limit);
+            bucket.limit = limit;
+            bucket.available = 0.0;
+            bucket.last_refill = Instant::now();
+        }
+        self.deprioritize_background.store(deprioritize_background, Ordering::SeqCst);
+    }
+
+    /// Registers a running download, background downloads wait here until no foreground download is running.
+    /// The download counts as running until the returned guard is dropped.
+    pub async fn start(&self, priority: DownloadPriority) -> DownloadGuard<'_> {
+        match priority {
+            DownloadPriority::Foreground => {
+                self.foreground_downloads.fetch_add(1, Ordering::SeqCst);
+            }
+            DownloadPriority::Background => {
+                while self.deprioritize_background.load(Ordering::SeqCst) && self.foreground_downloads.load(Ordering::SeqCst) > 0 {
+                    tokio::time::sleep(BACKGROUND_POLL_INTERVAL).await;
+                }
+            }
+        }
+
+        DownloadGuard { limiter: self, priority }
+    }
+
+    /// Takes the received bytes from the bucket and waits if the limit was exceeded
+    pub async fn consume(&self, bytes: usize) {
+        let wait = {
+            let mut bucket = self.bucket.lock().unwrap();
+            if bucket.limit == 0 {
+                return;
+            }
+
+            // Refill, but never allow a burst bigger than one second of bandwidth
+            let now = Instant::now();
+            let elapsed = now.duration_since(bucket.last_refill).as_secs_f64();
+            bucket.last_refill = now;
+            bucket.available = (bucket.available + elapsed * bucket.limit as f64).min(bucket.limit as f64);
+            bucket.available -= bytes as f64;
+
+            if bucket.available >= 0.0 {
+                return;
+            }
+            Duration::from_secs_f64(-bucket.available / bucket.limit as f64)
+        };
+
+        tokio::time::sleep(wait).await;
+    }
+}
+
+impl Default for BandwidthLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct DownloadGuard<'a> {
+    limiter: &'a BandwidthLimiter,
+    priority: DownloadPriority,
+}
+
+impl Drop for DownloadGuard<'_> {
+    fn drop(&mut self) {
+        if self.priority == DownloadPriority::Foreground {
+            self.limiter.foreground_downloads.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn downloads_over_the_limit_wait_until_the_debt_is_paid() {
+        let limiter = BandwidthLimiter::new();
+        limiter.configure(1000, false);
+        let start = Instant::now();
+
+        limiter.consume(500).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(500));
+
+        // The half second of waiting refilled what the first chunk took
+        limiter.consume(1000).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(1500));
+
+        // Idle time refills at most one second of bandwidth
+        tokio::time::sleep(Duration::from_secs(10)).await;
+        let idle = Instant::now();
+        limiter.consume(1000).await;
+        assert_eq!(idle.elapsed(), Duration::ZERO);
+        limiter.consume(250).await;
+        assert_eq!(idle.elapsed(), Duration::from_millis(250));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn unlimited_downloads_never_wait() {
+        let limiter = BandwidthLimiter::new();
+        limiter.configure(1000, false);
+        limiter.configure(0, false);
+        let start = Instant::now();
+
+        limiter.consume(1_000_000).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn background_downloads_yield_to_foreground_downloads() {
+        let limiter = BandwidthLimiter::new();
+        limiter.configure(0, true);
+        let start = Instant::now();
+
+        let foreground = limiter.start(DownloadPriority::Foreground).await;
+        let background = async {
+            let _guard = limiter.start(DownloadPriority::Background).await;
+            start.elapsed()
+        };
+        let finish_foreground = async move {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+            drop(foreground);
+        };
+
+        let (waited, ()) = tokio::join!(background, finish_foreground);
+        assert!(waited >= Duration::from_secs(1));
+        assert!(waited <= Duration::from_secs(1) + BACKGROUND_POLL_INTERVAL);
+        assert_eq!(limiter.foreground_downloads.load(Ordering::SeqCst), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn background_downloads_only_yield_if_deprioritized() {
+        let limiter = BandwidthLimiter::new();
+        limiter.configure(0, false);
+        let start = Instant::now();
+
+        let _foreground = limiter.start(DownloadPriority::Foreground).await;
+        let _background = limiter.start(DownloadPriority::Background).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+}
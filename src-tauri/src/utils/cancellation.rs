@@ -0,0 +1,71 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use anyhow::Result;
+use tokio::sync::Notify;
+
+use crate::error::LauncherError;
+
+/// Cooperative cancellation of a task, every part of the task that should stop gets a clone
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    inner: Arc<CancellationInner>,
+}
+
+#[derive(Debug, Default)]
+struct CancellationInner {
+    cancelled: AtomicBool,
+    notify: Notify,
+}
+
+/// Tokens are equal if they are clones of each other
+impl PartialEq for CancellationToken {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        self.inner.notify.notify_waiters();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Completes as soon as the token gets cancelled
+    pub async fn cancelled(&self) {
+        loop {
+            // Register before checking the flag so a cancel in between isn't missed
+            let notified = self.inner.notify.notified();
+            if self.is_cancelled() {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Fails with `LauncherError::Cancelled` if the token got cancelled
+    pub fn check(&self) -> Result<()> {
+        if self.is_cancelled() {
+            return Err(LauncherError::Cancelled.into());
+        }
+        Ok(())
+    }
+
+    /// Runs the future until it completes or the token gets cancelled.
+    /// A cancelled future is dropped, which aborts all downloads it was running.
+    pub async fn run<F: Future>(&self, future: F) -> Result<F::Output> {
+        tokio::select! {
+            output = future => Ok(output),
+            _ = self.cancelled() => Err(LauncherError::Cancelled.into()),
+        }
+    }
+}
@@ -15,6 +15,16 @@ pub fn sha1sum(path: &PathBuf) -> Result<String> {
     Ok(hex_hash)
 }
 
+/// Hashes the file on the blocking thread pool, so large files don't stall the async runtime
+pub async fn sha1sum_blocking(path: PathBuf) -> Result<String> {
+    tokio::task::spawn_blocking(move || sha1sum(&path)).await?
+}
+
+/// Same as [`sha1sum_blocking`], for md5
+pub async fn md5sum_blocking(path: PathBuf) -> Result<String> {
+    tokio::task::spawn_blocking(move || md5sum(&path)).await?
+}
+
 pub fn md5sum(file_path: &Path) -> Result<String> {
     let file = fs::File::open(file_path)?;
 
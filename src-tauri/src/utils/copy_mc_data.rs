@@ -119,7 +119,7 @@ impl McDataHandler {
         Ok(())
     }
     
-    async fn copy_mc_dir_all(src: &PathBuf, dst: &PathBuf, app: &tauri::AppHandle) -> Result<(), String> {
+    pub(crate) async fn copy_mc_dir_all(src: &PathBuf, dst: &PathBuf, app: &tauri::AppHandle) -> Result<(), String> {
         let src_clone = src.clone();
         let dst_clone = dst.clone();
         let app_clone = app.clone();
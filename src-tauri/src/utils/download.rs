@@ -1,10 +1,46 @@
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use tokio::fs;
+use tokio::io::AsyncWriteExt;
 use log::debug;
 use anyhow::Result;
+use reqwest::Response;
 
-use crate::HTTP_CLIENT;
+use crate::app::app_data::MavenRepositoryAuth;
+use crate::utils::DownloadPriority;
+use crate::{BANDWIDTH_LIMITER, HTTP_CLIENT};
+
+/// Removes a partially written file or directory when dropped before `keep` was called,
+/// e.g. because the download writing it got cancelled
+pub struct PartialPath {
+    path: PathBuf,
+    keep: bool,
+}
+
+impl PartialPath {
+    pub fn new(path: impl AsRef<Path>) -> Self {
+        PartialPath { path: path.as_ref().to_path_buf(), keep: false }
+    }
+
+    pub fn keep(mut self) {
+        self.keep = true;
+    }
+}
+
+impl Drop for PartialPath {
+    fn drop(&mut self) {
+        if self.keep {
+            return;
+        }
+
+        debug!("Removing partial {:?}", self.path);
+        let _ = if self.path.is_dir() {
+            std::fs::remove_dir_all(&self.path)
+        } else {
+            std::fs::remove_file(&self.path)
+        };
+    }
+}
 
 /// Download file using HTTP_CLIENT without any progress tracking
 pub async fn download_file_untracked(url: &str, path: impl AsRef<Path>) -> Result<()> {
@@ -12,10 +48,8 @@ pub async fn download_file_untracked(url: &str, path: impl AsRef<Path>) -> Resul
     let response = HTTP_CLIENT.get(url)
         .send().await?
         .error_for_status()?;
-    
-    let content = response.bytes().await?;
-    fs::write(path, content).await?;
-    Ok(())
+
+    write_response(response, &path, DownloadPriority::Foreground, |_, _| {}).await
 }
 
 pub async fn download_private_file_untracked(url: &str, norisk_token: String, path: impl AsRef<Path>) -> Result<()> {
@@ -25,20 +59,67 @@ pub async fn download_private_file_untracked(url: &str, norisk_token: String, pa
         .send().await?
         .error_for_status()?;
 
-    let content = response.bytes().await?;
-    fs::write(path, content).await?;
-    Ok(())
+    write_response(response, &path, DownloadPriority::Foreground, |_, _| {}).await
+}
+
+/// Downloads previews like capes and skins of the launcher screens, background downloads yield to game downloads
+pub async fn download_bytes_with_priority(url: &str, priority: DownloadPriority) -> Result<Vec<u8>> {
+    let response = HTTP_CLIENT.get(url.trim())
+        .send().await?
+        .error_for_status()?;
+
+    read_response(response, priority, |_, _| {}).await
+}
+
+/// Downloads straight into the file, it only appears at `path` once the download is complete
+pub async fn download_file_to<F>(url: &str, path: impl AsRef<Path>, on_progress: F) -> Result<()> where F : Fn(u64, u64) {
+    download_file_to_with_auth(url, None, path, on_progress).await
+}
+
+pub async fn download_file_to_with_auth<F>(url: &str, auth: Option<&MavenRepositoryAuth>, path: impl AsRef<Path>, on_progress: F) -> Result<()> where F : Fn(u64, u64) {
+    debug!("Downloading file {:?} to {:?}", url, path.as_ref());
+
+    let mut request = HTTP_CLIENT.get(url.trim());
+    if let Some(auth) = auth {
+        request = auth.apply(request);
+    }
+
+    let response = request
+        .send().await?
+        .error_for_status()?;
+
+    write_response(response, path.as_ref(), DownloadPriority::Foreground, on_progress).await
 }
 
 pub async fn download_file<F>(url: &str, on_progress: F) -> Result<Vec<u8>> where F : Fn(u64, u64) {
+    download_file_with_auth(url, None, on_progress).await
+}
+
+/// Download file using HTTP_CLIENT with optional repository credentials
+pub async fn download_file_with_auth<F>(url: &str, auth: Option<&MavenRepositoryAuth>, on_progress: F) -> Result<Vec<u8>> where F : Fn(u64, u64) {
     debug!("Downloading file {:?}", url);
 
-    let mut response = HTTP_CLIENT.get(url.trim())
+    let mut request = HTTP_CLIENT.get(url.trim());
+    if let Some(auth) = auth {
+        request = auth.apply(request);
+    }
+
+    let response = request
         .send().await?
         .error_for_status()?;
 
     debug!("Response received from url");
 
+    let output = read_response(response, DownloadPriority::Foreground, on_progress).await?;
+
+    debug!("Downloaded file");
+    Ok(output)
+}
+
+/// Reads the body of a response chunk by chunk while respecting the global bandwidth limit
+async fn read_response<F>(mut response: Response, priority: DownloadPriority, on_progress: F) -> Result<Vec<u8>> where F : Fn(u64, u64) {
+    let _download = BANDWIDTH_LIMITER.start(priority).await;
+
     let max_len = response.content_length().unwrap_or(0);
     let mut output = Vec::with_capacity(max_len as usize);
     let mut curr_len = 0;
@@ -47,6 +128,7 @@ pub async fn download_file<F>(url: &str, on_progress: F) -> Result<Vec<u8>> wher
 
     debug!("Reading data from response chunk...");
     while let Some(data) = response.chunk().await? {
+        BANDWIDTH_LIMITER.consume(data.len()).await;
         output.extend_from_slice(&data);
         curr_len += data.len();
         on_progress(curr_len as u64, max_len);
@@ -55,6 +137,43 @@ pub async fn download_file<F>(url: &str, on_progress: F) -> Result<Vec<u8>> wher
     // Send 100% completion to ensure the progress ends on 100%
     on_progress(max_len, max_len);
 
-    debug!("Downloaded file");
     Ok(output)
-}
\ No newline at end of file
+}
+
+/// Streams the body of a response into `<path>.part` and renames it once it is complete.
+/// If the download fails or its future gets dropped, the part file is removed and `path` stays untouched.
+async fn write_response<F>(mut response: Response, path: &Path, priority: DownloadPriority, on_progress: F) -> Result<()> where F : Fn(u64, u64) {
+    let _download = BANDWIDTH_LIMITER.start(priority).await;
+
+    let part_path = part_path(path);
+    let partial = PartialPath::new(&part_path);
+    let mut file = fs::File::create(&part_path).await?;
+
+    let max_len = response.content_length().unwrap_or(0);
+    let mut curr_len = 0;
+
+    on_progress(0, max_len);
+
+    while let Some(data) = response.chunk().await? {
+        BANDWIDTH_LIMITER.consume(data.len()).await;
+        file.write_all(&data).await?;
+        curr_len += data.len();
+        on_progress(curr_len as u64, max_len);
+    }
+
+    // Waits for the last write, otherwise the rename could happen before all data reached the file
+    file.flush().await?;
+    drop(file);
+
+    fs::rename(&part_path, path).await?;
+    partial.keep();
+
+    on_progress(max_len, max_len);
+    Ok(())
+}
+
+fn part_path(path: &Path) -> PathBuf {
+    let mut file_name = path.file_name().unwrap_or_default().to_os_string();
+    file_name.push(".part");
+    path.with_file_name(file_name)
+}
@@ -49,6 +49,23 @@ pub async fn zip_extract<R>(archive: R, out_dir: &Path) -> Result<()>
     Ok(())
 }
 
+/// Reads a single file from the ZIP archive, returns None if the archive doesn't contain it
+pub async fn zip_read_entry<R>(archive: R, name: &str) -> Result<Option<Vec<u8>>>
+    where R: AsyncRead + AsyncSeek + Unpin {
+    let mut reader = ZipFileReader::new(archive).await?;
+    let index = reader.file().entries().iter().position(|entry| entry.entry().filename() == name);
+
+    match index {
+        Some(index) => {
+            let mut entry_reader = reader.entry(index).await?;
+            let mut content = Vec::new();
+            entry_reader.read_to_end(&mut content).await?;
+            Ok(Some(content))
+        }
+        None => Ok(None)
+    }
+}
+
 pub async fn tar_gz_extract<R>(archive: R, out_dir: &Path) -> Result<()>
     where R: AsyncRead + AsyncSeek + Unpin {
     let mut decoder = GzipDecoder::new(BufReader::new(archive));
@@ -0,0 +1,84 @@
+use std::path::Path;
+use std::sync::RwLock;
+
+use anyhow::{Context, Result};
+use log::{error, info};
+use reqwest::{Certificate, Client, IntoUrl, NoProxy, Proxy, RequestBuilder};
+
+use crate::app::app_data::ProxySettings;
+use crate::APP_USER_AGENT;
+
+/// HTTP client which can be rebuilt at runtime when the network settings change.
+/// Requests that are already running keep using the client they were started with.
+pub struct HttpClient {
+    client: RwLock<Client>,
+}
+
+impl HttpClient {
+    pub fn new(proxy: &ProxySettings, trusted_certificates: &[String]) -> Self {
+        let client = build_client(proxy, trusted_certificates).unwrap_or_else(|err| {
+            error!("Failed to build http client with network settings, falling back to defaults: {:?}", err);
+            build_client(&ProxySettings::default(), &[]).unwrap_or_else(|_| Client::new())
+        });
+
+        HttpClient { client: RwLock::new(client) }
+    }
+
+    /// Replaces the client, the old client is kept if the settings are invalid
+    pub fn rebuild(&self, proxy: &ProxySettings, trusted_certificates: &[String]) -> Result<()> {
+        let client = build_client(proxy, trusted_certificates)?;
+        *self.client.write().unwrap() = client;
+        info!("Rebuilt http client with proxy {:?} and {} additional certificates", proxy.url(), trusted_certificates.len());
+        Ok(())
+    }
+
+    pub fn client(&self) -> Client {
+        self.client.read().unwrap().clone()
+    }
+
+    pub fn get<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.client().get(url)
+    }
+
+    pub fn post<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.client().post(url)
+    }
+
+    pub fn put<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.client().put(url)
+    }
+
+    pub fn delete<U: IntoUrl>(&self, url: U) -> RequestBuilder {
+        self.client().delete(url)
+    }
+}
+
+fn build_client(proxy: &ProxySettings, trusted_certificates: &[String]) -> Result<Client> {
+    let mut builder = reqwest::ClientBuilder::new()
+        .user_agent(APP_USER_AGENT);
+
+    if let Some(url) = proxy.url() {
+        let mut reqwest_proxy = Proxy::all(&url).with_context(|| format!("Invalid proxy {}", url))?;
+        if let Some(username) = proxy.username.as_ref().filter(|username| !username.is_empty()) {
+            reqwest_proxy = reqwest_proxy.basic_auth(username, proxy.password.as_deref().unwrap_or_default());
+        }
+        if !proxy.no_proxy.is_empty() {
+            reqwest_proxy = reqwest_proxy.no_proxy(NoProxy::from_string(&proxy.no_proxy.join(",")));
+        }
+        builder = builder.proxy(reqwest_proxy);
+    }
+
+    for certificate in trusted_certificates {
+        builder = builder.add_root_certificate(load_certificate(Path::new(certificate))?);
+    }
+
+    Ok(builder.build()?)
+}
+
+/// Loads a PEM or DER encoded certificate
+fn load_certificate(path: &Path) -> Result<Certificate> {
+    let content = std::fs::read(path).with_context(|| format!("Unable to read certificate {:?}", path))?;
+    Certificate::from_pem(&content)
+        .or_else(|_| Certificate::from_der(&content))
+        .with_context(|| format!("Invalid certificate {:?}", path))
+}
@@ -0,0 +1,613 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use log::{debug, info, warn};
+use serde::Serialize;
+use serde_json::Value;
+use tokio::fs;
+use uuid::Uuid;
+
+use crate::addons::mod_manager::ModManager;
+use crate::app::api::{LoaderMod, ModSource, NoRiskLaunchManifest};
+use crate::app::app_data::{LauncherOptions, LauncherProfile, LauncherProfiles};
+use crate::app::gui::FileData;
+use crate::app::modrinth_api::{CustomMod, ModrinthApiEndpoints, ModrinthProjectSummary};
+use crate::error::{Error, ErrorKind};
+use crate::minecraft::minecraft_auth::MinecraftAuthStore;
+use crate::utils::{sha1sum_blocking, McDataHandler};
+use crate::LAUNCHER_DIRECTORY;
+
+/// Folders and files of the game directory which are copied into the branch
+const GAME_DATA: [&str; 6] = ["config", "saves", "resourcepacks", "shaderpacks", "options.txt", "servers.dat"];
+
+/// Launchers whose instances can be imported
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportLauncher {
+    /// Prism Launcher and MultiMC share the instance format
+    Prism,
+    CurseForge,
+    Modrinth,
+}
+
+/// What was carried over from the instance and what wasn't
+#[derive(Serialize, Debug)]
+pub struct ImportReport {
+    pub launcher: ImportLauncher,
+    pub profile_id: String,
+    pub profile_name: String,
+    /// Mods found on Modrinth, they are downloaded on launch
+    pub mods: Vec<String>,
+    /// Mods which aren't on Modrinth, their files were copied as custom mods
+    pub custom_mods: Vec<String>,
+    /// Folders and files copied into the branch directory, entries which already existed there are skipped
+    pub copied: Vec<String>,
+    /// Offline accounts added to the launcher
+    pub accounts: Vec<String>,
+    pub warnings: Vec<String>,
+    pub skipped: Vec<SkippedImport>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SkippedImport {
+    pub name: String,
+    pub reason: String,
+}
+
+impl ImportReport {
+    fn skip(&mut self, name: &str, reason: impl Into<String>) {
+        self.skipped.push(SkippedImport { name: name.to_string(), reason: reason.into() });
+    }
+}
+
+/// Instance of another launcher, read from its instance files
+struct ImportSource {
+    launcher: ImportLauncher,
+    name: String,
+    game_dir: PathBuf,
+    mc_version: Option<String>,
+    /// Mod loader like `fabric` or `forge`, none for vanilla instances
+    loader: Option<String>,
+    /// Prism keeps the accounts next to its instances folder
+    accounts_file: Option<PathBuf>,
+}
+
+impl ImportSource {
+    async fn detect(path: &Path) -> Result<Self, Error> {
+        if path.join("instance.cfg").exists() {
+            Self::prism(path).await
+        } else if path.join("minecraftinstance.json").exists() {
+            Self::curseforge(path).await
+        } else if path.join("profile.json").exists() || path.parent().is_some_and(|parent| parent.ends_with("profiles")) {
+            Self::modrinth(path).await
+        } else {
+            Err(ErrorKind::InputError(format!("{:?} is no Prism, MultiMC, CurseForge or Modrinth App instance", path)).as_error())
+        }
+    }
+
+    async fn prism(path: &Path) -> Result<Self, Error> {
+        let config = fs::read_to_string(path.join("instance.cfg")).await?;
+        let name = config.lines()
+            .find_map(|line| line.strip_prefix("name="))
+            .map(str::to_string)
+            .unwrap_or_else(|| dir_name(path));
+
+        let mut mc_version = None;
+        let mut loader = None;
+        if let Ok(content) = fs::read(path.join("mmc-pack.json")).await {
+            let pack = serde_json::from_slice::<Value>(&content)?;
+            for component in pack["components"].as_array().into_iter().flatten() {
+                let version = component["version"].as_str().map(str::to_string);
+                match component["uid"].as_str() {
+                    Some("net.minecraft") => mc_version = version,
+                    Some("net.fabricmc.fabric-loader") => loader = Some("fabric".to_string()),
+                    Some("org.quiltmc.quilt-loader") => loader = Some("quilt".to_string()),
+                    Some("net.minecraftforge") => loader = Some("forge".to_string()),
+                    Some("net.neoforged") => loader = Some("neoforge".to_string()),
+                    _ => {}
+                }
+            }
+        }
+
+        // Prism uses .minecraft, MultiMC and older Prism versions use minecraft
+        let game_dir = [".minecraft", "minecraft"].iter()
+            .map(|dir| path.join(dir))
+            .find(|dir| dir.exists())
+            .unwrap_or_else(|| path.join(".minecraft"));
+
+        // Instances are stored in <launcher>/instances/<instance>
+        let accounts_file = path.parent()
+            .and_then(Path::parent)
+            .map(|launcher_dir| launcher_dir.join("accounts.json"))
+            .filter(|file| file.exists());
+
+        Ok(ImportSource { launcher: ImportLauncher::Prism, name, game_dir, mc_version, loader, accounts_file })
+    }
+
+    async fn curseforge(path: &Path) -> Result<Self, Error> {
+        let instance = serde_json::from_slice::<Value>(&fs::read(path.join("minecraftinstance.json")).await?)?;
+
+        Ok(ImportSource {
+            launcher: ImportLauncher::CurseForge,
+            name: instance["name"].as_str().map(str::to_string).unwrap_or_else(|| dir_name(path)),
+            game_dir: path.to_path_buf(),
+            mc_version: instance["gameVersion"].as_str().map(str::to_string),
+            // The loader is stored like fabric-0.15.11-1.20.1
+            loader: instance["baseModLoader"]["name"].as_str()
+                .and_then(|name| name.split('-').next())
+                .map(str::to_lowercase),
+            accounts_file: None,
+        })
+    }
+
+    /// Newer Modrinth App versions keep the profile metadata in their database, only the folder is known then
+    async fn modrinth(path: &Path) -> Result<Self, Error> {
+        let metadata = match fs::read(path.join("profile.json")).await {
+            Ok(content) => serde_json::from_slice::<Value>(&content)?.get("metadata").cloned().unwrap_or_default(),
+            Err(_) => Value::Null,
+        };
+
+        Ok(ImportSource {
+            launcher: ImportLauncher::Modrinth,
+            name: metadata["name"].as_str().map(str::to_string).unwrap_or_else(|| dir_name(path)),
+            game_dir: path.to_path_buf(),
+            mc_version: metadata["game_version"].as_str().map(str::to_string),
+            loader: metadata["loader"].as_str().filter(|loader| *loader != "vanilla").map(str::to_string),
+            accounts_file: None,
+        })
+    }
+
+    /// Configs and unknown mod files only work if the instance uses a fabric based loader
+    fn is_fabric_compatible(&self) -> bool {
+        self.loader.as_deref().map_or(true, |loader| loader == "fabric" || loader == "quilt")
+    }
+}
+
+pub struct InstanceImporter {}
+
+impl InstanceImporter {
+    /// Imports the instance at the path into a new profile of the branch
+    pub async fn import(path: &Path, branch: &str, app: &tauri::AppHandle) -> Result<ImportReport, Error> {
+        let source = ImportSource::detect(path).await?;
+        info!("Importing {:?} instance {:?} from {:?} into {}", source.launcher, source.name, path, branch);
+
+        let options = LauncherOptions::load(LAUNCHER_DIRECTORY.config_dir()).await.unwrap_or_default();
+        let profile_id = Uuid::new_v4().to_string();
+        let mut report = ImportReport {
+            launcher: source.launcher,
+            profile_id: profile_id.clone(),
+            profile_name: source.name.clone(),
+            mods: Vec::new(),
+            custom_mods: Vec::new(),
+            copied: Vec::new(),
+            accounts: Vec::new(),
+            warnings: Vec::new(),
+            skipped: Vec::new(),
+        };
+
+        // The launch manifest is cached once the branch was launched, without it mod versions can't be checked
+        let manifest = NoRiskLaunchManifest::load(&LAUNCHER_DIRECTORY.data_dir().join("gameDir").join(branch).join("nrc_cache")).await.ok();
+        let branch_version = manifest.as_ref().map(|manifest| manifest.build.mc_version.clone());
+        match (&branch_version, &source.mc_version) {
+            (None, _) => report.warnings.push(format!("{} wasn't launched yet, mod versions couldn't be checked", branch)),
+            (Some(branch_version), Some(mc_version)) if branch_version != mc_version => report.warnings.push(
+                format!("The instance uses Minecraft {} and {} uses {}, worlds are upgraded when they are opened", mc_version, branch, branch_version)
+            ),
+            _ => {}
+        }
+
+        let required_mods = manifest.map(|manifest| manifest.mods).unwrap_or_default();
+        let branch_dir = options.data_path_buf().join("gameDir").join(branch);
+        if let Err(err) = Self::import_profile(&source, branch, &branch_dir, branch_version.as_deref(), &required_mods, &options, app, &mut report).await {
+            warn!("Failed to import {:?}, removing what was imported so far: {:?}", source.name, err);
+            Self::clean_up(&branch_dir, &options, &report).await;
+            return Err(err);
+        }
+
+        if let Some(accounts_file) = &source.accounts_file {
+            if let Err(err) = Self::import_accounts(accounts_file, &mut report).await {
+                warn!("Failed to import accounts of {:?}: {:?}", accounts_file, err);
+                report.skip("accounts.json", format!("Unable to read the accounts: {}", err));
+            }
+        }
+
+        info!("Imported {:?}: {} mods, {} custom mods, {} skipped", report.profile_name, report.mods.len(), report.custom_mods.len(), report.skipped.len());
+        Ok(report)
+    }
+
+    /// Copies the mods and game data and adds the profile using them
+    #[allow(clippy::too_many_arguments)]
+    async fn import_profile(
+        source: &ImportSource,
+        branch: &str,
+        branch_dir: &PathBuf,
+        branch_version: Option<&str>,
+        required_mods: &[LoaderMod],
+        options: &LauncherOptions,
+        app: &tauri::AppHandle,
+        report: &mut ImportReport,
+    ) -> Result<(), Error> {
+        let profile_id = report.profile_id.clone();
+        let mods = Self::import_mods(source, branch_version, required_mods, options, &profile_id, report).await?;
+        Self::import_game_data(source, branch_dir, app, report).await?;
+
+        // Storing the defaults over an unreadable file would remove every profile
+        let mut launcher_profiles = if LAUNCHER_DIRECTORY.config_dir().join("launcher_profiles.json").exists() {
+            LauncherProfiles::load(LAUNCHER_DIRECTORY.config_dir()).await?
+        } else {
+            LauncherProfiles::default()
+        };
+        let (profiles, selected_profiles) = if options.experimental_mode {
+            (&mut launcher_profiles.experimental_profiles, &mut launcher_profiles.selected_experimental_profiles)
+        } else {
+            (&mut launcher_profiles.main_profiles, &mut launcher_profiles.selected_main_profiles)
+        };
+
+        if profiles.iter().any(|profile| profile.name == report.profile_name) {
+            report.profile_name = format!("{} (imported)", report.profile_name);
+        }
+        profiles.push(LauncherProfile {
+            id: profile_id.clone(),
+            branch: branch.to_string(),
+            name: report.profile_name.clone(),
+            mods,
+            account_id: None,
+        });
+        selected_profiles.insert(branch.to_string(), profile_id);
+        launcher_profiles.store(LAUNCHER_DIRECTORY.config_dir()).await?;
+        Ok(())
+    }
+
+    /// Removes the custom mods and game data of a failed import, a retry would skip the copied entries as existing otherwise
+    async fn clean_up(branch_dir: &Path, options: &LauncherOptions, report: &ImportReport) {
+        let custom_mods_dir = ModManager::get_custom_mods_folder(options.clone(), &report.profile_id);
+        let copied = report.copied.iter().map(|name| branch_dir.join(name));
+
+        for path in copied.chain(custom_mods_dir.exists().then_some(custom_mods_dir.clone())) {
+            if let Err(err) = remove_entry(&path).await {
+                warn!("Failed to remove {:?}: {:?}", path, err);
+            }
+        }
+    }
+
+    async fn import_mods(
+        source: &ImportSource,
+        branch_version: Option<&str>,
+        required_mods: &[LoaderMod],
+        options: &LauncherOptions,
+        profile_id: &str,
+        report: &mut ImportReport,
+    ) -> Result<Vec<CustomMod>, Error> {
+        let mods_dir = source.game_dir.join("mods");
+        if !mods_dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        // Disabled mods are imported as disabled mods of the profile
+        let mut files = Vec::new();
+        let mut entries = fs::read_dir(&mods_dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if let Some(name) = file_name.strip_suffix(".disabled").filter(|name| name.ends_with(".jar")) {
+                files.push((entry.path(), name.to_string(), false));
+            } else if file_name.ends_with(".jar") {
+                files.push((entry.path(), file_name, true));
+            }
+        }
+
+        let mut hashes = Vec::new();
+        for (path, _, _) in &files {
+            hashes.push(sha1sum_blocking(path.clone()).await?);
+        }
+
+        // Without Modrinth every mod is imported as custom mod
+        let versions = ModrinthApiEndpoints::get_versions_by_hashes(&hashes).await.unwrap_or_else(|err| {
+            warn!("Failed to look up mods on Modrinth: {:?}", err);
+            HashMap::new()
+        });
+        let project_ids = versions.values().map(|version| version.project_id.clone()).collect::<HashSet<_>>().into_iter().collect::<Vec<_>>();
+        let projects = if project_ids.is_empty() {
+            HashMap::new()
+        } else {
+            ModrinthApiEndpoints::get_projects::<ModrinthProjectSummary>(&project_ids).await
+                .unwrap_or_else(|err| {
+                    warn!("Failed to load Modrinth projects: {:?}", err);
+                    Vec::new()
+                })
+                .into_iter()
+                .map(|project| (project.id.clone(), project))
+                .collect::<HashMap<_, _>>()
+        };
+
+        let mut mods = Vec::new();
+        for ((path, file_name, enabled), hash) in files.into_iter().zip(hashes) {
+            let known = versions.get(&hash).and_then(|version| projects.get(&version.project_id).map(|project| (version, project)));
+
+            let Some((version, project)) = known else {
+                if !source.is_fabric_compatible() {
+                    report.skip(&file_name, format!("Not on Modrinth and made for {}", source.loader.as_deref().unwrap_or_default()));
+                } else if branch_version.is_some() && source.mc_version.is_some() && branch_version != source.mc_version.as_deref() {
+                    report.skip(&file_name, format!("Not on Modrinth and made for Minecraft {}", source.mc_version.as_deref().unwrap_or_default()));
+                } else {
+                    ModManager::save_custom_mod_to_folder(options.clone(), profile_id, FileData {
+                        name: file_name.clone(),
+                        location: path.to_string_lossy().to_string(),
+                    }).await?;
+                    mods.push(custom_mod(profile_id, &file_name, enabled));
+                    report.custom_mods.push(file_name);
+                }
+                continue;
+            };
+
+            if required_mods.iter().any(|required| required.source.get_slug().eq_ignore_ascii_case(&project.slug)) {
+                report.skip(&project.title, "Already included in NoRisk Client");
+                continue;
+            }
+
+            let compatible = version.loaders.iter().any(|loader| loader == "fabric")
+                && branch_version.map_or(true, |branch_version| version.game_versions.iter().any(|v| v == branch_version));
+            if compatible {
+                mods.push(version.to_custom_mod(&project.title, &project.slug, project.icon_url.clone(), Vec::new(), false, enabled));
+                report.mods.push(project.title.clone());
+                continue;
+            }
+
+            // The mod is on Modrinth, but the installed file was made for another version or loader
+            let branch_version = branch_version.unwrap_or_default();
+            let params = format!("?game_versions=[\"{}\"]&loaders=[\"fabric\"]", branch_version);
+            match ModrinthApiEndpoints::get_project_version(&project.slug, &params).await {
+                Ok(versions) if !versions.is_empty() => {
+                    debug!("Using version {} of {} instead of {}", versions[0].version_number, project.slug, version.version_number);
+                    mods.push(versions[0].to_custom_mod(&project.title, &project.slug, project.icon_url.clone(), Vec::new(), false, enabled));
+                    report.mods.push(format!("{} ({})", project.title, versions[0].version_number));
+                }
+                _ => report.skip(&project.title, format!("No Fabric version for Minecraft {}", branch_version)),
+            }
+        }
+
+        Ok(mods)
+    }
+
+    /// Existing worlds, packs and settings of the branch are kept, only entries missing in the branch are copied
+    async fn import_game_data(source: &ImportSource, branch_dir: &PathBuf, app: &tauri::AppHandle, report: &mut ImportReport) -> Result<(), Error> {
+        fs::create_dir_all(branch_dir).await?;
+
+        for name in GAME_DATA {
+            let path = source.game_dir.join(name);
+            if !path.exists() {
+                continue;
+            }
+
+            if name == "config" && !source.is_fabric_compatible() {
+                report.skip(name, format!("Configs of {} mods don't apply to Fabric mods", source.loader.as_deref().unwrap_or_default()));
+                continue;
+            }
+
+            let target = branch_dir.join(name);
+            if !target.exists() {
+                Self::copy_entry(&path, &target, app).await?;
+                report.copied.push(name.to_string());
+            } else if path.is_dir() {
+                let mut entries = fs::read_dir(&path).await?;
+                while let Some(entry) = entries.next_entry().await? {
+                    let entry_name = format!("{}/{}", name, entry.file_name().to_string_lossy());
+                    let entry_target = target.join(entry.file_name());
+                    if entry_target.exists() {
+                        report.skip(&entry_name, "Already exists in the branch directory");
+                    } else {
+                        Self::copy_entry(&entry.path(), &entry_target, app).await?;
+                        report.copied.push(entry_name);
+                    }
+                }
+            } else {
+                report.skip(name, "Already exists in the branch directory");
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn copy_entry(path: &PathBuf, target: &PathBuf, app: &tauri::AppHandle) -> Result<(), Error> {
+        let result = if path.is_dir() {
+            match fs::create_dir_all(target).await {
+                Ok(()) => McDataHandler::copy_mc_dir_all(path, target, app).await
+                    .map_err(|err| ErrorKind::OtherError(err).as_error()),
+                Err(err) => Err(err.into()),
+            }
+        } else {
+            fs::copy(path, target).await.map(|_| ()).map_err(Error::from)
+        };
+
+        // A partial copy isn't part of the report, so it is removed here
+        if result.is_err() && target.exists() {
+            let _ = remove_entry(target).await;
+        }
+        result
+    }
+
+    /// Offline accounts are added again, Microsoft logins are bound to the other launcher and need a new sign in
+    async fn import_accounts(accounts_file: &Path, report: &mut ImportReport) -> Result<(), Error> {
+        let content = serde_json::from_slice::<Value>(&fs::read(accounts_file).await?)?;
+        let mut store = MinecraftAuthStore::init(None).await?;
+
+        for account in content["accounts"].as_array().into_iter().flatten() {
+            let Some(username) = account["profile"]["name"].as_str() else {
+                continue;
+            };
+
+            let already_added = account["profile"]["id"].as_str()
+                .and_then(|id| Uuid::parse_str(id).ok())
+                .is_some_and(|id| store.users.contains_key(&id));
+            if already_added {
+                continue;
+            }
+
+            match account["type"].as_str() {
+                Some("Offline") => match store.add_offline(username).await {
+                    Ok(_) => report.accounts.push(username.to_string()),
+                    Err(err) => report.skip(username, err.to_string()),
+                },
+                _ => report.skip(username, "Microsoft logins of other launchers can't be reused, the account has to be added again"),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn custom_mod(profile_id: &str, file_name: &str, enabled: bool) -> CustomMod {
+    CustomMod {
+        title: file_name.to_string(),
+        image_url: Some(String::new()),
+        value: LoaderMod {
+            required: false,
+            enabled,
+            name: file_name.to_string(),
+            source: ModSource::Repository {
+                repository: "CUSTOM".to_string(),
+                artifact: format!("CUSTOM:{}:{}", profile_id, file_name),
+                url: Some(String::new()),
+            },
+        },
+        dependencies: Vec::new(),
+    }
+}
+
+async fn remove_entry(path: &Path) -> std::io::Result<()> {
+    if path.is_dir() {
+        fs::remove_dir_all(path).await
+    } else {
+        fs::remove_file(path).await
+    }
+}
+
+fn dir_name(path: &Path) -> String {
+    path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_else(|| "Imported".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("nrc-instance-import-{}", Uuid::new_v4()))
+    }
+
+    async fn write(path: &Path, content: &str) {
+        fs::create_dir_all(path.parent().unwrap()).await.unwrap();
+        fs::write(path, content).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn prism_instances_are_read_from_their_config_and_pack() {
+        let launcher_dir = temp_dir();
+        let instance = launcher_dir.join("instances").join("fabric-pack");
+        write(&instance.join("instance.cfg"), "InstanceType=OneSix\nname=Fabric Pack\n").await;
+        write(&instance.join("mmc-pack.json"), r#"{"components": [
+            {"uid": "org.lwjgl3", "version": "3.3.1"},
+            {"uid": "net.minecraft", "version": "1.20.1"},
+            {"uid": "net.fabricmc.fabric-loader", "version": "0.15.11"}
+        ]}"#).await;
+        fs::create_dir_all(instance.join(".minecraft")).await.unwrap();
+        write(&launcher_dir.join("accounts.json"), r#"{"accounts": []}"#).await;
+
+        let source = ImportSource::detect(&instance).await.unwrap();
+        assert_eq!(source.launcher, ImportLauncher::Prism);
+        assert_eq!(source.name, "Fabric Pack");
+        assert_eq!(source.mc_version.as_deref(), Some("1.20.1"));
+        assert_eq!(source.loader.as_deref(), Some("fabric"));
+        assert_eq!(source.game_dir, instance.join(".minecraft"));
+        assert_eq!(source.accounts_file, Some(launcher_dir.join("accounts.json")));
+        assert!(source.is_fabric_compatible());
+
+        fs::remove_dir_all(&launcher_dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn multimc_instances_use_the_minecraft_folder() {
+        let launcher_dir = temp_dir();
+        let instance = launcher_dir.join("instances").join("forge-pack");
+        write(&instance.join("instance.cfg"), "InstanceType=OneSix\n").await;
+        write(&instance.join("mmc-pack.json"), r#"{"components": [
+            {"uid": "net.minecraft", "version": "1.19.2"},
+            {"uid": "net.minecraftforge", "version": "43.3.0"}
+        ]}"#).await;
+        fs::create_dir_all(instance.join("minecraft")).await.unwrap();
+
+        let source = ImportSource::detect(&instance).await.unwrap();
+        assert_eq!(source.name, "forge-pack");
+        assert_eq!(source.loader.as_deref(), Some("forge"));
+        assert_eq!(source.game_dir, instance.join("minecraft"));
+        assert_eq!(source.accounts_file, None);
+        assert!(!source.is_fabric_compatible());
+
+        fs::remove_dir_all(&launcher_dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn curseforge_loaders_are_parsed_from_the_base_mod_loader() {
+        let instance = temp_dir();
+        write(&instance.join("minecraftinstance.json"), r#"{
+            "name": "CurseForge Pack",
+            "gameVersion": "1.20.1",
+            "baseModLoader": {"name": "fabric-0.15.11-1.20.1"}
+        }"#).await;
+
+        let source = ImportSource::detect(&instance).await.unwrap();
+        assert_eq!(source.launcher, ImportLauncher::CurseForge);
+        assert_eq!(source.name, "CurseForge Pack");
+        assert_eq!(source.mc_version.as_deref(), Some("1.20.1"));
+        assert_eq!(source.loader.as_deref(), Some("fabric"));
+        assert_eq!(source.game_dir, instance);
+
+        write(&instance.join("minecraftinstance.json"), r#"{"gameVersion": "1.20.1", "baseModLoader": {"name": "Forge-47.2.0"}}"#).await;
+        let source = ImportSource::detect(&instance).await.unwrap();
+        assert_eq!(source.name, dir_name(&instance));
+        assert_eq!(source.loader.as_deref(), Some("forge"));
+
+        write(&instance.join("minecraftinstance.json"), r#"{"gameVersion": "1.20.1", "baseModLoader": null}"#).await;
+        let source = ImportSource::detect(&instance).await.unwrap();
+        assert_eq!(source.loader, None);
+
+        fs::remove_dir_all(&instance).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn modrinth_profiles_are_read_from_their_metadata() {
+        let instance = temp_dir();
+        write(&instance.join("profile.json"), r#"{"metadata": {"name": "Modrinth Pack", "game_version": "1.20.4", "loader": "vanilla"}}"#).await;
+
+        let source = ImportSource::detect(&instance).await.unwrap();
+        assert_eq!(source.launcher, ImportLauncher::Modrinth);
+        assert_eq!(source.name, "Modrinth Pack");
+        assert_eq!(source.mc_version.as_deref(), Some("1.20.4"));
+        assert_eq!(source.loader, None);
+
+        fs::remove_dir_all(&instance).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn modrinth_profiles_without_metadata_are_detected_by_their_folder() {
+        let app_dir = temp_dir();
+        let instance = app_dir.join("profiles").join("Quilt Pack");
+        fs::create_dir_all(&instance).await.unwrap();
+
+        let source = ImportSource::detect(&instance).await.unwrap();
+        assert_eq!(source.launcher, ImportLauncher::Modrinth);
+        assert_eq!(source.name, "Quilt Pack");
+        assert_eq!(source.mc_version, None);
+        assert_eq!(source.loader, None);
+        assert_eq!(source.game_dir, instance);
+
+        fs::remove_dir_all(&app_dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn unknown_folders_are_rejected() {
+        let dir = temp_dir();
+        fs::create_dir_all(dir.join("mods")).await.unwrap();
+
+        assert!(ImportSource::detect(&dir).await.is_err());
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
@@ -0,0 +1,53 @@
+use std::fs::{File, OpenOptions};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::Result;
+use fs2::FileExt;
+use log::{debug, info};
+
+use crate::utils::CancellationToken;
+
+/// How often a waiting launch checks if the lock got released
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Advisory lock of a directory, held while a launch prepares it.
+/// The lock is released when dropped or when the process exits.
+pub struct DirectoryLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl DirectoryLock {
+    /// Waits until the lock of the directory is free, `on_wait` is called once if it is currently held by another launch
+    pub async fn acquire<F: FnOnce()>(dir: &Path, cancellation: &CancellationToken, on_wait: F) -> Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(".launch.lock");
+        let file = OpenOptions::new().read(true).write(true).create(true).open(&path)?;
+
+        let mut on_wait = Some(on_wait);
+        loop {
+            match file.try_lock_exclusive() {
+                Ok(()) => break,
+                Err(err) if err.kind() == fs2::lock_contended_error().kind() => {
+                    if let Some(on_wait) = on_wait.take() {
+                        info!("{:?} is locked by another launch, waiting...", dir);
+                        on_wait();
+                    }
+                    cancellation.run(tokio::time::sleep(LOCK_POLL_INTERVAL)).await?;
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        debug!("Acquired lock {:?}", path);
+        Ok(DirectoryLock { file, path })
+    }
+}
+
+impl Drop for DirectoryLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+        debug!("Released lock {:?}", self.path);
+    }
+}
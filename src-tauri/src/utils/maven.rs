@@ -1,14 +1,266 @@
-use crate::error::LauncherError;
+use std::fmt;
+use std::str::FromStr;
+
 use anyhow::Result;
+use log::debug;
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::app::app_data::MavenRepositoryAuth;
+use crate::error::LauncherError;
+use crate::HTTP_CLIENT;
+
+static SNAPSHOT_VERSION_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<snapshotVersion>(.*?)</snapshotVersion>").unwrap());
+static CLASSIFIER_REGEX: Lazy<Regex> = Lazy::new(|| xml_value_regex("classifier"));
+static EXTENSION_REGEX: Lazy<Regex> = Lazy::new(|| xml_value_regex("extension"));
+static VALUE_REGEX: Lazy<Regex> = Lazy::new(|| xml_value_regex("value"));
+static TIMESTAMP_REGEX: Lazy<Regex> = Lazy::new(|| xml_value_regex("timestamp"));
+static BUILD_NUMBER_REGEX: Lazy<Regex> = Lazy::new(|| xml_value_regex("buildNumber"));
+
+/// Maven coordinate in the form `group:artifact:version[:classifier][@extension]`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MavenCoordinate {
+    pub group: String,
+    pub artifact: String,
+    pub version: String,
+    pub classifier: Option<String>,
+    pub extension: String,
+}
+
+impl MavenCoordinate {
+    pub fn parse(coordinate: &str) -> Result<Self> {
+        let invalid = || LauncherError::InvalidVersionProfile(format!("Invalid artifact name: {}", coordinate));
+
+        let (coordinate_part, extension) = match coordinate.split_once('@') {
+            Some((coordinate_part, extension)) if !extension.is_empty() => (coordinate_part, extension.to_string()),
+            Some(_) => return Err(invalid().into()),
+            None => (coordinate, "jar".to_string()),
+        };
+
+        let split = coordinate_part.split(':').collect::<Vec<_>>();
+        if split.len() < 3 || split.len() > 4 || split.iter().any(|part| part.is_empty()) {
+            return Err(invalid().into());
+        }
+
+        Ok(MavenCoordinate {
+            group: split[0].to_string(),
+            artifact: split[1].to_string(),
+            version: split[2].to_string(),
+            classifier: split.get(3).map(|classifier| classifier.to_string()),
+            extension,
+        })
+    }
+
+    /// Identifier without version, used to tell apart different libraries of the same artifact
+    pub fn identifier(&self) -> String {
+        match &self.classifier {
+            Some(classifier) => format!("{}:{}:{}", self.group, self.artifact, classifier),
+            None => format!("{}:{}", self.group, self.artifact),
+        }
+    }
+
+    pub fn is_snapshot(&self) -> bool {
+        self.version.ends_with("-SNAPSHOT")
+    }
+
+    /// Directory of the version inside a repository, e.g. `net/fabricmc/fabric-loader/0.15.0`
+    pub fn directory(&self) -> String {
+        format!("{}/{}/{}", self.group.replace('.', "/"), self.artifact, self.version)
+    }
+
+    /// File name for the given file version. For releases this is the same as the version,
+    /// for snapshots it can be the timestamped version of a specific build.
+    pub fn file_name_for_version(&self, file_version: &str) -> String {
+        match &self.classifier {
+            Some(classifier) => format!("{}-{}-{}.{}", self.artifact, file_version, classifier, self.extension),
+            None => format!("{}-{}.{}", self.artifact, file_version, self.extension),
+        }
+    }
+
+    pub fn file_name(&self) -> String {
+        self.file_name_for_version(&self.version)
+    }
+
+    /// Path of the artifact relative to the repository root
+    pub fn path(&self) -> String {
+        format!("{}/{}", self.directory(), self.file_name())
+    }
+
+    pub fn path_for_version(&self, file_version: &str) -> String {
+        format!("{}/{}", self.directory(), self.file_name_for_version(file_version))
+    }
+
+    /// Url of the artifact in the given repository
+    pub fn url(&self, repository_url: &str) -> String {
+        self.url_for_version(repository_url, &self.version)
+    }
+
+    pub fn url_for_version(&self, repository_url: &str, file_version: &str) -> String {
+        format!("{}/{}", repository_url.trim_end_matches('/'), self.path_for_version(file_version))
+    }
+
+    /// Resolves the file version of the artifact. Release versions are returned as is,
+    /// snapshot versions are looked up in the maven-metadata.xml of the repository.
+    pub async fn resolve_version(&self, repository_url: &str, auth: Option<&MavenRepositoryAuth>) -> Result<String> {
+        if !self.is_snapshot() {
+            return Ok(self.version.clone());
+        }
+
+        let metadata_url = format!("{}/{}/maven-metadata.xml", repository_url.trim_end_matches('/'), self.directory());
+        debug!("Resolving snapshot {} using {}", self, metadata_url);
+
+        let mut request = HTTP_CLIENT.get(&metadata_url);
+        if let Some(auth) = auth {
+            request = auth.apply(request);
+        }
+        let metadata = request.send().await?
+            .error_for_status()?
+            .text().await?;
+
+        self.resolve_version_from_metadata(&metadata)
+            .ok_or_else(|| LauncherError::InvalidVersionProfile(format!("Unable to resolve snapshot version of {} from {}", self, metadata_url)).into())
+    }
+
+    /// Resolves the url of the artifact, including the timestamped file name for snapshots
+    pub async fn resolve_url(&self, repository_url: &str, auth: Option<&MavenRepositoryAuth>) -> Result<String> {
+        let file_version = self.resolve_version(repository_url, auth).await?;
+        Ok(self.url_for_version(repository_url, &file_version))
+    }
+
+    fn resolve_version_from_metadata(&self, metadata: &str) -> Option<String> {
+        // Maven 3 metadata lists every file of the snapshot with its own version
+        for snapshot_version in SNAPSHOT_VERSION_REGEX.captures_iter(metadata) {
+            let entry = &snapshot_version[1];
+            let classifier = xml_value(entry, &CLASSIFIER_REGEX);
+            let extension = xml_value(entry, &EXTENSION_REGEX);
+
+            if classifier == self.classifier && extension.as_deref() == Some(self.extension.as_str()) {
+                if let Some(value) = xml_value(entry, &VALUE_REGEX) {
+                    return Some(value);
+                }
+            }
+        }
+
+        // Older metadata only contains the timestamp and build number of the latest snapshot
+        let timestamp = xml_value(metadata, &TIMESTAMP_REGEX)?;
+        let build_number = xml_value(metadata, &BUILD_NUMBER_REGEX)?;
+        Some(format!("{}-{}-{}", self.version.trim_end_matches("-SNAPSHOT"), timestamp, build_number))
+    }
+}
+
+impl FromStr for MavenCoordinate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        MavenCoordinate::parse(s)
+    }
+}
+
+impl fmt::Display for MavenCoordinate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.group, self.artifact, self.version)?;
+        if let Some(classifier) = &self.classifier {
+            write!(f, ":{}", classifier)?;
+        }
+        if self.extension != "jar" {
+            write!(f, "@{}", self.extension)?;
+        }
+        Ok(())
+    }
+}
+
+fn xml_value_regex(tag: &str) -> Regex {
+    Regex::new(&format!(r"<{tag}>\s*([^<]*?)\s*</{tag}>", tag = tag)).unwrap()
+}
+
+fn xml_value(xml: &str, regex: &Regex) -> Option<String> {
+    regex.captures(xml).map(|captures| captures[1].to_string())
+}
 
 pub fn get_maven_artifact_path(artifact_id: &String) -> Result<String> {
     let split = artifact_id.split(':').collect::<Vec<_>>();
 
+    // Custom mods are stored as CUSTOM:<profile>:<file> and are not real maven artifacts
     if split.len() == 3 && split[0] == "CUSTOM" {
         Ok(artifact_id.replace(":", "/"))
-    } else if split.len() == 3 {
-        Ok(format!("{}/{name}/{ver}/{name}-{ver}.jar", split[0].replace('.', "/"), name = split[1], ver = split[2]))
     } else {
-        return Err(LauncherError::InvalidVersionProfile(format!("Invalid artifact name: {}", artifact_id)).into());
+        Ok(MavenCoordinate::parse(artifact_id)?.path())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_coordinates() {
+        let coordinate = MavenCoordinate::parse("net.fabricmc:fabric-loader:0.15.0").unwrap();
+        assert_eq!(coordinate.group, "net.fabricmc");
+        assert_eq!(coordinate.artifact, "fabric-loader");
+        assert_eq!(coordinate.version, "0.15.0");
+        assert_eq!(coordinate.classifier, None);
+        assert_eq!(coordinate.extension, "jar");
+
+        let coordinate = MavenCoordinate::parse("net.minecraftforge:forge:1.20.1-47.2.0:installer@zip").unwrap();
+        assert_eq!(coordinate.classifier.as_deref(), Some("installer"));
+        assert_eq!(coordinate.extension, "zip");
+        assert_eq!(coordinate.to_string(), "net.minecraftforge:forge:1.20.1-47.2.0:installer@zip");
+        assert_eq!(coordinate.identifier(), "net.minecraftforge:forge:installer");
+    }
+
+    #[test]
+    fn rejects_invalid_coordinates() {
+        for coordinate in ["", "group:artifact", "group::version", "a:b:c:d:e", "group:artifact:version@"] {
+            assert!(MavenCoordinate::parse(coordinate).is_err(), "{} should be invalid", coordinate);
+        }
+    }
+
+    #[test]
+    fn builds_paths_and_urls() {
+        let coordinate = MavenCoordinate::parse("gg.norisk:nrc-ui:1.0.0:dev").unwrap();
+        assert_eq!(coordinate.path(), "gg/norisk/nrc-ui/1.0.0/nrc-ui-1.0.0-dev.jar");
+        assert_eq!(coordinate.url("https://maven.norisk.gg/repository/norisk-production/"), "https://maven.norisk.gg/repository/norisk-production/gg/norisk/nrc-ui/1.0.0/nrc-ui-1.0.0-dev.jar");
+
+        let snapshot = MavenCoordinate::parse("gg.norisk:nrc-ui:1.1.0-SNAPSHOT").unwrap();
+        assert_eq!(snapshot.url_for_version("https://maven.example.com", "1.1.0-20240102.030405-7"), "https://maven.example.com/gg/norisk/nrc-ui/1.1.0-SNAPSHOT/nrc-ui-1.1.0-20240102.030405-7.jar");
+        assert_eq!(get_maven_artifact_path(&"CUSTOM:profile:mod.jar".to_string()).unwrap(), "CUSTOM/profile/mod.jar");
+    }
+
+    #[test]
+    fn resolves_snapshot_versions() {
+        let metadata = r#"<metadata>
+  <versioning>
+    <snapshot>
+      <timestamp>20240102.030405</timestamp>
+      <buildNumber>7</buildNumber>
+    </snapshot>
+    <snapshotVersions>
+      <snapshotVersion>
+        <classifier>sources</classifier>
+        <extension>jar</extension>
+        <value>1.1.0-20240102.030405-7</value>
+      </snapshotVersion>
+      <snapshotVersion>
+        <extension>jar</extension>
+        <value>1.1.0-20240101.120000-6</value>
+      </snapshotVersion>
+    </snapshotVersions>
+  </versioning>
+</metadata>"#;
+
+        let coordinate = MavenCoordinate::parse("gg.norisk:nrc-ui:1.1.0-SNAPSHOT").unwrap();
+        assert!(coordinate.is_snapshot());
+        assert_eq!(coordinate.resolve_version_from_metadata(metadata).as_deref(), Some("1.1.0-20240101.120000-6"));
+
+        let sources = MavenCoordinate::parse("gg.norisk:nrc-ui:1.1.0-SNAPSHOT:sources").unwrap();
+        assert_eq!(sources.resolve_version_from_metadata(metadata).as_deref(), Some("1.1.0-20240102.030405-7"));
+    }
+
+    #[test]
+    fn resolves_legacy_snapshot_metadata() {
+        let metadata = "<metadata><versioning><snapshot><timestamp> 20230815.101010 </timestamp><buildNumber>12</buildNumber></snapshot></versioning></metadata>";
+        let coordinate = MavenCoordinate::parse("gg.norisk:nrc-ui:2.0.0-SNAPSHOT").unwrap();
+        assert_eq!(coordinate.resolve_version_from_metadata(metadata).as_deref(), Some("2.0.0-20230815.101010-12"));
+        assert_eq!(coordinate.resolve_version_from_metadata("<metadata></metadata>"), None);
     }
-}
\ No newline at end of file
+}
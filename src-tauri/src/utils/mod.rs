@@ -4,6 +4,13 @@ mod download;
 mod maven;
 mod checksum;
 mod copy_mc_data;
+mod cancellation;
+mod bandwidth;
+mod http;
+mod lock;
+mod verified_files;
+mod secret_store;
+mod instance_import;
 
 pub use {
     sys::*,
@@ -12,5 +19,12 @@ pub use {
     maven::*,
     checksum::*,
     copy_mc_data::*,
+    cancellation::*,
+    bandwidth::*,
+    http::*,
+    lock::*,
+    verified_files::*,
+    secret_store::*,
+    instance_import::*,
 };
 
@@ -0,0 +1,190 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+use anyhow::{anyhow, bail, Context, Result};
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use log::{info, warn};
+use machineid_rs::{Encryption, HWIDComponent, IdBuilder};
+use once_cell::sync::OnceCell;
+use sha2::{Digest, Sha256};
+
+const FORMAT_VERSION: u8 = 1;
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+const HEADER_LENGTH: usize = 2 + SALT_LENGTH + NONCE_LENGTH;
+
+const KEYRING_SERVICE: &str = "NoRiskClient";
+const KEYRING_USER: &str = "secret-store-key";
+
+/// The key of the OS keyring is only read once, some keyrings ask the user for every access
+static KEYRING_KEY: OnceCell<[u8; 32]> = OnceCell::new();
+
+/// Where the key of an encrypted file comes from, it is stored in the file header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum KeySource {
+    /// Random key stored in the OS keyring
+    Keyring = 1,
+    /// Key derived from the machine id and the salt of the file, for systems without a keyring like headless linux
+    Machine = 2,
+}
+
+impl KeySource {
+    fn from_byte(byte: u8) -> Result<Self> {
+        match byte {
+            1 => Ok(KeySource::Keyring),
+            2 => Ok(KeySource::Machine),
+            _ => bail!("Unknown secret key source {}", byte),
+        }
+    }
+
+    fn key(self, salt: &[u8]) -> Result<[u8; 32]> {
+        match self {
+            KeySource::Keyring => KEYRING_KEY.get_or_try_init(keyring_key).copied(),
+            KeySource::Machine => machine_key(salt),
+        }
+    }
+}
+
+/// File which is only ever written AES-256-GCM encrypted.
+/// The key is kept in the OS keyring, if no keyring is available it is derived from the machine id instead.
+pub struct SecretStore {
+    path: PathBuf,
+}
+
+impl SecretStore {
+    pub fn new(path: PathBuf) -> Self {
+        SecretStore { path }
+    }
+
+    /// Moves an unreadable file to `<name>.bak`, so the next write doesn't replace the only copy
+    pub fn backup(&self) -> Result<PathBuf> {
+        let mut backup_path = self.path.clone().into_os_string();
+        backup_path.push(".bak");
+        let backup_path = PathBuf::from(backup_path);
+
+        fs::rename(&self.path, &backup_path)
+            .with_context(|| format!("Unable to move {:?} to {:?}", self.path, backup_path))?;
+        Ok(backup_path)
+    }
+
+    /// Reads and decrypts the file, none if it doesn't exist
+    pub fn read(&self) -> Result<Option<Vec<u8>>> {
+        let content = match fs::read(&self.path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(err).with_context(|| format!("Unable to read {:?}", self.path)),
+        };
+
+        if content.len() < HEADER_LENGTH || content[0] != FORMAT_VERSION {
+            bail!("{:?} is not a valid secret file", self.path);
+        }
+
+        let source = KeySource::from_byte(content[1])?;
+        let salt = &content[2..2 + SALT_LENGTH];
+        let nonce = Nonce::from_slice(&content[2 + SALT_LENGTH..HEADER_LENGTH]);
+
+        let cipher = Aes256Gcm::new_from_slice(&source.key(salt)?).map_err(|_| anyhow!("Invalid secret key length"))?;
+        let data = cipher.decrypt(nonce, &content[HEADER_LENGTH..])
+            .map_err(|_| anyhow!("Unable to decrypt {:?}, the key of the {:?} source doesn't match", self.path, source))?;
+
+        Ok(Some(data))
+    }
+
+    /// Encrypts and writes the data, the previous file is only replaced once the new one was written completely
+    pub fn write(&self, data: &[u8]) -> Result<()> {
+        let salt: [u8; SALT_LENGTH] = rand::random();
+        let nonce: [u8; NONCE_LENGTH] = rand::random();
+
+        let (source, key) = match KeySource::Keyring.key(&salt) {
+            Ok(key) => (KeySource::Keyring, key),
+            Err(err) => {
+                warn!("OS keyring is not available, deriving the secret key from the machine id: {:?}", err);
+                (KeySource::Machine, KeySource::Machine.key(&salt)?)
+            }
+        };
+
+        let cipher = Aes256Gcm::new_from_slice(&key).map_err(|_| anyhow!("Invalid secret key length"))?;
+        let encrypted = cipher.encrypt(Nonce::from_slice(&nonce), data)
+            .map_err(|_| anyhow!("Unable to encrypt {:?}", self.path))?;
+
+        let mut content = Vec::with_capacity(HEADER_LENGTH + encrypted.len());
+        content.push(FORMAT_VERSION);
+        content.push(source as u8);
+        content.extend_from_slice(&salt);
+        content.extend_from_slice(&nonce);
+        content.extend_from_slice(&encrypted);
+
+        write_private(&self.path, &content)
+    }
+}
+
+/// Reads the key from the OS keyring or creates it if there is none yet
+fn keyring_key() -> Result<[u8; 32]> {
+    let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)?;
+
+    match entry.get_password() {
+        Ok(encoded) => BASE64_STANDARD.decode(encoded)?
+            .try_into()
+            .map_err(|_| anyhow!("Secret key in the OS keyring has an invalid length")),
+        Err(keyring::Error::NoEntry) => {
+            let key: [u8; 32] = rand::random();
+            entry.set_password(&BASE64_STANDARD.encode(key))?;
+            info!("Created secret key in the OS keyring");
+            Ok(key)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn machine_key(salt: &[u8]) -> Result<[u8; 32]> {
+    let machine_id = IdBuilder::new(Encryption::SHA256)
+        .add_component(HWIDComponent::SystemID)
+        .build("NRC-SecretStore")
+        .map_err(|err| anyhow!("Unable to read machine id: {:?}", err))?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(machine_id.as_bytes());
+    hasher.update(salt);
+    Ok(hasher.finalize().into())
+}
+
+/// Writes the file next to the target first and renames it, only the current user can read it on unix
+fn write_private(path: &Path, content: &[u8]) -> Result<()> {
+    let temp_path = path.with_extension("tmp");
+    fs::write(&temp_path, content)?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&temp_path, fs::Permissions::from_mode(0o600))?;
+    }
+
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backup_moves_the_store_aside() {
+        let dir = std::env::temp_dir().join(format!("nrc-secret-store-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("accounts.enc");
+        fs::write(&path, b"not encrypted").unwrap();
+
+        let store = SecretStore::new(path.clone());
+        assert!(store.read().is_err());
+
+        let backup_path = store.backup().unwrap();
+        assert_eq!(backup_path, dir.join("accounts.enc.bak"));
+        assert_eq!(fs::read(&backup_path).unwrap(), b"not encrypted");
+        assert!(store.read().unwrap().is_none());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+use anyhow::Result;
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+
+use crate::utils::sha1sum_blocking;
+
+/// Index of files whose SHA1 was already verified, keyed by their path.
+/// A file is only hashed again if its size or modification time changed since it was verified.
+pub struct VerifiedFileIndex {
+    path: PathBuf,
+    files: Mutex<HashMap<PathBuf, VerifiedFile>>,
+    store_lock: tokio::sync::Mutex<()>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct VerifiedFile {
+    size: u64,
+    /// Modification time in nanoseconds since the unix epoch
+    modified: u128,
+    sha1: String,
+}
+
+impl VerifiedFile {
+    async fn metadata(path: &Path) -> Result<(u64, u128)> {
+        let metadata = fs::metadata(path).await?;
+        let modified = metadata.modified()?.duration_since(UNIX_EPOCH)?.as_nanos();
+        Ok((metadata.len(), modified))
+    }
+}
+
+impl VerifiedFileIndex {
+    /// Loads the index, an unreadable index is treated as empty
+    pub fn load(path: PathBuf) -> Self {
+        let files = match std::fs::read(&path) {
+            Ok(content) => serde_json::from_slice(&content).unwrap_or_else(|err| {
+                warn!("Failed to read verified file index {:?}, starting with an empty index: {}", path, err);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+
+        VerifiedFileIndex { path, files: Mutex::new(files), store_lock: tokio::sync::Mutex::new(()) }
+    }
+
+    /// Stores are serialized and write a temporary file first, so an interrupted store keeps the previous index
+    pub async fn store(&self) -> Result<()> {
+        let _guard = self.store_lock.lock().await;
+        let content = serde_json::to_string(&*self.files.lock().unwrap())?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        let temp_path = self.path.with_extension("json.tmp");
+        fs::write(&temp_path, content).await?;
+        fs::rename(&temp_path, &self.path).await?;
+        Ok(())
+    }
+
+    /// Checks whether the file has the expected SHA1. Unchanged files which were verified before are not hashed again,
+    /// unless `full_verify` is set. Files that match are recorded in the index.
+    pub async fn verify_sha1(&self, path: &Path, sha1: &str, full_verify: bool) -> Result<bool> {
+        let (size, modified) = VerifiedFile::metadata(path).await?;
+
+        if !full_verify {
+            let indexed = self.files.lock().unwrap().get(path).is_some_and(|file| {
+                file.size == size && file.modified == modified && file.sha1 == sha1
+            });
+            if indexed {
+                debug!("Skipping hash of unchanged file {:?}", path);
+                return Ok(true);
+            }
+        }
+
+        let hash = sha1sum_blocking(path.to_path_buf()).await?;
+        let mut files = self.files.lock().unwrap();
+        if hash == sha1 {
+            files.insert(path.to_path_buf(), VerifiedFile { size, modified, sha1: hash });
+            Ok(true)
+        } else {
+            files.remove(path);
+            Ok(false)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::utils::sha1sum;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("nrc-verified-files-{}", Uuid::new_v4()))
+    }
+
+    /// Writes the content and sets the modification time, so changes are only seen through the metadata the test picks
+    fn write(path: &Path, content: &str, modified: SystemTime) {
+        std::fs::write(path, content).unwrap();
+        std::fs::File::options().write(true).open(path).unwrap().set_modified(modified).unwrap();
+    }
+
+    fn time(seconds: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(seconds)
+    }
+
+    async fn setup() -> (PathBuf, VerifiedFileIndex, PathBuf, String) {
+        let dir = temp_dir();
+        fs::create_dir_all(&dir).await.unwrap();
+        let file = dir.join("library.jar");
+        write(&file, "library", time(1_000));
+        let sha1 = sha1sum(&file).unwrap();
+        (dir.clone(), VerifiedFileIndex::load(dir.join("verified_files.json")), file, sha1)
+    }
+
+    #[tokio::test]
+    async fn unchanged_files_are_not_hashed_again() {
+        let (dir, index, file, sha1) = setup().await;
+        assert!(index.verify_sha1(&file, &sha1, false).await.unwrap());
+
+        // Same size and modification time, only a new hash would notice the change
+        write(&file, "LIBRARY", time(1_000));
+        assert!(index.verify_sha1(&file, &sha1, false).await.unwrap());
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn changed_files_are_hashed_again() {
+        let (dir, index, file, sha1) = setup().await;
+        assert!(index.verify_sha1(&file, &sha1, false).await.unwrap());
+
+        write(&file, "LIBRARY", time(2_000));
+        assert!(!index.verify_sha1(&file, &sha1, false).await.unwrap());
+
+        write(&file, "library", time(1_000));
+        assert!(index.verify_sha1(&file, &sha1, false).await.unwrap());
+        write(&file, "library!", time(1_000));
+        assert!(!index.verify_sha1(&file, &sha1, false).await.unwrap());
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn full_verify_ignores_the_index() {
+        let (dir, index, file, sha1) = setup().await;
+        assert!(index.verify_sha1(&file, &sha1, false).await.unwrap());
+
+        write(&file, "LIBRARY", time(1_000));
+        assert!(!index.verify_sha1(&file, &sha1, true).await.unwrap());
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn mismatching_files_are_removed_from_the_index() {
+        let (dir, index, file, sha1) = setup().await;
+        assert!(index.verify_sha1(&file, &sha1, false).await.unwrap());
+        assert!(index.files.lock().unwrap().contains_key(&file));
+
+        assert!(!index.verify_sha1(&file, "0000000000000000000000000000000000000000", false).await.unwrap());
+        assert!(!index.files.lock().unwrap().contains_key(&file));
+
+        // Without the entry, the changed file isn't accepted by its old metadata
+        write(&file, "LIBRARY", time(1_000));
+        assert!(!index.verify_sha1(&file, &sha1, false).await.unwrap());
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn stored_index_is_loaded_again() {
+        let (dir, index, file, sha1) = setup().await;
+        assert!(index.verify_sha1(&file, &sha1, false).await.unwrap());
+        index.store().await.unwrap();
+        assert!(!index.path.with_extension("json.tmp").exists());
+
+        let loaded = VerifiedFileIndex::load(index.path.clone());
+        assert_eq!(loaded.files.lock().unwrap().get(&file), index.files.lock().unwrap().get(&file));
+
+        fs::remove_dir_all(&dir).await.unwrap();
+    }
+}
@@ -74,8 +74,21 @@ export async function getClientInstances() {
 export async function runClient(branch, checkedForNewBranch = false) {
   let options = get(launcherOptions);
 
+  let launcherProfiles = get(profiles);
+  let launcherProfile;
+  if (options.experimentalMode) {
+    const activeProfileId = launcherProfiles.selectedExperimentalProfiles[branch];
+    launcherProfile = launcherProfiles.experimentalProfiles.find(p => p.id === activeProfileId);
+  } else {
+    const activeProfileId = launcherProfiles.selectedMainProfiles[branch];
+    launcherProfile = launcherProfiles.mainProfiles.find(p => p.id === activeProfileId);
+  }
+
+  // Profiles can be bound to an account, otherwise the default account is used
+  const accountId = launcherProfile?.accountId ?? null;
+
   if (!options.multipleInstances) {
-    let instance = get(clientInstances).find(value => value.branch === branch);
+    let instance = get(clientInstances).find(value => value.branch === branch && (accountId == null || value.account?.id === accountId));
     if (instance) {
       await push(`/start-progress/` + instance.id);
       return;
@@ -107,7 +120,6 @@ export async function runClient(branch, checkedForNewBranch = false) {
 
   noriskLog("Client started");
 
-  let launcherProfiles = get(profiles);
   let installedMods = [];
 
   if (options.experimentalMode) {
@@ -118,15 +130,6 @@ export async function runClient(branch, checkedForNewBranch = false) {
 
   await saveOptions(false);
 
-  let launcherProfile;
-  if (options.experimentalMode) {
-    const activeProfileId = launcherProfiles.selectedExperimentalProfiles[branch];
-    launcherProfile = launcherProfiles.experimentalProfiles.find(p => p.id === activeProfileId);
-  } else {
-    const activeProfileId = launcherProfiles.selectedMainProfiles[branch];
-    launcherProfile = launcherProfiles.mainProfiles.find(p => p.id === activeProfileId);
-  }
-
   launcherProfile?.mods?.forEach(mod => {
     noriskLog(`Pushing Mod: ${JSON.stringify(mod.value)}`);
     installedMods.push(mod.value);
@@ -144,6 +147,7 @@ export async function runClient(branch, checkedForNewBranch = false) {
     options: options,
     forceServer: get(forceServer).length > 0 ? get(forceServer) : null,
     mods: installedMods,
+    accountId: accountId,
   }).then((uuid) => {
     noriskLog(`Started Instance ${uuid}`);
     //thats not needed anymore as we emit the event beforehand
@@ -161,6 +165,33 @@ export async function runClient(branch, checkedForNewBranch = false) {
   await fetchDefaultUserOrError(false);
 }
 
+/** Launches an unmodded Minecraft version, every version gets its own game directory */
+export async function runVanillaClient(versionId, accountId = null) {
+  let options = get(launcherOptions);
+  const branch = `vanilla-${versionId}`;
+
+  if (!options.multipleInstances) {
+    let instance = get(clientInstances).find(value => value.branch === branch && (accountId == null || value.account?.id === accountId));
+    if (instance) {
+      await push(`/start-progress/` + instance.id);
+      return;
+    }
+  }
+
+  noriskLog(`Starting vanilla ${versionId}`);
+
+  await invoke("run_vanilla_client", {
+    versionId: versionId,
+    options: options,
+    accountId: accountId,
+  }).then((uuid) => {
+    noriskLog(`Started Instance ${uuid}`);
+  }).catch(error => {
+    pop();
+    addNotification(get(translations).app.notification.failedToRunClient.replace("{error}", error));
+  });
+}
+
 export async function stopClient(instanceId) {
   push("/");
   await invoke("terminate", {